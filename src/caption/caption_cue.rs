@@ -0,0 +1,7 @@
+// text shown on screen from start until end, in milliseconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptionCue {
+    pub start: u64,
+    pub end: u64,
+    pub text: String    // rows separated by '\n'
+}
@@ -0,0 +1,7 @@
+// which caption service of the cc_data to decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionSource {
+    Cea608Field1,   // data channel 1 of field 1 (CC1)
+    Cea608Field2,   // data channel 1 of field 2 (CC3)
+    Cea708Service1  // primary caption service of the DTVCC packets
+}
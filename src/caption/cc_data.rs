@@ -0,0 +1,91 @@
+use crate::h264::{sei_nalu::SeiNalu, sei_payload::SeiPayload, user_data_registered_itu_t_t35::UserDataRegisteredItuTT35};
+
+// one cc_data triplet of ATSC A/53 closed caption data; cc_type 0 and 1 carry CEA-608 byte pairs
+// of field 1 and 2, cc_type 2 and 3 carry a CEA-708 DTVCC packet (3 starts a new one)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcData {
+    pub cc_valid: bool,
+    pub cc_type: u8,
+    pub cc_data_1: u8,
+    pub cc_data_2: u8
+}
+
+impl CcData {
+    // cc_data triplets of every GA94 user_data_registered_itu_t_t35 message of a SEI NAL unit
+    pub fn read_sei(sei: &SeiNalu) -> Vec<CcData> {
+        sei.sei_messages.iter()
+            .filter_map(|sei_message| match &sei_message.payload {
+                SeiPayload::UserDataRegisteredItuTT35(user_data) => Some(CcData::read_atsc_a53(user_data)),
+                _ => None
+            })
+            .flatten()
+            .collect()
+    }
+
+    // ATSC1_data() with user_data_type_code 0x03; anything else carries no captions
+    pub fn read_atsc_a53(user_data: &UserDataRegisteredItuTT35) -> Vec<CcData> {
+        let payload = &user_data.itu_t_t35_payload_byte;
+        // country code United States, provider code ATSC, user_identifier "GA94"
+        if user_data.itu_t_t35_country_code != 0xB5 || payload.len() < 9 || payload[..7] != [0x00, 0x31, b'G', b'A', b'9', b'4', 0x03] {
+            return vec![];
+        }
+        let process_cc_data_flag = payload[7] & 0x40 != 0;
+        if !process_cc_data_flag {
+            return vec![];
+        }
+        let cc_count = usize::from(payload[7] & 0x1F);
+        // payload[8] is em_data
+        payload[9..].chunks_exact(3)
+            .take(cc_count)
+            .map(|triplet| CcData {
+                cc_valid: triplet[0] & 0x04 != 0,
+                cc_type: triplet[0] & 0x03,
+                cc_data_1: triplet[1],
+                cc_data_2: triplet[2]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::h264::user_data_registered_itu_t_t35::UserDataRegisteredItuTT35;
+
+    use super::CcData;
+
+    fn get_user_data(payload: &[u8]) -> UserDataRegisteredItuTT35 {
+        UserDataRegisteredItuTT35 {
+            itu_t_t35_country_code: 0xB5,
+            itu_t_t35_country_code_extension_byte: 0,
+            itu_t_t35_payload_byte: payload.to_vec()
+        }
+    }
+
+    #[test]
+    fn read_atsc_a53() {
+        // process_cc_data_flag with cc_count 2, em_data, then a field 1 pair and an invalid DTVCC packet start
+        let header = [0x00, 0x31, b'G', b'A', b'9', b'4', 0x03, 0xC2, 0xFF];
+        let payload = [&header[..], &[0xFC, 0x94, 0x20, 0xFB, 0x02, 0x21, 0xFA, 0x00, 0x00]].concat();
+        assert_eq!(CcData::read_atsc_a53(&get_user_data(&payload)), [
+            CcData {
+                cc_valid: true,
+                cc_type: 0,
+                cc_data_1: 0x94,
+                cc_data_2: 0x20
+            },
+            CcData {
+                cc_valid: false,
+                cc_type: 3,
+                cc_data_1: 0x02,
+                cc_data_2: 0x21
+            }
+        ]);
+        // without process_cc_data_flag, or with another user_identifier, there are no captions
+        let mut payload_without_flag = payload.clone();
+        payload_without_flag[7] = 0x82;
+        assert!(CcData::read_atsc_a53(&get_user_data(&payload_without_flag)).is_empty());
+        let mut payload_of_afd = payload;
+        payload_of_afd[2..6].copy_from_slice(b"DTG1");
+        assert!(CcData::read_atsc_a53(&get_user_data(&payload_of_afd)).is_empty());
+    }
+}
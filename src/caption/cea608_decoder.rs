@@ -0,0 +1,343 @@
+use std::{mem, ops::Range};
+
+use super::{caption_cue::CaptionCue, cue_builder::CueBuilder};
+
+const ROWS: usize = 15;
+const COLUMNS: usize = 32;
+
+// 0x11 0x30 to 0x3F
+const SPECIAL_CHARACTERS: [char; 16] = ['®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û'];
+// 0x12 0x20 to 0x3F, then 0x13 0x20 to 0x3F
+const EXTENDED_CHARACTERS: [char; 64] = [
+    'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '—', '©', '℠', '•', '“', '”',
+    'À', 'Â', 'Ç', 'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+    'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~',
+    'Ä', 'ä', 'Ö', 'ö', 'ß', '¥', '¤', '¦', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘'
+];
+// first byte of a preamble address code without the channel bit, for rows 1 to 15
+const PREAMBLE_ROWS: [u8; ROWS] = [0x11, 0x11, 0x12, 0x12, 0x15, 0x15, 0x16, 0x16, 0x17, 0x17, 0x10, 0x13, 0x13, 0x14, 0x14];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaptionMode {
+    PopOn,
+    RollUp(usize),
+    PaintOn
+}
+
+// decodes data channel 1 of one field of CEA-608 byte pairs into cues
+pub struct Cea608Decoder {
+    mode: CaptionMode,
+    displayed: [[char; COLUMNS]; ROWS],
+    non_displayed: [[char; COLUMNS]; ROWS],
+    row: usize,
+    column: usize,
+    text_mode: bool,                    // text service data after TR or RTD is not captions
+    other_channel: bool,                // data after control codes of data channel 2 is skipped
+    last_control: Option<(u8, u8)>,     // control codes are usually sent twice
+    cue_builder: CueBuilder
+}
+
+impl Cea608Decoder {
+    pub fn new() -> Self {
+        Cea608Decoder {
+            mode: CaptionMode::PopOn,
+            displayed: [[' '; COLUMNS]; ROWS],
+            non_displayed: [[' '; COLUMNS]; ROWS],
+            row: ROWS - 1,
+            column: 0,
+            text_mode: false,
+            other_channel: false,
+            last_control: None,
+            cue_builder: CueBuilder::new()
+        }
+    }
+
+    // one byte pair with its parity bits, at a presentation time in milliseconds
+    pub fn decode(&mut self, cc_data_1: u8, cc_data_2: u8, time: u64) {
+        let (byte_1, byte_2) = (cc_data_1 & 0x7F, cc_data_2 & 0x7F);
+        if byte_1 == 0 && byte_2 == 0 {
+            return;
+        }
+        if (0x10..=0x1F).contains(&byte_1) {
+            if self.last_control == Some((byte_1, byte_2)) {
+                self.last_control = None;
+                return;
+            }
+            self.last_control = Some((byte_1, byte_2));
+            self.other_channel = byte_1 & 0x08 != 0;
+            if !self.other_channel {
+                self.decode_control(byte_1 & !0x08, byte_2, time);
+            }
+            return;
+        }
+        self.last_control = None;
+        if self.other_channel {
+            return;
+        }
+        for byte in [byte_1, byte_2] {
+            if byte >= 0x20 {
+                self.write_char(Cea608Decoder::get_basic_char(byte), time);
+            }
+        }
+    }
+
+    // cues up to time, the end of the last sample
+    pub fn finish(mut self, time: u64) -> Vec<CaptionCue> {
+        self.commit(time);
+        self.cue_builder.finish(time)
+    }
+
+    fn decode_control(&mut self, code: u8, byte_2: u8, time: u64) {
+        // text written to the screen since the previous control code becomes a cue
+        if self.mode != CaptionMode::PopOn {
+            self.commit(time);
+        }
+        match (code, byte_2) {
+            (_, 0x40..=0x7F) => self.decode_preamble_address(code, byte_2),
+            // miscellaneous control codes use 0x14 in field 1 and 0x15 in field 2
+            (0x14 | 0x15, 0x20..=0x2F) => self.decode_misc_control(byte_2, time),
+            (0x17, 0x21..=0x23) => self.column = (self.column + usize::from(byte_2 - 0x20)).min(COLUMNS - 1),
+            // mid-row codes change the style and show as a space
+            (0x11, 0x20..=0x2F) => self.write_char(' ', time),
+            (0x11, 0x30..=0x3F) => self.write_char(SPECIAL_CHARACTERS[usize::from(byte_2 - 0x30)], time),
+            // extended characters replace the basic character sent before them as a fallback
+            (0x12 | 0x13, 0x20..=0x3F) => {
+                self.column = self.column.saturating_sub(1);
+                self.write_char(EXTENDED_CHARACTERS[usize::from(code - 0x12) * 32 + usize::from(byte_2 - 0x20)], time);
+            },
+            _ => ()
+        }
+    }
+
+    fn decode_preamble_address(&mut self, code: u8, byte_2: u8) {
+        let second_row = byte_2 >= 0x60;
+        let row = match PREAMBLE_ROWS.iter().enumerate().filter(|(_, &row_code)| row_code == code).nth(usize::from(second_row)) {
+            Some((row, _)) => row,
+            None => return
+        };
+        if let CaptionMode::RollUp(rows) = self.mode {
+            // the roll-up window moves to the new base row
+            if row != self.row {
+                let window = Cea608Decoder::get_roll_up_window(self.row, rows);
+                let contents: Vec<[char; COLUMNS]> = self.displayed[window].to_vec();
+                self.displayed = [[' '; COLUMNS]; ROWS];
+                let new_window = Cea608Decoder::get_roll_up_window(row, rows);
+                let kept = contents.len().min(new_window.len());
+                self.displayed[new_window.end - kept..new_window.end].copy_from_slice(&contents[contents.len() - kept..]);
+            }
+        }
+        self.row = row;
+        self.column = if byte_2 & 0x10 != 0 { usize::from(byte_2 & 0x0E) * 2 } else { 0 };
+    }
+
+    fn decode_misc_control(&mut self, byte_2: u8, time: u64) {
+        match byte_2 {
+            // RCL
+            0x20 => {
+                self.mode = CaptionMode::PopOn;
+                self.text_mode = false;
+            },
+            // BS
+            0x21 => {
+                self.column = self.column.saturating_sub(1);
+                let (row, column) = (self.row, self.column);
+                self.get_memory(time)[row][column] = ' ';
+            },
+            // DER
+            0x24 => {
+                let (row, column) = (self.row, self.column);
+                self.get_memory(time)[row][column..].fill(' ');
+            },
+            // RU2, RU3, RU4
+            0x25..=0x27 => {
+                let rows = usize::from(byte_2 - 0x23);
+                if !matches!(self.mode, CaptionMode::RollUp(_)) {
+                    self.displayed = [[' '; COLUMNS]; ROWS];
+                    self.non_displayed = [[' '; COLUMNS]; ROWS];
+                    self.row = ROWS - 1;
+                    self.commit(time);
+                }
+                self.mode = CaptionMode::RollUp(rows);
+                self.text_mode = false;
+                self.column = 0;
+            },
+            // RDC
+            0x29 => {
+                self.mode = CaptionMode::PaintOn;
+                self.text_mode = false;
+            },
+            // TR, RTD
+            0x2A | 0x2B => self.text_mode = true,
+            // EDM
+            0x2C => {
+                self.displayed = [[' '; COLUMNS]; ROWS];
+                self.commit(time);
+            },
+            // CR
+            0x2D => {
+                if let CaptionMode::RollUp(rows) = self.mode {
+                    self.commit(time);
+                    let window = Cea608Decoder::get_roll_up_window(self.row, rows);
+                    let (top, bottom) = (window.start, window.end);
+                    self.displayed.copy_within(top + 1..bottom, top);
+                    self.displayed[bottom - 1] = [' '; COLUMNS];
+                    self.column = 0;
+                }
+            },
+            // ENM
+            0x2E => self.non_displayed = [[' '; COLUMNS]; ROWS],
+            // EOC
+            0x2F => {
+                mem::swap(&mut self.displayed, &mut self.non_displayed);
+                self.mode = CaptionMode::PopOn;
+                self.commit(time);
+            },
+            _ => ()
+        }
+    }
+
+    // pop-on captions are built off screen, the other modes write straight to the screen
+    fn get_memory(&mut self, time: u64) -> &mut [[char; COLUMNS]; ROWS] {
+        match self.mode {
+            CaptionMode::PopOn => &mut self.non_displayed,
+            CaptionMode::RollUp(_) | CaptionMode::PaintOn => {
+                self.cue_builder.mark(time);
+                &mut self.displayed
+            }
+        }
+    }
+
+    fn write_char(&mut self, c: char, time: u64) {
+        if self.text_mode {
+            return;
+        }
+        let (row, column) = (self.row, self.column);
+        self.get_memory(time)[row][column] = c;
+        // past the last column, characters replace the one in it
+        self.column = (column + 1).min(COLUMNS - 1);
+    }
+
+    fn commit(&mut self, time: u64) {
+        let text = self.displayed.iter()
+            .map(|row| row.iter().collect::<String>().trim().to_owned())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.cue_builder.commit(text, time);
+    }
+
+    // rows of a roll-up window with its base row at the bottom
+    fn get_roll_up_window(base_row: usize, rows: usize) -> Range<usize> {
+        (base_row + 1).saturating_sub(rows)..base_row + 1
+    }
+
+    // the basic character set is ASCII except for a few accented letters
+    fn get_basic_char(byte: u8) -> char {
+        match byte {
+            0x2A => 'á',
+            0x5C => 'é',
+            0x5E => 'í',
+            0x5F => 'ó',
+            0x60 => 'ú',
+            0x7B => 'ç',
+            0x7C => '÷',
+            0x7D => 'Ñ',
+            0x7E => 'ñ',
+            0x7F => '█',
+            _ => char::from(byte)
+        }
+    }
+}
+
+impl Default for Cea608Decoder {
+    fn default() -> Self {
+        Cea608Decoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::caption::caption_cue::CaptionCue;
+
+    use super::Cea608Decoder;
+
+    // control codes are sent twice, with the odd parity bit of every byte set
+    fn decode(decoder: &mut Cea608Decoder, pairs: &[(u8, u8)], time: u64) {
+        let with_parity = |byte: u8| if byte.count_ones() % 2 == 1 { byte } else { byte | 0x80 };
+        for &(byte_1, byte_2) in pairs {
+            let repeats = if (0x10..=0x1F).contains(&byte_1) { 2 } else { 1 };
+            for _ in 0..repeats {
+                decoder.decode(with_parity(byte_1), with_parity(byte_2), time);
+            }
+        }
+    }
+
+    fn get_pairs(text: &str) -> Vec<(u8, u8)> {
+        text.as_bytes().chunks(2).map(|pair| (pair[0], pair.get(1).copied().unwrap_or(0))).collect()
+    }
+
+    fn get_cue(start: u64, end: u64, text: &str) -> CaptionCue {
+        CaptionCue {
+            start,
+            end,
+            text: text.to_owned()
+        }
+    }
+
+    #[test]
+    fn decode_pop_on() {
+        let mut decoder = Cea608Decoder::new();
+        // RCL, row 14 and 15, built off screen until EOC
+        decode(&mut decoder, &[(0x14, 0x20), (0x14, 0x40)], 0);
+        decode(&mut decoder, &get_pairs("HELLO"), 0);
+        decode(&mut decoder, &[(0x14, 0x60)], 0);
+        decode(&mut decoder, &get_pairs("WORLD"), 0);
+        decode(&mut decoder, &[(0x14, 0x2F)], 1000);
+        // the next caption replaces it, then EDM clears the screen
+        decode(&mut decoder, &[(0x14, 0x20), (0x14, 0x2E), (0x14, 0x60)], 1500);
+        decode(&mut decoder, &get_pairs("BYE"), 1500);
+        decode(&mut decoder, &[(0x14, 0x2F)], 2000);
+        decode(&mut decoder, &[(0x14, 0x2C)], 3000);
+        assert_eq!(decoder.finish(4000), [get_cue(1000, 2000, "HELLO\nWORLD"), get_cue(2000, 3000, "BYE")]);
+    }
+
+    #[test]
+    fn decode_roll_up() {
+        let mut decoder = Cea608Decoder::new();
+        // RU2 on row 15; each CR scrolls the rows up
+        decode(&mut decoder, &[(0x14, 0x25), (0x14, 0x60)], 0);
+        decode(&mut decoder, &get_pairs("ONE"), 100);
+        decode(&mut decoder, &[(0x14, 0x2D)], 500);
+        decode(&mut decoder, &get_pairs("TWO"), 600);
+        decode(&mut decoder, &[(0x14, 0x2D)], 900);
+        decode(&mut decoder, &get_pairs("THREE"), 1000);
+        assert_eq!(decoder.finish(2000), [
+            get_cue(100, 600, "ONE"),
+            get_cue(600, 1000, "ONE\nTWO"),
+            get_cue(1000, 2000, "TWO\nTHREE")
+        ]);
+    }
+
+    #[test]
+    fn decode_paint_on() {
+        let mut decoder = Cea608Decoder::new();
+        // RDC on row 1, then BS erases the last character
+        decode(&mut decoder, &[(0x14, 0x29), (0x11, 0x40)], 0);
+        decode(&mut decoder, &get_pairs("HIX"), 100);
+        decode(&mut decoder, &[(0x14, 0x21)], 200);
+        decode(&mut decoder, &[(0x14, 0x2C)], 1000);
+        assert_eq!(decoder.finish(2000), [get_cue(100, 200, "HIX"), get_cue(200, 1000, "HI")]);
+    }
+
+    #[test]
+    fn decode_special_and_extended_characters() {
+        let mut decoder = Cea608Decoder::new();
+        decode(&mut decoder, &[(0x14, 0x20), (0x14, 0x60)], 0);
+        // É replaces the E sent before it, then a music note and the basic á
+        decode(&mut decoder, &[(b'C', b'A'), (b'F', b'E'), (0x12, 0x21), (0x11, 0x37), (0x2A, 0x00)], 0);
+        // data channel 2 is skipped
+        decode(&mut decoder, &[(0x1C, 0x20), (b'N', b'O')], 0);
+        decode(&mut decoder, &[(0x14, 0x2F)], 1000);
+        assert_eq!(decoder.finish(2000), [get_cue(1000, 2000, "CAFÉ♪á")]);
+    }
+}
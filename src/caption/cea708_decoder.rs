@@ -0,0 +1,432 @@
+use std::mem;
+
+use super::{caption_cue::CaptionCue, cue_builder::CueBuilder};
+
+const WINDOWS: usize = 8;
+
+// decodes caption service 1 of CEA-708 DTVCC packets into cues; the text of the visible windows
+// is what the screen shows
+pub struct Cea708Decoder {
+    packet: Vec<u8>,                            // DTVCC packet being assembled
+    current_window: usize,
+    window_defined: [bool; WINDOWS],
+    window_visible: [bool; WINDOWS],
+    window_row_count: [usize; WINDOWS],
+    window_rows: [Vec<Vec<char>>; WINDOWS],
+    pen_row: [usize; WINDOWS],
+    pen_column: [usize; WINDOWS],
+    cue_builder: CueBuilder
+}
+
+impl Cea708Decoder {
+    pub fn new() -> Self {
+        Cea708Decoder {
+            packet: vec![],
+            current_window: 0,
+            window_defined: [false; WINDOWS],
+            window_visible: [false; WINDOWS],
+            window_row_count: [0; WINDOWS],
+            window_rows: Default::default(),
+            pen_row: [0; WINDOWS],
+            pen_column: [0; WINDOWS],
+            cue_builder: CueBuilder::new()
+        }
+    }
+
+    // one cc_data pair of type 2 or 3, at a presentation time in milliseconds
+    pub fn decode(&mut self, cc_type: u8, cc_data_1: u8, cc_data_2: u8, time: u64) {
+        match cc_type {
+            // DTVCC_PACKET_START; a packet cut short by the next one is decoded as far as it goes
+            3 => {
+                if !self.packet.is_empty() {
+                    self.decode_packet(time);
+                }
+                self.packet = vec![cc_data_1, cc_data_2];
+            },
+            // DTVCC_PACKET_DATA without a packet start is lost
+            2 if !self.packet.is_empty() => self.packet.extend([cc_data_1, cc_data_2]),
+            _ => return
+        }
+        if self.packet.len() >= Cea708Decoder::get_packet_size(&self.packet) {
+            self.decode_packet(time);
+        }
+    }
+
+    // cues up to time, the end of the last sample
+    pub fn finish(mut self, time: u64) -> Vec<CaptionCue> {
+        if !self.packet.is_empty() {
+            self.decode_packet(time);
+        }
+        self.commit(time);
+        self.cue_builder.finish(time)
+    }
+
+    // packet_size_code counts pairs of bytes, 0 meaning 64 pairs
+    fn get_packet_size(packet: &[u8]) -> usize {
+        match packet[0] & 0x3F {
+            0 => 128,
+            packet_size_code => usize::from(packet_size_code) * 2
+        }
+    }
+
+    fn decode_packet(&mut self, time: u64) {
+        let packet = mem::take(&mut self.packet);
+        let end = Cea708Decoder::get_packet_size(&packet).min(packet.len());
+        let mut i = 1;
+        while i < end {
+            let mut service_number = packet[i] >> 5;
+            let block_size = usize::from(packet[i] & 0x1F);
+            i += 1;
+            // null service block, the rest is padding
+            if service_number == 0 {
+                break;
+            }
+            if service_number == 7 {
+                match packet.get(i) {
+                    Some(&extended_service_number) => service_number = extended_service_number & 0x3F,
+                    None => break
+                }
+                i += 1;
+            }
+            let block_end = (i + block_size).min(end);
+            if service_number == 1 && i < block_end {
+                self.decode_service_block(&packet[i..block_end], time);
+            }
+            i = block_end;
+        }
+    }
+
+    fn decode_service_block(&mut self, block: &[u8], time: u64) {
+        let mut i = 0;
+        while i < block.len() {
+            let code = block[i];
+            i += 1;
+            let parameters = &block[i..];
+            i += match code {
+                // C0: ETX, BS, FF, CR, HCR
+                0x03 => {
+                    self.commit(time);
+                    0
+                },
+                0x08 => {
+                    self.backspace();
+                    0
+                },
+                0x0C => {
+                    self.clear_window(self.current_window);
+                    self.commit(time);
+                    0
+                },
+                0x0D => {
+                    self.carriage_return(time);
+                    0
+                },
+                0x0E => {
+                    let window = self.current_window;
+                    if let Some(row) = self.window_rows[window].get_mut(self.pen_row[window]) {
+                        row.clear();
+                    }
+                    self.pen_column[window] = 0;
+                    0
+                },
+                // EXT1 introduces a code of the extended code space
+                0x10 => match parameters.first() {
+                    Some(&extended_code) => 1 + self.decode_extended_code(extended_code, &parameters[1..], time),
+                    None => 0
+                },
+                0x00..=0x0F => 0,
+                0x11..=0x17 => 1,
+                // P16 carries a 16-bit character
+                0x18..=0x1F => {
+                    if let [high, low, ..] = *parameters {
+                        if let Some(c) = char::from_u32(u32::from(u16::from_be_bytes([high, low]))) {
+                            self.write_char(c, time);
+                        }
+                    }
+                    2
+                },
+                // G0 is ASCII except for the music note
+                0x7F => {
+                    self.write_char('♪', time);
+                    0
+                },
+                0x20..=0x7E => {
+                    self.write_char(char::from(code), time);
+                    0
+                },
+                // C1 window commands
+                0x80..=0x87 => {
+                    self.current_window = usize::from(code - 0x80);
+                    0
+                },
+                0x88..=0x8C => {
+                    if let Some(&window_bitmap) = parameters.first() {
+                        self.decode_window_bitmap_command(code, window_bitmap, time);
+                    }
+                    1
+                },
+                // DLY
+                0x8D => 1,
+                // RST
+                0x8F => {
+                    self.reset();
+                    self.commit(time);
+                    0
+                },
+                // SPA
+                0x90 => 2,
+                // SPC
+                0x91 => 3,
+                // SPL
+                0x92 => {
+                    if let [row, column, ..] = *parameters {
+                        let window = self.current_window;
+                        self.pen_row[window] = usize::from(row & 0x0F);
+                        self.pen_column[window] = usize::from(column & 0x3F);
+                    }
+                    2
+                },
+                // SWA
+                0x97 => 4,
+                // DF0 to DF7
+                0x98..=0x9F => {
+                    if parameters.len() >= 6 {
+                        self.define_window(usize::from(code - 0x98), parameters, time);
+                    }
+                    6
+                },
+                // DLC and reserved C1 codes
+                0x8E | 0x93..=0x96 => 0,
+                // G1 is Latin-1
+                0xA0..=0xFF => {
+                    self.write_char(char::from(code), time);
+                    0
+                }
+            };
+        }
+    }
+
+    // number of bytes after the extended code that belong to it
+    fn decode_extended_code(&mut self, extended_code: u8, parameters: &[u8], time: u64) -> usize {
+        match extended_code {
+            // C2
+            0x00..=0x07 => 0,
+            0x08..=0x0F => 1,
+            0x10..=0x17 => 2,
+            0x18..=0x1F => 3,
+            // G2
+            0x20..=0x7F => {
+                if let Some(c) = Cea708Decoder::get_g2_char(extended_code) {
+                    self.write_char(c, time);
+                }
+                0
+            },
+            // C3
+            0x80..=0x87 => 4,
+            0x88..=0x8F => 5,
+            0x90..=0x9F => parameters.first().map_or(0, |&length| 1 + usize::from(length & 0x3F)),
+            // G3 only has the [CC] icon
+            0xA0..=0xFF => 0
+        }
+    }
+
+    fn decode_window_bitmap_command(&mut self, code: u8, window_bitmap: u8, time: u64) {
+        for window in (0..WINDOWS).filter(|window| window_bitmap & (1 << window) != 0) {
+            match code {
+                // CLW
+                0x88 => self.clear_window(window),
+                // DSW
+                0x89 => self.window_visible[window] = self.window_defined[window],
+                // HDW
+                0x8A => self.window_visible[window] = false,
+                // TGW
+                0x8B => self.window_visible[window] = self.window_defined[window] && !self.window_visible[window],
+                // DLW
+                _ => {
+                    self.window_defined[window] = false;
+                    self.window_visible[window] = false;
+                    self.clear_window(window);
+                }
+            }
+        }
+        self.commit(time);
+    }
+
+    fn define_window(&mut self, window: usize, parameters: &[u8], time: u64) {
+        // redefining a window keeps its text
+        if !self.window_defined[window] {
+            self.window_defined[window] = true;
+            self.clear_window(window);
+        }
+        self.window_visible[window] = parameters[0] & 0x20 != 0;
+        self.window_row_count[window] = usize::from(parameters[3] & 0x0F) + 1;
+        self.current_window = window;
+        self.commit(time);
+    }
+
+    fn reset(&mut self) {
+        for window in 0..WINDOWS {
+            self.window_defined[window] = false;
+            self.window_visible[window] = false;
+            self.clear_window(window);
+        }
+        self.current_window = 0;
+    }
+
+    fn clear_window(&mut self, window: usize) {
+        self.window_rows[window].clear();
+        self.pen_row[window] = 0;
+        self.pen_column[window] = 0;
+    }
+
+    fn write_char(&mut self, c: char, time: u64) {
+        let window = self.current_window;
+        if !self.window_defined[window] {
+            return;
+        }
+        if self.window_visible[window] {
+            self.cue_builder.mark(time);
+        }
+        let (pen_row, pen_column) = (self.pen_row[window], self.pen_column[window]);
+        let rows = &mut self.window_rows[window];
+        if rows.len() <= pen_row {
+            rows.resize(pen_row + 1, vec![]);
+        }
+        let row = &mut rows[pen_row];
+        if row.len() <= pen_column {
+            row.resize(pen_column + 1, ' ');
+        }
+        row[pen_column] = c;
+        self.pen_column[window] += 1;
+    }
+
+    fn backspace(&mut self) {
+        let window = self.current_window;
+        if self.pen_column[window] == 0 {
+            return;
+        }
+        self.pen_column[window] -= 1;
+        let (pen_row, pen_column) = (self.pen_row[window], self.pen_column[window]);
+        if let Some(c) = self.window_rows[window].get_mut(pen_row).and_then(|row| row.get_mut(pen_column)) {
+            *c = ' ';
+        }
+    }
+
+    // moves the pen to the next row, scrolling the window up from its last row
+    fn carriage_return(&mut self, time: u64) {
+        let window = self.current_window;
+        if self.window_visible[window] {
+            self.commit(time);
+        }
+        self.pen_column[window] = 0;
+        if self.pen_row[window] + 1 < self.window_row_count[window] {
+            self.pen_row[window] += 1;
+        } else if !self.window_rows[window].is_empty() {
+            self.window_rows[window].remove(0);
+        }
+    }
+
+    fn commit(&mut self, time: u64) {
+        let text = (0..WINDOWS)
+            .filter(|&window| self.window_defined[window] && self.window_visible[window])
+            .flat_map(|window| &self.window_rows[window])
+            .map(|row| row.iter().collect::<String>().trim().to_owned())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.cue_builder.commit(text, time);
+    }
+
+    // G2 characters, None for unassigned codes
+    fn get_g2_char(extended_code: u8) -> Option<char> {
+        Some(match extended_code {
+            0x20 | 0x21 => ' ',
+            0x25 => '…',
+            0x2A => 'Š',
+            0x2C => 'Œ',
+            0x30 => '█',
+            0x31 => '‘',
+            0x32 => '’',
+            0x33 => '“',
+            0x34 => '”',
+            0x35 => '•',
+            0x39 => '™',
+            0x3A => 'š',
+            0x3C => 'œ',
+            0x3D => '℠',
+            0x3F => 'Ÿ',
+            0x76 => '⅛',
+            0x77 => '⅜',
+            0x78 => '⅝',
+            0x79 => '⅞',
+            0x7A => '│',
+            0x7B => '┐',
+            0x7C => '└',
+            0x7D => '─',
+            0x7E => '┘',
+            0x7F => '┌',
+            _ => return None
+        })
+    }
+}
+
+impl Default for Cea708Decoder {
+    fn default() -> Self {
+        Cea708Decoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::caption::caption_cue::CaptionCue;
+
+    use super::Cea708Decoder;
+
+    // a DTVCC packet of one service 1 block, as cc_data pairs of type 3 then 2
+    fn get_packet(sequence_number: u8, block: &[u8]) -> Vec<(u8, u8, u8)> {
+        let mut packet = vec![0, 0x20 | block.len() as u8];
+        packet.extend(block);
+        if packet.len() % 2 != 0 {
+            packet.push(0);
+        }
+        packet[0] = sequence_number << 6 | (packet.len() / 2) as u8;
+        packet.chunks(2).enumerate().map(|(index, pair)| (if index == 0 { 3 } else { 2 }, pair[0], pair[1])).collect()
+    }
+
+    fn decode(decoder: &mut Cea708Decoder, cc_data: &[(u8, u8, u8)], time: u64) {
+        for &(cc_type, cc_data_1, cc_data_2) in cc_data {
+            decoder.decode(cc_type, cc_data_1, cc_data_2, time);
+        }
+    }
+
+    #[test]
+    fn decode_window_text() {
+        let mut decoder = Cea708Decoder::new();
+        // DF0 visible with 2 rows, text and ETX, then CLW clears window 0
+        decode(&mut decoder, &get_packet(0, &[0x98, 0x38, 0x00, 0x00, 0x01, 0x1F, 0x00, b'H', b'I', 0x0D, b'T', b'H', b'E', b'R', b'E', 0x03]), 500);
+        decode(&mut decoder, &get_packet(1, &[0x88, 0x01]), 2000);
+        // the first row alone is replaced within the same instant
+        assert_eq!(decoder.finish(3000), [CaptionCue {
+            start: 500,
+            end: 2000,
+            text: "HI\nTHERE".to_owned()
+        }]);
+    }
+
+    #[test]
+    fn decode_packet_split_across_samples() {
+        let mut decoder = Cea708Decoder::new();
+        // EXT1 with a G2 character, then P16 with a 16-bit one
+        let packet = get_packet(0, &[0x98, 0x38, 0x00, 0x00, 0x00, 0x1F, 0x00, 0x10, 0x25, 0x18, 0x26, 0x20]);
+        // nothing is decoded until the last pair of the packet arrives
+        decode(&mut decoder, &packet[..3], 0);
+        decode(&mut decoder, &packet[3..], 33);
+        // a packet without a start is lost
+        decode(&mut decoder, &[(2, 0x22, 0x88), (2, 0x01, 0x00)], 500);
+        assert_eq!(decoder.finish(1000), [CaptionCue {
+            start: 33,
+            end: 1000,
+            text: "…☠".to_owned()
+        }]);
+    }
+}
@@ -0,0 +1,87 @@
+use super::caption_cue::CaptionCue;
+
+// turns the changing text of a caption display into cues
+pub struct CueBuilder {
+    cues: Vec<CaptionCue>,
+    current: Option<(u64, String)>,     // start and text of the cue on screen
+    pending_since: Option<u64>          // earliest write straight to the screen since the last commit
+}
+
+impl CueBuilder {
+    pub fn new() -> Self {
+        CueBuilder {
+            cues: vec![],
+            current: None,
+            pending_since: None
+        }
+    }
+
+    // text written straight to the screen (roll-up, paint-on) shows up before it is committed
+    pub fn mark(&mut self, time: u64) {
+        self.pending_since.get_or_insert(time);
+    }
+
+    // the screen shows text from time on, or from the first pending write
+    pub fn commit(&mut self, text: String, time: u64) {
+        let start = self.pending_since.take().map_or(time, |pending_since| pending_since.min(time));
+        if self.current.as_ref().map_or(text.is_empty(), |(_, current_text)| *current_text == text) {
+            return;
+        }
+        // text replaced within the same instant never shows
+        if let Some((cue_start, cue_text)) = self.current.take().filter(|(cue_start, _)| start > *cue_start) {
+            self.cues.push(CaptionCue {
+                start: cue_start,
+                end: start,
+                text: cue_text
+            });
+        }
+        if !text.is_empty() {
+            self.current = Some((start, text));
+        }
+    }
+
+    pub fn finish(mut self, time: u64) -> Vec<CaptionCue> {
+        self.commit(String::new(), time);
+        self.cues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::caption::caption_cue::CaptionCue;
+
+    use super::CueBuilder;
+
+    #[test]
+    fn build_cues() {
+        let mut cue_builder = CueBuilder::new();
+        // text written from 100 on is committed at 300
+        cue_builder.mark(100);
+        cue_builder.mark(200);
+        cue_builder.commit("A".to_owned(), 300);
+        // the same text keeps its cue going
+        cue_builder.commit("A".to_owned(), 400);
+        // B is replaced by C at the same instant and never shows
+        cue_builder.commit("B".to_owned(), 500);
+        cue_builder.commit("C".to_owned(), 500);
+        cue_builder.commit(String::new(), 600);
+        cue_builder.commit("D".to_owned(), 700);
+        assert_eq!(cue_builder.finish(800), [
+            CaptionCue {
+                start: 100,
+                end: 500,
+                text: "A".to_owned()
+            },
+            CaptionCue {
+                start: 500,
+                end: 600,
+                text: "C".to_owned()
+            },
+            CaptionCue {
+                start: 700,
+                end: 800,
+                text: "D".to_owned()
+            }
+        ]);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod cc_data;
+pub mod caption_source;
+pub mod caption_cue;
+pub mod subtitle_format;
+pub mod cea608_decoder;
+pub mod cea708_decoder;
+mod cue_builder;
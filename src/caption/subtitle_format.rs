@@ -0,0 +1,41 @@
+use std::io::Write;
+
+use crate::error::Result;
+
+use super::caption_cue::CaptionCue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt
+}
+
+impl SubtitleFormat {
+    pub fn write(&self, cues: &[CaptionCue], wtr: &mut dyn Write) -> Result<()> {
+        if *self == SubtitleFormat::WebVtt {
+            write!(wtr, "WEBVTT\n\n")?;
+        }
+        for (index, cue) in cues.iter().enumerate() {
+            match self {
+                SubtitleFormat::Srt => {
+                    write!(wtr, "{}\n{} --> {}\n{}\n\n", index + 1, self.format_time(cue.start), self.format_time(cue.end), cue.text)?;
+                },
+                SubtitleFormat::WebVtt => {
+                    // cue text is markup, so its special characters are escaped
+                    let text = cue.text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+                    write!(wtr, "{} --> {}\n{}\n\n", self.format_time(cue.start), self.format_time(cue.end), text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // hh:mm:ss,mmm for SRT and hh:mm:ss.mmm for WebVTT
+    fn format_time(&self, time: u64) -> String {
+        let separator = match self {
+            SubtitleFormat::Srt => ',',
+            SubtitleFormat::WebVtt => '.'
+        };
+        format!("{:02}:{:02}:{:02}{}{:03}", time / 3_600_000, time / 60_000 % 60, time / 1000 % 60, separator, time % 1000)
+    }
+}
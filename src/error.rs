@@ -0,0 +1,110 @@
+use std::{error, fmt, io, num::TryFromIntError, result};
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    Io(io::Error),
+    Truncated,
+    Unsupported(String),
+    SpecViolation(String)
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub box_path: Vec<String>,      // outermost box first, e.g. ["moov", "trak", "mdia"]
+    pub nalu_index: Option<usize>,
+    pub offset: Option<u64>         // absolute offset of the innermost box or NALU being parsed
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            box_path: vec![],
+            nalu_index: None,
+            offset: None
+        }
+    }
+
+    pub fn truncated() -> Self {
+        Error::new(ErrorKind::Truncated)
+    }
+
+    pub fn unsupported(what: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Unsupported(what.into()))
+    }
+
+    pub fn spec_violation(what: impl Into<String>) -> Self {
+        Error::new(ErrorKind::SpecViolation(what.into()))
+    }
+
+    // called while unwinding out of nested boxes, so the path is built from the inside out
+    pub fn in_box(mut self, boxtype: &str) -> Self {
+        self.box_path.insert(0, boxtype.to_owned());
+        self
+    }
+
+    pub fn at_nalu(mut self, index: usize) -> Self {
+        self.nalu_index.get_or_insert(index);
+        self
+    }
+
+    // keeps the innermost offset if one has already been recorded
+    pub fn at_offset(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Error::truncated(),
+            _ => Error::new(ErrorKind::Io(err))
+        }
+    }
+}
+
+impl From<TryFromIntError> for Error {
+    fn from(_err: TryFromIntError) -> Self {
+        Error::spec_violation("value out of range")
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Io(err) => write!(f, "I/O error: {}", err),
+            ErrorKind::Truncated => write!(f, "truncated data"),
+            ErrorKind::Unsupported(what) => write!(f, "unsupported syntax: {}", what),
+            ErrorKind::SpecViolation(what) => write!(f, "spec violation: {}", what)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.box_path.is_empty() {
+            write!(f, " in {}", self.box_path.join("/"))?;
+        }
+        if let Some(nalu_index) = self.nalu_index {
+            write!(f, " at NALU #{}", nalu_index)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " (offset {})", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
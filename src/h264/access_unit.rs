@@ -0,0 +1,155 @@
+use std::fmt;
+
+use super::{idr_nalu::IdrNalu, nalu::Nalu, non_idr_nalu::NonIdrNalu, slice_header::SliceHeader};
+
+// NAL units of one primary coded picture together with its non-VCL units, in decoding order (7.4.1.2.3)
+#[derive(Default)]
+pub struct AccessUnit {
+    pub units: Vec<Box<dyn Nalu>>
+}
+
+impl AccessUnit {
+    pub fn new() -> Self {
+        AccessUnit {
+            units: vec![]
+        }
+    }
+
+    // splits NAL units in decoding order into access units
+    pub fn group(units: Vec<Box<dyn Nalu>>) -> Vec<AccessUnit> {
+        let mut access_units = vec![];
+        let mut access_unit = AccessUnit::new();
+        let mut has_picture = false;
+        let mut ends_access_unit = false;
+        for unit in units {
+            let starts_access_unit = (ends_access_unit && unit.get_nal_unit_type() != 11) || match AccessUnit::get_slice(unit.as_ref()) {
+                Some((nal_ref_idc, slice_header)) => access_unit.is_first_slice_of_new_picture(nal_ref_idc, slice_header),
+                // access unit delimiter, SPS, SEI, PPS and reserved types 14 to 18 only start an access unit after a picture
+                None => has_picture && matches!(unit.get_nal_unit_type(), 6..=9 | 14..=18)
+            };
+            if starts_access_unit && !access_unit.units.is_empty() {
+                access_units.push(access_unit);
+                access_unit = AccessUnit::new();
+                has_picture = false;
+            }
+            // end of sequence and end of stream are the last units of their access unit, end of stream
+            // possibly following end of sequence
+            ends_access_unit = matches!(unit.get_nal_unit_type(), 10 | 11);
+            has_picture |= AccessUnit::get_slice(unit.as_ref()).is_some();
+            access_unit.units.push(unit);
+        }
+        if !access_unit.units.is_empty() {
+            access_units.push(access_unit);
+        }
+        access_units
+    }
+
+    // nal_ref_idc and slice header of the first slice of the primary coded picture
+    pub fn get_primary_slice(&self) -> Option<(u8, &SliceHeader)> {
+        self.units.iter().find_map(|unit| AccessUnit::get_slice(unit.as_ref()))
+    }
+
+    pub fn is_idr(&self) -> bool {
+        self.units.iter().any(|unit| unit.get_nal_unit_type() == 5)
+    }
+
+    // first VCL NAL unit of a new primary coded picture (7.4.1.2.4)
+    fn is_first_slice_of_new_picture(&self, nal_ref_idc: u8, slice_header: &SliceHeader) -> bool {
+        let slices: Vec<(u8, &SliceHeader)> = self.units.iter()
+            .filter_map(|unit| AccessUnit::get_slice(unit.as_ref()))
+            .collect();
+        let (previous_nal_ref_idc, previous) = match slices.last() {
+            Some(&slice) => slice,
+            None => return false
+        };
+        slice_header.differs_in_picture(nal_ref_idc, previous, previous_nal_ref_idc)
+            // a picture covers its first macroblock only once
+            || (slice_header.redundant_pic_cnt == 0 && slice_header.first_mb_in_slice == 0 && slices.iter().any(|(_, slice)| slice.first_mb_in_slice == 0 && slice.redundant_pic_cnt == 0))
+    }
+
+    // nal_ref_idc and slice header of a VCL NAL unit
+    pub fn get_slice(unit: &dyn Nalu) -> Option<(u8, &SliceHeader)> {
+        if let Some(idr) = unit.as_any().downcast_ref::<IdrNalu>() {
+            Some(((idr.header >> 5) & 0b11, &idr.slice_header))
+        } else {
+            unit.as_any().downcast_ref::<NonIdrNalu>().map(|non_idr| ((non_idr.header >> 5) & 0b11, &non_idr.slice_header))
+        }
+    }
+}
+
+impl fmt::Debug for AccessUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(&self.units)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, rc::Rc};
+
+    use crate::{h264::parameter_sets::ParameterSets, mp4::h264_nalu_list::H264NaluList, test_util::{BASELINE_SPS, to_annex_b_unit}};
+
+    use super::AccessUnit;
+
+    // slice with first_mb_in_slice and frame_num in bits, for the Baseline SPS and a PPS without weighted prediction
+    fn get_slice(header: u8, first_mb_in_slice: &str, frame_num: &str) -> Vec<u8> {
+        let bits = match header & 0x1F {
+            5 => format!("{} 0001000 1 {} 1 0 0 1 010 10101100", first_mb_in_slice, frame_num),
+            _ if header & 0x60 == 0 => format!("{} 00110 1 {} 0 0 1 010 10101100", first_mb_in_slice, frame_num),
+            _ => format!("{} 00110 1 {} 0 0 0 1 010 10101100", first_mb_in_slice, frame_num)
+        };
+        to_annex_b_unit(header, &bits)
+    }
+
+    fn get_nal_unit_types(stream: &[u8]) -> Vec<Vec<u8>> {
+        let nalu_list = H264NaluList::read_annex_b(&mut Cursor::new(stream), Rc::new(ParameterSets::new())).unwrap();
+        AccessUnit::group(nalu_list.units).iter()
+            .map(|access_unit| access_unit.units.iter().map(|unit| unit.get_nal_unit_type()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn group_access_units() {
+        // pic_timing with one clock timestamp
+        let sei = to_annex_b_unit(0x06, "00000001 00001001 0000 1 00 0 00100 1 0 1 00001100 100010 111000 00001 111111111111111111111110 1000000");
+        let mut stream = to_annex_b_unit(0x09, "000");
+        stream.extend([0, 0, 0, 1, 0x67]);
+        stream.extend(BASELINE_SPS);
+        stream.extend(to_annex_b_unit(0x68, "1 1 0 0 1 1 1 0 00 1 1 1 1 0 0"));
+        stream.extend(&sei);
+        // two slices of the IDR picture
+        stream.extend(get_slice(0x65, "1", "0000"));
+        stream.extend(get_slice(0x65, "00000101001", "0000"));
+        // frame_num 1, then frame_num 2 in arbitrary slice order, told apart by frame_num only
+        stream.extend(get_slice(0x41, "1", "0001"));
+        stream.extend(get_slice(0x41, "00000101001", "0001"));
+        stream.extend(get_slice(0x41, "00000101001", "0010"));
+        stream.extend(get_slice(0x41, "1", "0010"));
+        // SEI after a picture starts the next access unit, which ends with end of sequence and end of stream
+        stream.extend(&sei);
+        stream.extend(get_slice(0x01, "1", "0011"));
+        stream.extend([0, 0, 0, 1, 0x0A, 0, 0, 0, 1, 0x0B]);
+
+        assert_eq!(get_nal_unit_types(&stream), [
+            vec![9, 7, 8, 6, 5, 5],
+            vec![1, 1],
+            vec![1, 1],
+            vec![6, 1, 10, 11]
+        ]);
+    }
+
+    #[test]
+    fn group_after_end_of_sequence() {
+        // filler data after end of sequence belongs to the next access unit
+        let mut stream = vec![0, 0, 0, 1, 0x67];
+        stream.extend(BASELINE_SPS);
+        stream.extend(to_annex_b_unit(0x68, "1 1 0 0 1 1 1 0 00 1 1 1 1 0 0"));
+        stream.extend(get_slice(0x65, "1", "0000"));
+        stream.extend([0, 0, 0, 1, 0x0A, 0, 0, 0, 1, 0x0C, 0xFF, 0x80]);
+        stream.extend(get_slice(0x65, "00000101001", "0000"));
+
+        assert_eq!(get_nal_unit_types(&stream), [vec![7, 8, 5, 10], vec![12, 5]]);
+    }
+}
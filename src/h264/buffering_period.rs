@@ -0,0 +1,74 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider};
+
+// buffering_period SEI message (D.1.1); one entry per SchedSelIdx of the NAL and VCL HRD present in the SPS
+#[derive(Debug, Clone)]
+pub struct BufferingPeriod {
+    pub seq_parameter_set_id: u64,
+    pub nal_initial_cpb_removal_delay: Vec<u64>,
+    pub nal_initial_cpb_removal_delay_offset: Vec<u64>,
+    pub vcl_initial_cpb_removal_delay: Vec<u64>,
+    pub vcl_initial_cpb_removal_delay_offset: Vec<u64>
+}
+
+impl BufferingPeriod {
+    pub fn read(descriptor_reader: &mut DescriptorReader, sps_pps_provider: &dyn SpsPpsProvider) -> Result<Self> {
+        let seq_parameter_set_id = descriptor_reader.read_ue_v()?;
+        let sps = BufferingPeriod::get_sps(sps_pps_provider, seq_parameter_set_id)?;
+        let mut nal_initial_cpb_removal_delay = vec![];
+        let mut nal_initial_cpb_removal_delay_offset = vec![];
+        let mut vcl_initial_cpb_removal_delay = vec![];
+        let mut vcl_initial_cpb_removal_delay_offset = vec![];
+        if let Some(vui_parameters) = &sps.vui_parameters {
+            if let Some(hrd_parameters) = &vui_parameters.nal_hrd_parameters {
+                let bits = hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1;
+                for _sched_sel_idx in 0..=hrd_parameters.cpb_cnt_minus1 {
+                    nal_initial_cpb_removal_delay.push(descriptor_reader.read_u(bits)?);
+                    nal_initial_cpb_removal_delay_offset.push(descriptor_reader.read_u(bits)?);
+                }
+            }
+            if let Some(hrd_parameters) = &vui_parameters.vcl_hrd_parameters {
+                let bits = hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1;
+                for _sched_sel_idx in 0..=hrd_parameters.cpb_cnt_minus1 {
+                    vcl_initial_cpb_removal_delay.push(descriptor_reader.read_u(bits)?);
+                    vcl_initial_cpb_removal_delay_offset.push(descriptor_reader.read_u(bits)?);
+                }
+            }
+        }
+        Ok(BufferingPeriod {
+            seq_parameter_set_id,
+            nal_initial_cpb_removal_delay,
+            nal_initial_cpb_removal_delay_offset,
+            vcl_initial_cpb_removal_delay,
+            vcl_initial_cpb_removal_delay_offset
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
+        descriptor_writer.append_ue_v(self.seq_parameter_set_id);
+        let sps = BufferingPeriod::get_sps(sps_pps_provider, self.seq_parameter_set_id)?;
+        if let Some(vui_parameters) = &sps.vui_parameters {
+            if let Some(hrd_parameters) = &vui_parameters.nal_hrd_parameters {
+                let bits = hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1;
+                for (&delay, &offset) in self.nal_initial_cpb_removal_delay.iter().zip(&self.nal_initial_cpb_removal_delay_offset) {
+                    descriptor_writer.append_u(bits, delay);
+                    descriptor_writer.append_u(bits, offset);
+                }
+            }
+            if let Some(hrd_parameters) = &vui_parameters.vcl_hrd_parameters {
+                let bits = hrd_parameters.initial_cpb_removal_delay_length_minus1 + 1;
+                for (&delay, &offset) in self.vcl_initial_cpb_removal_delay.iter().zip(&self.vcl_initial_cpb_removal_delay_offset) {
+                    descriptor_writer.append_u(bits, delay);
+                    descriptor_writer.append_u(bits, offset);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get_sps(sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Result<&SpsNalu> {
+        sps_pps_provider.get_sps(seq_parameter_set_id)
+            .ok_or_else(|| Error::spec_violation(format!("buffering_period refers to missing SPS {}", seq_parameter_set_id)))
+    }
+}
@@ -0,0 +1,98 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// one clock timestamp of a pic_timing SEI message (D.1.3); with full_timestamp_flag set,
+// the seconds, minutes and hours flags are all true
+#[derive(Debug, Clone, Default)]
+pub struct ClockTimestamp {
+    pub ct_type: u8,
+    pub nuit_field_based_flag: bool,
+    pub counting_type: u8,
+    pub full_timestamp_flag: bool,
+    pub discontinuity_flag: bool,
+    pub cnt_dropped_flag: bool,
+    pub n_frames: u8,
+    pub seconds_flag: bool,
+    pub seconds_value: u8,
+    pub minutes_flag: bool,
+    pub minutes_value: u8,
+    pub hours_flag: bool,
+    pub hours_value: u8,
+    pub time_offset: i64
+}
+
+impl ClockTimestamp {
+    pub fn read(descriptor_reader: &mut DescriptorReader, time_offset_length: u8) -> Result<Self> {
+        let mut clock_timestamp = ClockTimestamp {
+            ct_type: descriptor_reader.read_u(2)? as u8,
+            nuit_field_based_flag: descriptor_reader.read_u1()?,
+            counting_type: descriptor_reader.read_u(5)? as u8,
+            full_timestamp_flag: descriptor_reader.read_u1()?,
+            discontinuity_flag: descriptor_reader.read_u1()?,
+            cnt_dropped_flag: descriptor_reader.read_u1()?,
+            n_frames: descriptor_reader.read_u8()?,
+            ..Default::default()
+        };
+        if clock_timestamp.full_timestamp_flag {
+            clock_timestamp.seconds_flag = true;
+            clock_timestamp.seconds_value = descriptor_reader.read_u(6)? as u8;
+            clock_timestamp.minutes_flag = true;
+            clock_timestamp.minutes_value = descriptor_reader.read_u(6)? as u8;
+            clock_timestamp.hours_flag = true;
+            clock_timestamp.hours_value = descriptor_reader.read_u(5)? as u8;
+        } else {
+            clock_timestamp.seconds_flag = descriptor_reader.read_u1()?;
+            if clock_timestamp.seconds_flag {
+                clock_timestamp.seconds_value = descriptor_reader.read_u(6)? as u8;
+                clock_timestamp.minutes_flag = descriptor_reader.read_u1()?;
+                if clock_timestamp.minutes_flag {
+                    clock_timestamp.minutes_value = descriptor_reader.read_u(6)? as u8;
+                    clock_timestamp.hours_flag = descriptor_reader.read_u1()?;
+                    if clock_timestamp.hours_flag {
+                        clock_timestamp.hours_value = descriptor_reader.read_u(5)? as u8;
+                    }
+                }
+            }
+        }
+        if time_offset_length > 0 {
+            // i(v), two's complement
+            let value = descriptor_reader.read_u(time_offset_length)?;
+            let shift = 64 - u32::from(time_offset_length);
+            clock_timestamp.time_offset = ((value << shift) as i64) >> shift;
+        }
+        Ok(clock_timestamp)
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, time_offset_length: u8) {
+        descriptor_writer.append_u(2, self.ct_type.into());
+        descriptor_writer.append_u1(self.nuit_field_based_flag);
+        descriptor_writer.append_u(5, self.counting_type.into());
+        descriptor_writer.append_u1(self.full_timestamp_flag);
+        descriptor_writer.append_u1(self.discontinuity_flag);
+        descriptor_writer.append_u1(self.cnt_dropped_flag);
+        descriptor_writer.append_u8(self.n_frames);
+        if self.full_timestamp_flag {
+            descriptor_writer.append_u(6, self.seconds_value.into());
+            descriptor_writer.append_u(6, self.minutes_value.into());
+            descriptor_writer.append_u(5, self.hours_value.into());
+        } else {
+            descriptor_writer.append_u1(self.seconds_flag);
+            if self.seconds_flag {
+                descriptor_writer.append_u(6, self.seconds_value.into());
+                descriptor_writer.append_u1(self.minutes_flag);
+                if self.minutes_flag {
+                    descriptor_writer.append_u(6, self.minutes_value.into());
+                    descriptor_writer.append_u1(self.hours_flag);
+                    if self.hours_flag {
+                        descriptor_writer.append_u(5, self.hours_value.into());
+                    }
+                }
+            }
+        }
+        if time_offset_length > 0 {
+            let mask = u64::MAX >> (64 - u32::from(time_offset_length));
+            descriptor_writer.append_u(time_offset_length, self.time_offset as u64 & mask);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// content_light_level_info SEI message (D.1.31), in candelas per square metre
+#[derive(Debug, Clone)]
+pub struct ContentLightLevelInfo {
+    pub max_content_light_level: u16,
+    pub max_pic_average_light_level: u16
+}
+
+impl ContentLightLevelInfo {
+    pub fn read(descriptor_reader: &mut DescriptorReader) -> Result<Self> {
+        let max_content_light_level = descriptor_reader.read_u16()?;
+        let max_pic_average_light_level = descriptor_reader.read_u16()?;
+        Ok(ContentLightLevelInfo {
+            max_content_light_level,
+            max_pic_average_light_level
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        descriptor_writer.append_u16(self.max_content_light_level);
+        descriptor_writer.append_u16(self.max_pic_average_light_level);
+    }
+}
@@ -0,0 +1,11 @@
+// timing of one access unit through the CPB, in seconds, and the CPB fullness in bits just
+// before and just after its removal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpbFrame {
+    pub initial_arrival_time: f64,
+    pub final_arrival_time: f64,
+    pub nominal_removal_time: f64,
+    pub removal_time: f64,
+    pub fullness_before_removal: f64,
+    pub fullness_after_removal: f64
+}
@@ -0,0 +1,94 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// the terminating memory_management_control_operation 0 is not stored,
+// operands that do not apply to an operation are 0
+#[derive(Debug, Clone, Default)]
+pub struct DecRefPicMarking {
+    pub no_output_of_prior_pics_flag: bool,
+    pub long_term_reference_flag: bool,
+    pub adaptive_ref_pic_marking_mode_flag: bool,
+    pub memory_management_control_operation: Vec<u64>,
+    pub difference_of_pic_nums_minus1: Vec<u64>,
+    pub long_term_pic_num: Vec<u64>,
+    pub long_term_frame_idx: Vec<u64>,
+    pub max_long_term_frame_idx_plus1: Vec<u64>
+}
+
+impl DecRefPicMarking {
+    pub fn read(descriptor_reader: &mut DescriptorReader, idr_pic_flag: bool) -> Result<Self> {
+        let mut dec_ref_pic_marking = DecRefPicMarking::default();
+        if idr_pic_flag {
+            dec_ref_pic_marking.no_output_of_prior_pics_flag = descriptor_reader.read_u1()?;
+            dec_ref_pic_marking.long_term_reference_flag = descriptor_reader.read_u1()?;
+            return Ok(dec_ref_pic_marking);
+        }
+        dec_ref_pic_marking.adaptive_ref_pic_marking_mode_flag = descriptor_reader.read_u1()?;
+        if dec_ref_pic_marking.adaptive_ref_pic_marking_mode_flag {
+            loop {
+                let operation = descriptor_reader.read_ue_v()?;
+                if operation == 0 {
+                    break;
+                }
+                if operation > 6 {
+                    return Err(Error::spec_violation(format!("memory_management_control_operation {}", operation)));
+                }
+                let mut difference_of_pic_nums_minus1 = 0;
+                let mut long_term_pic_num = 0;
+                let mut long_term_frame_idx = 0;
+                let mut max_long_term_frame_idx_plus1 = 0;
+                if operation == 1 || operation == 3 {
+                    difference_of_pic_nums_minus1 = descriptor_reader.read_ue_v()?;
+                }
+                if operation == 2 {
+                    long_term_pic_num = descriptor_reader.read_ue_v()?;
+                }
+                if operation == 3 || operation == 6 {
+                    long_term_frame_idx = descriptor_reader.read_ue_v()?;
+                }
+                if operation == 4 {
+                    max_long_term_frame_idx_plus1 = descriptor_reader.read_ue_v()?;
+                }
+                dec_ref_pic_marking.memory_management_control_operation.push(operation);
+                dec_ref_pic_marking.difference_of_pic_nums_minus1.push(difference_of_pic_nums_minus1);
+                dec_ref_pic_marking.long_term_pic_num.push(long_term_pic_num);
+                dec_ref_pic_marking.long_term_frame_idx.push(long_term_frame_idx);
+                dec_ref_pic_marking.max_long_term_frame_idx_plus1.push(max_long_term_frame_idx_plus1);
+            }
+        }
+        Ok(dec_ref_pic_marking)
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, idr_pic_flag: bool) {
+        if idr_pic_flag {
+            descriptor_writer.append_u1(self.no_output_of_prior_pics_flag);
+            descriptor_writer.append_u1(self.long_term_reference_flag);
+            return;
+        }
+        descriptor_writer.append_u1(self.adaptive_ref_pic_marking_mode_flag);
+        if self.adaptive_ref_pic_marking_mode_flag {
+            for (i, &operation) in self.memory_management_control_operation.iter().enumerate() {
+                descriptor_writer.append_ue_v(operation);
+                if operation == 1 || operation == 3 {
+                    descriptor_writer.append_ue_v(self.difference_of_pic_nums_minus1[i]);
+                }
+                if operation == 2 {
+                    descriptor_writer.append_ue_v(self.long_term_pic_num[i]);
+                }
+                if operation == 3 || operation == 6 {
+                    descriptor_writer.append_ue_v(self.long_term_frame_idx[i]);
+                }
+                if operation == 4 {
+                    descriptor_writer.append_ue_v(self.max_long_term_frame_idx_plus1[i]);
+                }
+            }
+            descriptor_writer.append_ue_v(0);
+        }
+    }
+
+    // memory_management_control_operation 5 marks all reference pictures unused and resets frame_num and POC
+    pub fn has_memory_management_control_operation_5(&self) -> bool {
+        self.memory_management_control_operation.contains(&5)
+    }
+}
@@ -1,4 +1,6 @@
-use std::{any::Any, fmt, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use crate::error::Result;
 
 use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, opaque_data::OpaqueData, sps_pps_provider::SpsPpsProvider};
 
@@ -7,10 +9,10 @@ pub struct DelimNalu {
 }
 
 impl DelimNalu {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u32) -> io::Result<Self> {
-        let mut descriptor_reader = DescriptorReader::new(rdr, len);
-        let remaining = descriptor_reader.read_to_end();
-        descriptor_reader.read_rbsp_trailing_bits();
+    pub fn read(rdr: &mut (impl Read + Seek), len: u32) -> Result<Self> {
+        let mut descriptor_reader = DescriptorReader::new(rdr, len)?;
+        let remaining = descriptor_reader.read_to_end()?;
+        descriptor_reader.read_rbsp_trailing_bits()?;
 
         Ok(DelimNalu {
             remaining
@@ -19,11 +21,15 @@ impl DelimNalu {
 }
 
 impl Nalu for DelimNalu {
-    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) {
+    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
         let mut descriptor_writer = DescriptorWriter::new(wtr);
         descriptor_writer.append_all(&self.remaining);
         descriptor_writer.append_rbsp_trailing_bits();
-        descriptor_writer.write_with_header(0x09);
+        descriptor_writer.write_with_header(0x09)
+    }
+
+    fn get_nal_unit_type(&self) -> u8 {
+        9
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -1,6 +1,8 @@
 use std::{cmp, io::Read};
 
-use super::opaque_data::OpaqueData;
+use crate::error::{Error, Result};
+
+use super::{emulation_prevention, opaque_data::OpaqueData};
 
 pub struct DescriptorReader {
     buffer: Vec<u8>,
@@ -9,9 +11,18 @@ pub struct DescriptorReader {
 }
 
 impl<'a> DescriptorReader {
-    pub fn new(rdr: &'a mut (dyn Read), len: u32) -> Self {
-        let mut buffer = vec![0u8; len.try_into().unwrap()];
-        rdr.read_exact(&mut buffer).unwrap();
+    pub fn new(rdr: &'a mut dyn Read, len: u32) -> Result<Self> {
+        let mut nal_bytes = vec![0u8; len.try_into()?];
+        rdr.read_exact(&mut nal_bytes)?;
+        Ok(DescriptorReader {
+            buffer: emulation_prevention::nal_to_rbsp(&nal_bytes),
+            next_pos: 0,
+            residue_bits: 0
+        })
+    }
+
+    // reads bytes that are already free of emulation prevention, such as an SEI payload
+    pub fn from_rbsp(buffer: Vec<u8>) -> Self {
         DescriptorReader {
             buffer,
             next_pos: 0,
@@ -19,13 +30,15 @@ impl<'a> DescriptorReader {
         }
     }
 
-    pub fn read_u(&mut self, bits: u8) -> u64 {
+    pub fn read_u(&mut self, bits: u8) -> Result<u64> {
+        if bits > 64 {
+            return Err(Error::unsupported(format!("reading {} bits into a 64-bit value", bits)));
+        }
         let mut value: u64 = 0;
         let mut remaining_bits = bits;
         while remaining_bits > 0 {
             if self.residue_bits == 0 {
-                self.residue_bits = 8;
-                self.next_pos += 1;
+                self.next_byte()?;
             }
             // Example:
             // residue_value  0b10110111
@@ -39,109 +52,118 @@ impl<'a> DescriptorReader {
             self.residue_bits -= read_bits;
             remaining_bits -= read_bits;
         }
-        value
+        Ok(value)
     }
 
-    pub fn read_u1(&mut self) -> bool {
-        self.read_u(1) > 0
+    pub fn read_u1(&mut self) -> Result<bool> {
+        Ok(self.read_u(1)? > 0)
     }
 
-    pub fn read_u8(&mut self) -> u8 {
-        u8::try_from(self.read_u(8)).unwrap()
+    pub fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_u(8)? as u8)
     }
     
-    pub fn read_u16(&mut self) -> u16 {
-        u16::try_from(self.read_u(16)).unwrap()
+    pub fn read_u16(&mut self) -> Result<u16> {
+        Ok(self.read_u(16)? as u16)
     }
 
-    pub fn read_u32(&mut self) -> u32 {
-        u32::try_from(self.read_u(32)).unwrap()
+    pub fn read_u32(&mut self) -> Result<u32> {
+        Ok(self.read_u(32)? as u32)
     }
 
-    pub fn read_ue_v(&mut self) -> u64 {
-        let bits = self.read_zero_bits();
-        self.read_u(bits + 1) - 1
+    pub fn read_ue_v(&mut self) -> Result<u64> {
+        let bits = self.read_zero_bits()?;
+        Ok(self.read_u(bits + 1)? - 1)
     }
 
-    pub fn read_se_v(&mut self) -> i64 {
-        let bits = self.read_zero_bits();
-        let magnitude = if bits > 0 { i64::try_from(self.read_u(bits)).unwrap() } else { 0 };
-        let sign = self.read_u1();
+    pub fn read_se_v(&mut self) -> Result<i64> {
+        let bits = self.read_zero_bits()?;
+        let magnitude = if bits > 0 { self.read_u(bits)? as i64 } else { 0 };
+        let sign = self.read_u1()?;
         if sign {
-            return -magnitude;
+            Ok(-magnitude)
         } else {
-            return magnitude;
+            Ok(magnitude)
         }
     }
 
     pub fn more_rbsp_data(&self) -> bool {
-        let assumed_stop_bit;
-        if self.residue_bits > 0 {
-            assumed_stop_bit = self.buffer[self.next_pos - 1] << (8 - self.residue_bits) >> 7;
-        } else if self.next_pos < self.buffer.len() {
-            assumed_stop_bit = self.buffer[self.next_pos] >> 7;
+        // position of the next bit to be read
+        let (byte_index, bit_index) = if self.residue_bits > 0 {
+            (self.next_pos - 1, 8 - self.residue_bits)
         } else {
-            return false;
-        }
-
-        if assumed_stop_bit != 1 {
-            return true;
-        }
+            (self.next_pos, 0)
+        };
 
-        if self.residue_bits > 1 {
-            let assumed_zero_bits = self.buffer[self.next_pos - 1] << (9 - self.residue_bits);
-            if assumed_zero_bits != 0 {
-                return true;
-            }
-        }
-        
-        for i in self.next_pos..self.buffer.len() {
-            if self.buffer[i] != 0 {
-                return true
-            }
+        // more data only if the last 1 bit (rbsp_stop_one_bit) is after the next bit
+        match self.find_last_one() {
+            Some(last_one) => last_one > (byte_index, bit_index),
+            None => false
         }
-
-        // empty or 1 followed by padding 0s => RBSP endding, no more data
-        false
     }
 
-    pub fn read_to_end(&mut self) -> OpaqueData {
+    pub fn read_to_end(&mut self) -> Result<OpaqueData> {
         if let Some((last_one_byte_index, last_one_bit_index)) = self.find_last_one() {
             let mut bytes = vec![];
-            let mut bits_to_read: isize = isize::try_from((last_one_byte_index - self.next_pos + 1) * 8).unwrap() + isize::from(last_one_bit_index) - isize::from(8 - self.residue_bits);
+            let mut bits_to_read: isize = (last_one_byte_index as isize - self.next_pos as isize + 1) * 8 + isize::from(last_one_bit_index) - isize::from(8 - self.residue_bits);
+            if bits_to_read < 0 {
+                return Err(Error::spec_violation("rbsp_stop_one_bit already consumed"));
+            }
             while bits_to_read > 8 {
-                let value = self.read_u8();
+                let value = self.read_u8()?;
                 bytes.push(value);
                 bits_to_read -= 8;
             }
             let residue_value = if bits_to_read > 0 {
-                u8::try_from(self.read_u(bits_to_read.try_into().unwrap())).unwrap()
+                self.read_u(bits_to_read as u8)? as u8
             } else {
                 0
             };
-            OpaqueData{
+            Ok(OpaqueData{
                 bytes,
                 residue_value,
-                residue_bits: bits_to_read.try_into().unwrap()
-            }
+                residue_bits: bits_to_read as u8
+            })
         } else {
-            OpaqueData {
+            Ok(OpaqueData {
                 bytes: vec![],
                 residue_value: 0,
                 residue_bits: 0
-            }
+            })
         }
     }
 
-    pub fn read_rbsp_trailing_bits(&mut self) {
-        let stop_bit = self.read_u1();
-        if stop_bit != true {
-            panic!("Stop bit of 1 expected but 0 is read")
+    pub fn read_rbsp_trailing_bits(&mut self) -> Result<()> {
+        let stop_bit = self.read_u1()?;
+        if !stop_bit {
+            return Err(Error::spec_violation("rbsp_stop_one_bit of 1 expected but 0 is read"));
         }
-        let zero_bits = self.read_u(self.residue_bits);
+        let zero_bits = self.read_u(self.residue_bits)?;
         if zero_bits != 0 {
-            panic!("Zero bits expected but some 1 bit is found in trailing bits")
+            return Err(Error::spec_violation("rbsp_alignment_zero_bit expected but some 1 bit is found in trailing bits"));
+        }
+        Ok(())
+    }
+
+    // rbsp_slice_trailing_bits (7.3.2.10), returning the number of cabac_zero_words
+    pub fn read_rbsp_slice_trailing_bits(&mut self) -> Result<u64> {
+        self.read_rbsp_trailing_bits()?;
+        let trailing_bytes = &self.buffer[self.next_pos..];
+        if !trailing_bytes.len().is_multiple_of(2) || trailing_bytes.iter().any(|&byte| byte != 0) {
+            return Err(Error::spec_violation("cabac_zero_word of 0x0000 expected after rbsp_trailing_bits"));
+        }
+        let cabac_zero_words = trailing_bytes.len() as u64 / 2;
+        self.next_pos = self.buffer.len();
+        Ok(cabac_zero_words)
+    }
+
+    fn next_byte(&mut self) -> Result<()> {
+        if self.next_pos >= self.buffer.len() {
+            return Err(Error::truncated());
         }
+        self.residue_bits = 8;
+        self.next_pos += 1;
+        Ok(())
     }
 
     fn find_last_one(&self) -> Option<(usize, u8)> {
@@ -171,12 +193,11 @@ impl<'a> DescriptorReader {
         None
     }
 
-    fn read_zero_bits(&mut self) -> u8 {
+    fn read_zero_bits(&mut self) -> Result<u8> {
         let mut count = 0;
         loop {
             if self.residue_bits == 0 {
-                self.residue_bits = 8;
-                self.next_pos += 1;
+                self.next_byte()?;
             }
             // Example:
             // residue_value  0b11000001
@@ -184,23 +205,16 @@ impl<'a> DescriptorReader {
             // read_value       0b00000100
             // read_bits          ~~~~~    (5)
             let read_value = self.buffer[self.next_pos - 1] << (8 - self.residue_bits);
-            let read_bits = cmp::min(self.residue_bits, match read_value {
-                0b10000000..=0b11111111 => 0,
-                0b01000000..=0b01111111 => 1,
-                0b00100000..=0b00111111 => 2,
-                0b00010000..=0b00011111 => 3,
-                0b00001000..=0b00001111 => 4,
-                0b00000100..=0b00000111 => 5,
-                0b00000010..=0b00000011 => 6,
-                0b00000001 => 7,
-                0 => 8,
-            });
+            let read_bits = cmp::min(self.residue_bits, read_value.leading_zeros() as u8);
             count += read_bits;
             self.residue_bits -= read_bits;
+            if count > 32 {
+                return Err(Error::spec_violation("Exp-Golomb code longer than 32 leading zero bits"));
+            }
             if read_bits < self.residue_bits {
                 break;
             }
         }
-        count
+        Ok(count)
     }
-}
\ No newline at end of file
+}
@@ -2,17 +2,19 @@ use std::{cmp, io::Write};
 
 use byteorder::WriteBytesExt;
 
-use super::opaque_data::OpaqueData;
+use crate::error::Result;
+
+use super::{emulation_prevention, opaque_data::OpaqueData};
 
 pub struct DescriptorWriter<'a> {
-    wtr: &'a mut (dyn Write),
+    wtr: &'a mut dyn Write,
     buffer: Vec<u8>,
     residue_bits: u8,
     residue_value: u8
 }
 
 impl<'a> DescriptorWriter<'a> {
-    pub fn new(wtr: &'a mut (dyn Write)) -> Self {
+    pub fn new(wtr: &'a mut dyn Write) -> Self {
         DescriptorWriter {
             wtr,
             buffer: vec![],
@@ -33,7 +35,7 @@ impl<'a> DescriptorWriter<'a> {
         while remaining_bits > 0 {
             let write_bits = cmp::min(8 - self.residue_bits, remaining_bits);
             let write_value = shifted_value >> (remaining_bits - write_bits) << (8 - self.residue_bits - write_bits);
-            self.residue_value = self.residue_value | (write_value as u8);
+            self.residue_value |= write_value as u8;
             self.residue_bits += write_bits;
             shifted_value = (shifted_value.overflowing_shl((64 - remaining_bits + write_bits).into()).0).overflowing_shr((64 - remaining_bits + write_bits).into()).0;
             remaining_bits -= write_bits;
@@ -82,10 +84,10 @@ impl<'a> DescriptorWriter<'a> {
             let magnitude: u64;
             if value < 0 {
                 sign = true;
-                magnitude = u64::try_from(-value).unwrap();
+                magnitude = value.unsigned_abs();
             } else {
                 sign = false;
-                magnitude = u64::try_from(value).unwrap();
+                magnitude = value.unsigned_abs();
             }
             let bits = DescriptorWriter::count_bits(magnitude);
             self.append_u(bits, 0);
@@ -112,10 +114,30 @@ impl<'a> DescriptorWriter<'a> {
         }
     }
 
-    pub fn write_with_header(&mut self, header: u8) {
-        self.wtr.write_u8(header).unwrap();
-        self.wtr.write_all(&self.buffer).unwrap();
+    pub fn append_rbsp_slice_trailing_bits(&mut self, cabac_zero_words: u64) {
+        self.append_rbsp_trailing_bits();
+        for _i in 0..cabac_zero_words {
+            self.append_u16(0);
+        }
+    }
+
+    pub fn is_byte_aligned(&self) -> bool {
+        self.residue_bits == 0
+    }
+
+    // bytes appended so far without emulation prevention, a partial last byte padded with 0 bits
+    pub fn into_rbsp(mut self) -> Vec<u8> {
+        if self.residue_bits > 0 {
+            self.buffer.push(self.residue_value);
+        }
+        self.buffer
+    }
+
+    pub fn write_with_header(&mut self, header: u8) -> Result<()> {
+        self.wtr.write_u8(header)?;
+        self.wtr.write_all(&emulation_prevention::rbsp_to_nal(&self.buffer))?;
         self.buffer.clear();
+        Ok(())
     }
 
     // pub fn write_with_size_and_header(&mut self, header: u8) {
@@ -127,17 +149,6 @@ impl<'a> DescriptorWriter<'a> {
     // }
 
     fn count_bits(value: u64) -> u8 {
-        match value {
-            0 => 0,
-            0b1 => 1,
-            0b10..=0b11 => 2,
-            0b100..=0b111 => 3,
-            0b1000..=0b1111 => 4,
-            0b10000..=0b11111 => 5,
-            0b100000..=0b111111 => 6,
-            0b1000000..=0b1111111 => 7,
-            0b10000000..=0b11111111 => 8,
-            _ => todo!()
-        }
+        (64 - value.leading_zeros()) as u8
     }
 }
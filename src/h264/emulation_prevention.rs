@@ -0,0 +1,88 @@
+// Conversion between NAL unit payload bytes and RBSP bytes (see 7.3.1 and 7.4.1 of H.264).
+//
+// Inside a NAL unit, the byte sequences 0x000000, 0x000001, 0x000002 and 0x000003 must never
+// appear, so an emulation_prevention_three_byte (0x03) is inserted after any two consecutive
+// zero bytes that would otherwise be followed by one of 0x00..=0x03.
+
+pub fn nal_to_rbsp(nal_bytes: &[u8]) -> Vec<u8> {
+    let mut rbsp_bytes = Vec::with_capacity(nal_bytes.len());
+    let mut zero_count = 0;
+    for &byte in nal_bytes {
+        if zero_count >= 2 && byte == 0x03 {
+            // emulation_prevention_three_byte, discarded
+            zero_count = 0;
+            continue;
+        }
+        rbsp_bytes.push(byte);
+        if byte == 0 {
+            zero_count += 1;
+        } else {
+            zero_count = 0;
+        }
+    }
+    rbsp_bytes
+}
+
+pub fn rbsp_to_nal(rbsp_bytes: &[u8]) -> Vec<u8> {
+    let mut nal_bytes = Vec::with_capacity(rbsp_bytes.len() + rbsp_bytes.len() / 64);
+    let mut zero_count = 0;
+    for &byte in rbsp_bytes {
+        if zero_count >= 2 && byte <= 0x03 {
+            nal_bytes.push(0x03);
+            zero_count = 0;
+        }
+        nal_bytes.push(byte);
+        if byte == 0 {
+            zero_count += 1;
+        } else {
+            zero_count = 0;
+        }
+    }
+    // a NAL unit must not end with 0x00, which only happens when cabac_zero_words are present
+    if nal_bytes.last() == Some(&0) {
+        nal_bytes.push(0x03);
+    }
+    nal_bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::h264::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+    use super::{nal_to_rbsp, rbsp_to_nal};
+
+    #[test]
+    fn escape_start_code_prefixes() {
+        let rbsp = [0x25, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x03, 0x00, 0x00, 0x04, 0x80];
+        let nal = [0x25, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x03, 0x02, 0x00, 0x00, 0x03, 0x03, 0x00, 0x00, 0x04, 0x80];
+        assert_eq!(rbsp_to_nal(&rbsp), nal);
+        assert_eq!(nal_to_rbsp(&nal), rbsp);
+    }
+
+    #[test]
+    fn escape_trailing_zero_bytes() {
+        // cabac_zero_words end the RBSP with 0x0000, so the NAL unit ends with 0x000003
+        let rbsp = [0x88, 0x80, 0x00, 0x00, 0x00, 0x00];
+        let nal = [0x88, 0x80, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03];
+        assert_eq!(rbsp_to_nal(&rbsp), nal);
+        assert_eq!(nal_to_rbsp(&nal), rbsp);
+    }
+
+    #[test]
+    fn round_trip_cabac_zero_words() {
+        let nal = [0x88, 0x80, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03];
+        let mut descriptor_reader = DescriptorReader::new(&mut Cursor::new(nal), nal.len() as u32).unwrap();
+        assert_eq!(descriptor_reader.read_u8().unwrap(), 0x88);
+        assert_eq!(descriptor_reader.read_rbsp_slice_trailing_bits().unwrap(), 2);
+
+        let mut bytes = vec![];
+        let mut descriptor_writer = DescriptorWriter::new(&mut bytes);
+        descriptor_writer.append_u8(0x88);
+        descriptor_writer.append_rbsp_slice_trailing_bits(2);
+        descriptor_writer.write_with_header(0x65).unwrap();
+        assert_eq!(bytes[0], 0x65);
+        assert_eq!(bytes[1..], nal);
+    }
+}
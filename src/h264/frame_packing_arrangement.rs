@@ -0,0 +1,88 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// frame_packing_arrangement SEI message (D.1.26); fields after frame_packing_arrangement_cancel_flag
+// are 0 when cancelled, grid positions are 0 unless present
+#[derive(Debug, Clone, Default)]
+pub struct FramePackingArrangement {
+    pub frame_packing_arrangement_id: u64,
+    pub frame_packing_arrangement_cancel_flag: bool,
+    pub frame_packing_arrangement_type: u8,
+    pub quincunx_sampling_flag: bool,
+    pub content_interpretation_type: u8,
+    pub spatial_flipping_flag: bool,
+    pub frame0_flipped_flag: bool,
+    pub field_views_flag: bool,
+    pub current_frame_is_frame0_flag: bool,
+    pub frame0_self_contained_flag: bool,
+    pub frame1_self_contained_flag: bool,
+    pub frame0_grid_position_x: u8,
+    pub frame0_grid_position_y: u8,
+    pub frame1_grid_position_x: u8,
+    pub frame1_grid_position_y: u8,
+    pub frame_packing_arrangement_reserved_byte: u8,
+    pub frame_packing_arrangement_repetition_period: u64,
+    pub frame_packing_arrangement_extension_flag: bool
+}
+
+impl FramePackingArrangement {
+    pub fn read(descriptor_reader: &mut DescriptorReader) -> Result<Self> {
+        let mut frame_packing_arrangement = FramePackingArrangement {
+            frame_packing_arrangement_id: descriptor_reader.read_ue_v()?,
+            frame_packing_arrangement_cancel_flag: descriptor_reader.read_u1()?,
+            ..Default::default()
+        };
+        if !frame_packing_arrangement.frame_packing_arrangement_cancel_flag {
+            frame_packing_arrangement.frame_packing_arrangement_type = descriptor_reader.read_u(7)? as u8;
+            frame_packing_arrangement.quincunx_sampling_flag = descriptor_reader.read_u1()?;
+            frame_packing_arrangement.content_interpretation_type = descriptor_reader.read_u(6)? as u8;
+            frame_packing_arrangement.spatial_flipping_flag = descriptor_reader.read_u1()?;
+            frame_packing_arrangement.frame0_flipped_flag = descriptor_reader.read_u1()?;
+            frame_packing_arrangement.field_views_flag = descriptor_reader.read_u1()?;
+            frame_packing_arrangement.current_frame_is_frame0_flag = descriptor_reader.read_u1()?;
+            frame_packing_arrangement.frame0_self_contained_flag = descriptor_reader.read_u1()?;
+            frame_packing_arrangement.frame1_self_contained_flag = descriptor_reader.read_u1()?;
+            if frame_packing_arrangement.has_grid_positions() {
+                frame_packing_arrangement.frame0_grid_position_x = descriptor_reader.read_u(4)? as u8;
+                frame_packing_arrangement.frame0_grid_position_y = descriptor_reader.read_u(4)? as u8;
+                frame_packing_arrangement.frame1_grid_position_x = descriptor_reader.read_u(4)? as u8;
+                frame_packing_arrangement.frame1_grid_position_y = descriptor_reader.read_u(4)? as u8;
+            }
+            frame_packing_arrangement.frame_packing_arrangement_reserved_byte = descriptor_reader.read_u8()?;
+            frame_packing_arrangement.frame_packing_arrangement_repetition_period = descriptor_reader.read_ue_v()?;
+        }
+        frame_packing_arrangement.frame_packing_arrangement_extension_flag = descriptor_reader.read_u1()?;
+        Ok(frame_packing_arrangement)
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        descriptor_writer.append_ue_v(self.frame_packing_arrangement_id);
+        descriptor_writer.append_u1(self.frame_packing_arrangement_cancel_flag);
+        if !self.frame_packing_arrangement_cancel_flag {
+            descriptor_writer.append_u(7, self.frame_packing_arrangement_type.into());
+            descriptor_writer.append_u1(self.quincunx_sampling_flag);
+            descriptor_writer.append_u(6, self.content_interpretation_type.into());
+            descriptor_writer.append_u1(self.spatial_flipping_flag);
+            descriptor_writer.append_u1(self.frame0_flipped_flag);
+            descriptor_writer.append_u1(self.field_views_flag);
+            descriptor_writer.append_u1(self.current_frame_is_frame0_flag);
+            descriptor_writer.append_u1(self.frame0_self_contained_flag);
+            descriptor_writer.append_u1(self.frame1_self_contained_flag);
+            if self.has_grid_positions() {
+                descriptor_writer.append_u(4, self.frame0_grid_position_x.into());
+                descriptor_writer.append_u(4, self.frame0_grid_position_y.into());
+                descriptor_writer.append_u(4, self.frame1_grid_position_x.into());
+                descriptor_writer.append_u(4, self.frame1_grid_position_y.into());
+            }
+            descriptor_writer.append_u8(self.frame_packing_arrangement_reserved_byte);
+            descriptor_writer.append_ue_v(self.frame_packing_arrangement_repetition_period);
+        }
+        descriptor_writer.append_u1(self.frame_packing_arrangement_extension_flag);
+    }
+
+    // frame_packing_arrangement_type 5 is temporal interleaving, which has no grid
+    fn has_grid_positions(&self) -> bool {
+        !self.quincunx_sampling_flag && self.frame_packing_arrangement_type != 5
+    }
+}
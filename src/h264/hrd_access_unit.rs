@@ -0,0 +1,10 @@
+use super::{buffering_period::BufferingPeriod, pic_timing::PicTiming};
+
+// what the HRD needs to know about one access unit, in decoding order
+#[derive(Debug, Clone)]
+pub struct HrdAccessUnit {
+    pub size: u64,                                  // in bits, counted as the HRD type requires
+    pub buffering_period: Option<BufferingPeriod>,
+    pub pic_timing: Option<PicTiming>,
+    pub arrival_time: Option<f64>                   // seconds at which the first bit actually arrived, None to follow the HRD schedule
+}
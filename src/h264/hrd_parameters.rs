@@ -1,3 +1,5 @@
+use crate::error::{Error, Result};
+
 use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
 
 #[derive(Debug, Clone)]
@@ -15,23 +17,26 @@ pub struct HrdParameters {
 }
 
 impl HrdParameters {
-    pub fn read(descriptor_reader: &mut DescriptorReader) -> Self {
-        let cpb_cnt_minus1 = descriptor_reader.read_ue_v();
-        let bit_rate_scale = descriptor_reader.read_u(4).try_into().unwrap();
-        let cpb_size_scale = descriptor_reader.read_u(4).try_into().unwrap();
+    pub fn read(descriptor_reader: &mut DescriptorReader) -> Result<Self> {
+        let cpb_cnt_minus1 = descriptor_reader.read_ue_v()?;
+        if cpb_cnt_minus1 > 31 {
+            return Err(Error::spec_violation(format!("cpb_cnt_minus1 {} out of range", cpb_cnt_minus1)));
+        }
+        let bit_rate_scale = descriptor_reader.read_u(4)? as u8;
+        let cpb_size_scale = descriptor_reader.read_u(4)? as u8;
         let mut bit_rate_value_minus1 = vec![];
         let mut cpb_size_value_minus1 = vec![];
         let mut cbr_flag = vec![];
         for _sched_sel_idx in 0..=cpb_cnt_minus1 {
-            bit_rate_value_minus1.push(descriptor_reader.read_ue_v());
-            cpb_size_value_minus1.push(descriptor_reader.read_ue_v());
-            cbr_flag.push(descriptor_reader.read_u1());
+            bit_rate_value_minus1.push(descriptor_reader.read_ue_v()?);
+            cpb_size_value_minus1.push(descriptor_reader.read_ue_v()?);
+            cbr_flag.push(descriptor_reader.read_u1()?);
         }
-        let initial_cpb_removal_delay_length_minus1 = descriptor_reader.read_u(5).try_into().unwrap();
-        let cpb_removal_delay_length_minus1 = descriptor_reader.read_u(5).try_into().unwrap();
-        let dpb_output_delay_length_minus1 = descriptor_reader.read_u(5).try_into().unwrap();
-        let time_offset_length = descriptor_reader.read_u(5).try_into().unwrap();
-        HrdParameters {
+        let initial_cpb_removal_delay_length_minus1 = descriptor_reader.read_u(5)? as u8;
+        let cpb_removal_delay_length_minus1 = descriptor_reader.read_u(5)? as u8;
+        let dpb_output_delay_length_minus1 = descriptor_reader.read_u(5)? as u8;
+        let time_offset_length = descriptor_reader.read_u(5)? as u8;
+        Ok(HrdParameters {
             cpb_cnt_minus1,
             bit_rate_scale,
             cpb_size_scale,
@@ -42,14 +47,14 @@ impl HrdParameters {
             cpb_removal_delay_length_minus1,
             dpb_output_delay_length_minus1,
             time_offset_length
-        }
+        })
     }
 
     pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
         descriptor_writer.append_ue_v(self.cpb_cnt_minus1);
         descriptor_writer.append_u(4, self.bit_rate_scale.into());
         descriptor_writer.append_u(4, self.cpb_size_scale.into());
-        for sched_sel_idx in 0..=usize::try_from(self.cpb_cnt_minus1).unwrap() {
+        for sched_sel_idx in 0..=self.cpb_cnt_minus1 as usize {
             descriptor_writer.append_ue_v(self.bit_rate_value_minus1[sched_sel_idx]);
             descriptor_writer.append_ue_v(self.cpb_size_value_minus1[sched_sel_idx]);
             descriptor_writer.append_u1(self.cbr_flag[sched_sel_idx]);
@@ -59,4 +64,14 @@ impl HrdParameters {
         descriptor_writer.append_u(5, self.dpb_output_delay_length_minus1.into());
         descriptor_writer.append_u(5, self.time_offset_length.into());
     }
-}
\ No newline at end of file
+
+    // BitRate[SchedSelIdx] in bits per second (E.2.2)
+    pub fn get_bit_rate(&self, sched_sel_idx: usize) -> Option<u64> {
+        self.bit_rate_value_minus1.get(sched_sel_idx).map(|value_minus1| (value_minus1 + 1) << (6 + self.bit_rate_scale))
+    }
+
+    // CpbSize[SchedSelIdx] in bits (E.2.2)
+    pub fn get_cpb_size(&self, sched_sel_idx: usize) -> Option<u64> {
+        self.cpb_size_value_minus1.get(sched_sel_idx).map(|value_minus1| (value_minus1 + 1) << (4 + self.cpb_size_scale))
+    }
+}
@@ -0,0 +1,16 @@
+use super::{cpb_frame::CpbFrame, hrd_violation::HrdViolation};
+
+#[derive(Debug, Clone)]
+pub struct HrdReport {
+    pub bit_rate: u64,              // bits per second
+    pub cpb_size: u64,              // bits
+    pub cbr: bool,
+    pub frames: Vec<CpbFrame>,      // one per access unit, in decoding order
+    pub violations: Vec<HrdViolation>
+}
+
+impl HrdReport {
+    pub fn is_conformant(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
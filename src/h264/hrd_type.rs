@@ -0,0 +1,18 @@
+use std::fmt;
+
+// which HRD parameters a bitstream is checked against (C.1): the VCL HRD counts only VCL and
+// filler data NAL units (Type I bitstream), the NAL HRD counts every NAL unit (Type II bitstream)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HrdType {
+    Nal,
+    Vcl
+}
+
+impl fmt::Display for HrdType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HrdType::Nal => write!(f, "NAL"),
+            HrdType::Vcl => write!(f, "VCL")
+        }
+    }
+}
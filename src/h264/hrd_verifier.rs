@@ -0,0 +1,323 @@
+use crate::error::{Error, Result};
+
+use super::{buffering_period::BufferingPeriod, cpb_frame::CpbFrame, hrd_access_unit::HrdAccessUnit, hrd_report::HrdReport, hrd_type::HrdType, hrd_violation::HrdViolation, sps_nalu::SpsNalu};
+
+// slack for rounding in times (seconds) and fullness (bits)
+const TIME_TOLERANCE: f64 = 1e-9;
+const BITS_TOLERANCE: f64 = 1e-3;
+
+// hypothetical reference decoder (Annex C): runs access units through the CPB of one delivery
+// schedule and reports where the bitstream does not conform
+pub struct HrdVerifier {
+    pub hrd_type: HrdType,
+    pub sched_sel_idx: usize
+}
+
+impl HrdVerifier {
+    pub fn new(hrd_type: HrdType, sched_sel_idx: usize) -> Self {
+        HrdVerifier {
+            hrd_type,
+            sched_sel_idx
+        }
+    }
+
+    // access units in decoding order, the first one starting a buffering period
+    pub fn verify(&self, sps: &SpsNalu, access_units: &[HrdAccessUnit]) -> Result<HrdReport> {
+        let vui_parameters = sps.vui_parameters.as_ref()
+            .ok_or_else(|| Error::unsupported("HRD verification without VUI parameters"))?;
+        let hrd_parameters = match self.hrd_type {
+            HrdType::Nal => vui_parameters.nal_hrd_parameters.as_ref(),
+            HrdType::Vcl => vui_parameters.vcl_hrd_parameters.as_ref()
+        }.ok_or_else(|| Error::unsupported(format!("SPS without {} HRD parameters", self.hrd_type)))?;
+        let (num_units_in_tick, time_scale) = match (vui_parameters.num_units_in_tick, vui_parameters.time_scale) {
+            (Some(num_units_in_tick), Some(time_scale)) if num_units_in_tick > 0 && time_scale > 0 => (num_units_in_tick, time_scale),
+            _ => return Err(Error::unsupported("HRD verification without timing info"))
+        };
+        // clock tick
+        let tc = f64::from(num_units_in_tick) / f64::from(time_scale);
+        let (bit_rate, cpb_size, cbr) = match (hrd_parameters.get_bit_rate(self.sched_sel_idx), hrd_parameters.get_cpb_size(self.sched_sel_idx), hrd_parameters.cbr_flag.get(self.sched_sel_idx)) {
+            (Some(bit_rate), Some(cpb_size), Some(&cbr)) => (bit_rate, cpb_size, cbr),
+            _ => return Err(Error::spec_violation(format!("SchedSelIdx {} beyond cpb_cnt_minus1 {}", self.sched_sel_idx, hrd_parameters.cpb_cnt_minus1)))
+        };
+
+        let mut frames: Vec<CpbFrame> = vec![];
+        let mut violations = vec![];
+        // nominal removal time of the first access unit of the current buffering period
+        let mut buffering_period_removal_time = 0.0;
+        let mut initial_cpb_removal_delay = 0;
+        let mut initial_cpb_removal_delay_offset = 0;
+        for (index, access_unit) in access_units.iter().enumerate() {
+            let pic_timing = access_unit.pic_timing.as_ref()
+                .ok_or_else(|| Error::spec_violation(format!("access unit #{} has no pic_timing SEI", index)))?;
+            if index == 0 && access_unit.buffering_period.is_none() {
+                return Err(Error::spec_violation("first access unit has no buffering_period SEI"));
+            }
+
+            if let Some(buffering_period) = &access_unit.buffering_period {
+                (initial_cpb_removal_delay, initial_cpb_removal_delay_offset) = self.get_initial_cpb_removal_delay(buffering_period)?;
+                violations.extend(HrdVerifier::check_initial_cpb_removal_delay(index, initial_cpb_removal_delay, bit_rate, cpb_size));
+            }
+            // cpb_removal_delay counts from the previous access unit with a buffering period (C.1.2)
+            let nominal_removal_time = if index == 0 {
+                initial_cpb_removal_delay as f64 / 90000.0
+            } else {
+                buffering_period_removal_time + tc * pic_timing.cpb_removal_delay as f64
+            };
+            if access_unit.buffering_period.is_some() {
+                buffering_period_removal_time = nominal_removal_time;
+            }
+
+            // arrival at BitRate, as early as allowed by the initial delays for VBR (C.1.1)
+            let initial_arrival_time = match (access_unit.arrival_time, frames.last()) {
+                (Some(arrival_time), _) => arrival_time,
+                (None, None) => 0.0,
+                (None, Some(previous)) if cbr => previous.final_arrival_time,
+                (None, Some(previous)) => {
+                    let initial_delay = if access_unit.buffering_period.is_some() {
+                        initial_cpb_removal_delay
+                    } else {
+                        initial_cpb_removal_delay + initial_cpb_removal_delay_offset
+                    };
+                    previous.final_arrival_time.max(nominal_removal_time - initial_delay as f64 / 90000.0)
+                }
+            };
+            let final_arrival_time = initial_arrival_time + access_unit.size as f64 / bit_rate as f64;
+
+            // with low_delay_hrd_flag a late access unit is removed at a later clock tick instead (C.1.2)
+            let removal_time = if vui_parameters.low_delay_hrd_flag && final_arrival_time > nominal_removal_time {
+                nominal_removal_time + tc * ((final_arrival_time - nominal_removal_time) / tc).ceil()
+            } else {
+                nominal_removal_time
+            };
+            if final_arrival_time > removal_time + TIME_TOLERANCE {
+                violations.push(HrdViolation::CpbUnderflow {
+                    access_unit: index,
+                    final_arrival_time,
+                    removal_time
+                });
+            }
+            frames.push(CpbFrame {
+                initial_arrival_time,
+                final_arrival_time,
+                nominal_removal_time,
+                removal_time,
+                fullness_before_removal: 0.0,
+                fullness_after_removal: 0.0
+            });
+        }
+
+        HrdVerifier::fill_fullness(&mut frames, access_units, bit_rate);
+        for (index, frame) in frames.iter().enumerate() {
+            if frame.fullness_before_removal > cpb_size as f64 + BITS_TOLERANCE {
+                violations.push(HrdViolation::CpbOverflow {
+                    access_unit: index,
+                    fullness: frame.fullness_before_removal,
+                    cpb_size
+                });
+            }
+        }
+        Ok(HrdReport {
+            bit_rate,
+            cpb_size,
+            cbr,
+            frames,
+            violations
+        })
+    }
+
+    // bits in the CPB just before and after each removal: everything that has arrived by then,
+    // counting the access units still arriving at BitRate, less what was removed before
+    fn fill_fullness(frames: &mut [CpbFrame], access_units: &[HrdAccessUnit], bit_rate: u64) {
+        let mut arrived_index = 0;
+        let mut arrived_bits = 0.0;
+        let mut removed_bits = 0.0;
+        for index in 0..frames.len() {
+            let removal_time = frames[index].removal_time;
+            while arrived_index < frames.len() && frames[arrived_index].final_arrival_time <= removal_time {
+                arrived_bits += access_units[arrived_index].size as f64;
+                arrived_index += 1;
+            }
+            let arriving_bits: f64 = frames[arrived_index..].iter()
+                .zip(&access_units[arrived_index..])
+                .take_while(|(frame, _)| frame.initial_arrival_time < removal_time)
+                .map(|(frame, access_unit)| ((removal_time - frame.initial_arrival_time) * bit_rate as f64).min(access_unit.size as f64))
+                .sum();
+            let fullness_before_removal = arrived_bits + arriving_bits - removed_bits;
+            removed_bits += access_units[index].size as f64;
+            frames[index].fullness_before_removal = fullness_before_removal;
+            frames[index].fullness_after_removal = fullness_before_removal - access_units[index].size as f64;
+        }
+    }
+
+    // initial_cpb_removal_delay and its offset for the HRD type and SchedSelIdx
+    fn get_initial_cpb_removal_delay(&self, buffering_period: &BufferingPeriod) -> Result<(u64, u64)> {
+        let (delays, offsets) = match self.hrd_type {
+            HrdType::Nal => (&buffering_period.nal_initial_cpb_removal_delay, &buffering_period.nal_initial_cpb_removal_delay_offset),
+            HrdType::Vcl => (&buffering_period.vcl_initial_cpb_removal_delay, &buffering_period.vcl_initial_cpb_removal_delay_offset)
+        };
+        match (delays.get(self.sched_sel_idx), offsets.get(self.sched_sel_idx)) {
+            (Some(&delay), Some(&offset)) => Ok((delay, offset)),
+            _ => Err(Error::spec_violation(format!("buffering_period has no {} initial_cpb_removal_delay for SchedSelIdx {}", self.hrd_type, self.sched_sel_idx)))
+        }
+    }
+
+    // initial_cpb_removal_delay is neither 0 nor more than the CPB takes to fill (D.2.1)
+    fn check_initial_cpb_removal_delay(index: usize, initial_cpb_removal_delay: u64, bit_rate: u64, cpb_size: u64) -> Option<HrdViolation> {
+        // CpbSize reaches 2^53 bits, so multiply in 128 bits; a limit beyond u64 cannot be exceeded
+        let limit = u64::try_from(90000 * u128::from(cpb_size) / u128::from(bit_rate)).unwrap_or(u64::MAX);
+        if initial_cpb_removal_delay == 0 {
+            Some(HrdViolation::InitialCpbRemovalDelayZero {
+                access_unit: index
+            })
+        } else if initial_cpb_removal_delay > limit {
+            Some(HrdViolation::InitialCpbRemovalDelayTooLarge {
+                access_unit: index,
+                initial_cpb_removal_delay,
+                limit
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{h264::{buffering_period::BufferingPeriod, hrd_access_unit::HrdAccessUnit, hrd_type::HrdType, hrd_violation::HrdViolation, pic_timing::PicTiming, sps_nalu::SpsNalu}, test_util::{to_bytes, ue}};
+
+    use super::HrdVerifier;
+
+    // Baseline SPS with a clock tick of 0.1 s and a NAL HRD of 64000 bits/s and 32000 bits by default
+    fn get_sps(cpb_size_value_minus1: u64, cbr: bool, low_delay_hrd_flag: bool) -> SpsNalu {
+        let rbsp = to_bytes(&format!(
+            "01000010 00000000 00011110 1 1 011 010 0 000010100 0001111 1 1 0 1 0 0 0 0 1 {:032b} {:032b} 1 1 {} 0000 0000 {} {} {} 10111 10111 10111 11000 0 {} 0 0",
+            1, 10, ue(0), ue(999), ue(cpb_size_value_minus1), u8::from(cbr), u8::from(low_delay_hrd_flag)
+        ));
+        SpsNalu::read(&mut Cursor::new(&rbsp), rbsp.len() as u32, 0x67).unwrap()
+    }
+
+    // access units of the given sizes removed a clock tick apart, the first one 0.1 s after it starts arriving
+    fn get_access_units(sizes: &[u64], initial_cpb_removal_delay: u64) -> Vec<HrdAccessUnit> {
+        sizes.iter().enumerate().map(|(index, &size)| HrdAccessUnit {
+            size,
+            buffering_period: (index == 0).then(|| BufferingPeriod {
+                seq_parameter_set_id: 0,
+                nal_initial_cpb_removal_delay: vec![initial_cpb_removal_delay],
+                nal_initial_cpb_removal_delay_offset: vec![0],
+                vcl_initial_cpb_removal_delay: vec![],
+                vcl_initial_cpb_removal_delay_offset: vec![]
+            }),
+            pic_timing: Some(PicTiming {
+                cpb_removal_delay: index as u64,
+                ..Default::default()
+            }),
+            arrival_time: None
+        }).collect()
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn verify_conforming_vbr() {
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&get_sps(1999, false, false), &get_access_units(&[3200; 3], 9000)).unwrap();
+        assert_eq!((report.bit_rate, report.cpb_size, report.cbr), (64000, 32000, false));
+        assert!(report.is_conformant());
+        // each access unit arrives no earlier than 0.1 s before its removal
+        for (index, frame) in report.frames.iter().enumerate() {
+            let removal_time = 0.1 * (index + 1) as f64;
+            assert_near(frame.initial_arrival_time, removal_time - 0.1);
+            assert_near(frame.final_arrival_time, removal_time - 0.05);
+            assert_near(frame.nominal_removal_time, removal_time);
+            assert_near(frame.removal_time, removal_time);
+            assert_near(frame.fullness_before_removal, 3200.0);
+            assert_near(frame.fullness_after_removal, 0.0);
+        }
+    }
+
+    #[test]
+    fn verify_conforming_cbr() {
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&get_sps(1999, true, false), &get_access_units(&[3200; 3], 9000)).unwrap();
+        assert!(report.cbr && report.is_conformant());
+        // access units arrive back to back
+        let final_arrival_times: Vec<f64> = report.frames.iter().map(|frame| frame.final_arrival_time).collect();
+        for (actual, expected) in final_arrival_times.into_iter().zip([0.05, 0.1, 0.15]) {
+            assert_near(actual, expected);
+        }
+        let fullness: Vec<(f64, f64)> = report.frames.iter().map(|frame| (frame.fullness_before_removal, frame.fullness_after_removal)).collect();
+        for (actual, expected) in fullness.into_iter().zip([(6400.0, 3200.0), (6400.0, 3200.0), (3200.0, 0.0)]) {
+            assert_near(actual.0, expected.0);
+            assert_near(actual.1, expected.1);
+        }
+    }
+
+    #[test]
+    fn verify_underflow() {
+        // the second access unit takes 0.15 s to arrive from 0.1 s, after its removal at 0.2 s
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&get_sps(1999, false, false), &get_access_units(&[3200, 9600, 3200], 9000)).unwrap();
+        match &report.violations[..] {
+            [HrdViolation::CpbUnderflow { access_unit: 1, final_arrival_time, removal_time }] => {
+                assert_near(*final_arrival_time, 0.25);
+                assert_near(*removal_time, 0.2);
+            },
+            violations => panic!("unexpected violations {:?}", violations)
+        }
+    }
+
+    #[test]
+    fn verify_low_delay_late_removal() {
+        // with low_delay_hrd_flag the late access unit is removed at the next clock tick instead
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&get_sps(1999, false, true), &get_access_units(&[3200, 9600, 3200], 9000)).unwrap();
+        assert!(report.is_conformant());
+        assert_near(report.frames[1].nominal_removal_time, 0.2);
+        assert_near(report.frames[1].removal_time, 0.3);
+    }
+
+    #[test]
+    fn verify_overflow() {
+        // a CBR stream of 8000 bits/s into a CPB of 8000 bits, while 3200 bits are removed at each tick
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&get_sps(499, true, false), &get_access_units(&[3200; 5], 9000)).unwrap();
+        let overflows: Vec<(usize, f64)> = report.violations.iter().map(|violation| match violation {
+            HrdViolation::CpbOverflow { access_unit, fullness, cpb_size: 8000 } => (*access_unit, *fullness),
+            violation => panic!("unexpected violation {:?}", violation)
+        }).collect();
+        assert_eq!(overflows.len(), 2);
+        for ((access_unit, fullness), expected_access_unit) in overflows.into_iter().zip([1, 2]) {
+            assert_eq!(access_unit, expected_access_unit);
+            assert_near(fullness, 9600.0);
+        }
+    }
+
+    #[test]
+    fn verify_initial_cpb_removal_delay() {
+        let sps = get_sps(1999, false, false);
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&sps, &get_access_units(&[3200; 3], 0)).unwrap();
+        assert!(report.violations.contains(&HrdViolation::InitialCpbRemovalDelayZero {
+            access_unit: 0
+        }));
+        // the CPB takes 0.5 s to fill
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&sps, &get_access_units(&[3200; 3], 45001)).unwrap();
+        assert!(report.violations.contains(&HrdViolation::InitialCpbRemovalDelayTooLarge {
+            access_unit: 0,
+            initial_cpb_removal_delay: 45001,
+            limit: 45000
+        }));
+        let report = HrdVerifier::new(HrdType::Nal, 0).verify(&sps, &get_access_units(&[3200; 3], 45000)).unwrap();
+        assert!(report.is_conformant());
+    }
+
+    #[test]
+    fn check_initial_delay_against_largest_cpb() {
+        // cpb_size_value_minus1 of 2^32 - 2 at cpb_size_scale 15
+        let cpb_size = (u64::from(u32::MAX)) << 19;
+        assert_eq!(HrdVerifier::check_initial_cpb_removal_delay(0, 90000, 64, cpb_size), None);
+        assert_eq!(HrdVerifier::check_initial_cpb_removal_delay(0, 90001, 1 << 53, 1 << 53), Some(HrdViolation::InitialCpbRemovalDelayTooLarge {
+            access_unit: 0,
+            initial_cpb_removal_delay: 90001,
+            limit: 90000
+        }));
+    }
+}
@@ -0,0 +1,11 @@
+// a way in which a bitstream does not conform to the HRD (C.3, D.2.1); access_unit is the index in decoding order
+#[derive(Debug, Clone, PartialEq)]
+pub enum HrdViolation {
+    // the access unit has not fully arrived when it is due for removal
+    CpbUnderflow { access_unit: usize, final_arrival_time: f64, removal_time: f64 },
+    // the CPB holds more than CpbSize bits just before the access unit is removed
+    CpbOverflow { access_unit: usize, fullness: f64, cpb_size: u64 },
+    InitialCpbRemovalDelayZero { access_unit: usize },
+    // initial_cpb_removal_delay exceeds 90000 * CpbSize / BitRate
+    InitialCpbRemovalDelayTooLarge { access_unit: usize, initial_cpb_removal_delay: u64, limit: u64 }
+}
@@ -0,0 +1,49 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// mastering_display_colour_volume SEI message (D.1.29); chromaticities in units of 0.00002,
+// luminances in units of 0.0001 candelas per square metre
+#[derive(Debug, Clone)]
+pub struct MasteringDisplayColourVolume {
+    pub display_primaries_x: [u16; 3],
+    pub display_primaries_y: [u16; 3],
+    pub white_point_x: u16,
+    pub white_point_y: u16,
+    pub max_display_mastering_luminance: u32,
+    pub min_display_mastering_luminance: u32
+}
+
+impl MasteringDisplayColourVolume {
+    pub fn read(descriptor_reader: &mut DescriptorReader) -> Result<Self> {
+        let mut display_primaries_x = [0; 3];
+        let mut display_primaries_y = [0; 3];
+        for c in 0..3 {
+            display_primaries_x[c] = descriptor_reader.read_u16()?;
+            display_primaries_y[c] = descriptor_reader.read_u16()?;
+        }
+        let white_point_x = descriptor_reader.read_u16()?;
+        let white_point_y = descriptor_reader.read_u16()?;
+        let max_display_mastering_luminance = descriptor_reader.read_u32()?;
+        let min_display_mastering_luminance = descriptor_reader.read_u32()?;
+        Ok(MasteringDisplayColourVolume {
+            display_primaries_x,
+            display_primaries_y,
+            white_point_x,
+            white_point_y,
+            max_display_mastering_luminance,
+            min_display_mastering_luminance
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        for c in 0..3 {
+            descriptor_writer.append_u16(self.display_primaries_x[c]);
+            descriptor_writer.append_u16(self.display_primaries_y[c]);
+        }
+        descriptor_writer.append_u16(self.white_point_x);
+        descriptor_writer.append_u16(self.white_point_y);
+        descriptor_writer.append_u32(self.max_display_mastering_luminance);
+        descriptor_writer.append_u32(self.min_display_mastering_luminance);
+    }
+}
@@ -2,14 +2,44 @@ pub mod nalu;
 pub mod sps_nalu;
 pub mod vui_parameters;
 pub mod hrd_parameters;
+pub mod scaling_list;
+pub mod scaling_matrix;
 pub mod pps_nalu;
 pub mod sps_pps_provider;
+pub mod parameter_sets;
 pub mod sei_nalu;
+pub mod sei_message;
+pub mod sei_payload;
+pub mod buffering_period;
+pub mod pic_timing;
+pub mod clock_timestamp;
+pub mod user_data_registered_itu_t_t35;
+pub mod user_data_unregistered;
+pub mod recovery_point;
+pub mod frame_packing_arrangement;
+pub mod mastering_display_colour_volume;
+pub mod content_light_level_info;
 pub mod slice_header;
+pub mod ref_pic_list_modification;
+pub mod pred_weight_table;
+pub mod pred_weight;
+pub mod dec_ref_pic_marking;
 pub mod idr_nalu;
 pub mod non_idr_nalu;
 pub mod delim_nalu;
 pub mod unknown_nalu;
-mod opaque_data;
-mod descriptor_reader;
-mod descriptor_writer;
+pub mod access_unit;
+pub mod pic_order_cnt;
+pub mod poc_decoder;
+pub mod timecode;
+pub mod timecode_decoder;
+pub mod hrd_type;
+pub mod hrd_access_unit;
+pub mod cpb_frame;
+pub mod hrd_violation;
+pub mod hrd_report;
+pub mod hrd_verifier;
+pub mod opaque_data;
+pub mod descriptor_reader;
+pub mod descriptor_writer;
+mod emulation_prevention;
@@ -1,4 +1,6 @@
-use std::{any::Any, fmt, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use crate::error::Result;
 
 use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, opaque_data::OpaqueData, slice_header::SliceHeader, sps_pps_provider::SpsPpsProvider};
 
@@ -6,34 +8,40 @@ pub struct NonIdrNalu {
     pub header: u8,
     pub slice_header: SliceHeader,
     remaining: OpaqueData,
+    pub cabac_zero_words: u64,      // 0x0000 words appended after the slice data
     pub payload_size: u32
 }
 
 impl NonIdrNalu {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u32, header: u8, sps_pps_provider: &impl SpsPpsProvider) -> io::Result<Self> {
-        let mut descriptor_reader = DescriptorReader::new(rdr, len);
-        let slice_header = SliceHeader::read(&mut descriptor_reader, false, sps_pps_provider);
-        let remaining = descriptor_reader.read_to_end();
-        descriptor_reader.read_rbsp_trailing_bits();
+    pub fn read(rdr: &mut (impl Read + Seek), len: u32, header: u8, sps_pps_provider: &impl SpsPpsProvider) -> Result<Self> {
+        let mut descriptor_reader = DescriptorReader::new(rdr, len)?;
+        let slice_header = SliceHeader::read(&mut descriptor_reader, header, sps_pps_provider)?;
+        let remaining = descriptor_reader.read_to_end()?;
+        let cabac_zero_words = descriptor_reader.read_rbsp_slice_trailing_bits()?;
 
         Ok(NonIdrNalu {
             header,
             slice_header,
             remaining,
+            cabac_zero_words,
             payload_size: len
         })
     }
 }
 
 impl Nalu for NonIdrNalu {
-    fn write(&self, wtr: &mut dyn Write, sps_pps_provider: &dyn SpsPpsProvider) {
+    fn write(&self, wtr: &mut dyn Write, sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
         let mut descriptor_writer = DescriptorWriter::new(wtr);
-        self.slice_header.write(&mut descriptor_writer, sps_pps_provider);
+        self.slice_header.write(&mut descriptor_writer, sps_pps_provider)?;
         descriptor_writer.append_all(&self.remaining);
-        descriptor_writer.append_rbsp_trailing_bits();
-        descriptor_writer.write_with_header(self.header);
+        descriptor_writer.append_rbsp_slice_trailing_bits(self.cabac_zero_words);
+        descriptor_writer.write_with_header(self.header)
     }
     
+    fn get_nal_unit_type(&self) -> u8 {
+        1
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
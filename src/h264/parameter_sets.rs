@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use super::{pps_nalu::PpsNalu, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider};
+
+// parameter sets by id, a later set with the same id replaces the earlier one
+#[derive(Debug, Clone, Default)]
+pub struct ParameterSets {
+    pub sps: HashMap<u64, SpsNalu>,
+    pub pps: HashMap<u64, PpsNalu>
+}
+
+impl ParameterSets {
+    pub fn new() -> Self {
+        ParameterSets::default()
+    }
+
+    pub fn insert_sps(&mut self, sps: SpsNalu) {
+        self.sps.insert(sps.seq_parameter_set_id, sps);
+    }
+
+    pub fn insert_pps(&mut self, pps: PpsNalu) {
+        self.pps.insert(pps.pic_parameter_set_id, pps);
+    }
+}
+
+impl SpsPpsProvider for ParameterSets {
+    fn get_pps(&self, id: u64) -> Option<&PpsNalu> {
+        self.pps.get(&id)
+    }
+
+    fn get_sps(&self, id: u64) -> Option<&SpsNalu> {
+        self.sps.get(&id)
+    }
+}
@@ -0,0 +1,7 @@
+// picture order counts of a coded frame or field (8.2.1); a field only has the count of its own parity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PicOrderCnt {
+    pub top_field_order_cnt: Option<i64>,
+    pub bottom_field_order_cnt: Option<i64>,
+    pub pic_order_cnt: i64
+}
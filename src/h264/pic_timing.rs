@@ -0,0 +1,132 @@
+use crate::error::{Error, Result};
+
+use super::{clock_timestamp::ClockTimestamp, descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, hrd_parameters::HrdParameters, sps_nalu::SpsNalu};
+
+// pic_timing SEI message (D.1.3); the delays are present when the SPS has HRD parameters,
+// pic_struct and the clock timestamps when it sets pic_struct_present_flag
+#[derive(Debug, Clone, Default)]
+pub struct PicTiming {
+    pub cpb_removal_delay: u64,
+    pub dpb_output_delay: u64,
+    pub pic_struct: u8,
+    pub clock_timestamps: Vec<Option<ClockTimestamp>>     // None where clock_timestamp_flag is 0
+}
+
+impl PicTiming {
+    pub fn read(descriptor_reader: &mut DescriptorReader, sps: &SpsNalu) -> Result<Self> {
+        let mut pic_timing = PicTiming::default();
+        if let Some(hrd_parameters) = PicTiming::get_hrd_parameters(sps) {
+            pic_timing.cpb_removal_delay = descriptor_reader.read_u(hrd_parameters.cpb_removal_delay_length_minus1 + 1)?;
+            pic_timing.dpb_output_delay = descriptor_reader.read_u(hrd_parameters.dpb_output_delay_length_minus1 + 1)?;
+        }
+        if sps.vui_parameters.as_ref().is_some_and(|vui_parameters| vui_parameters.pic_struct_present_flag) {
+            pic_timing.pic_struct = descriptor_reader.read_u(4)? as u8;
+            let time_offset_length = PicTiming::get_time_offset_length(sps);
+            for _i in 0..PicTiming::get_num_clock_ts(pic_timing.pic_struct)? {
+                let clock_timestamp_flag = descriptor_reader.read_u1()?;
+                if clock_timestamp_flag {
+                    pic_timing.clock_timestamps.push(Some(ClockTimestamp::read(descriptor_reader, time_offset_length)?));
+                } else {
+                    pic_timing.clock_timestamps.push(None);
+                }
+            }
+        }
+        Ok(pic_timing)
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, sps: &SpsNalu) {
+        if let Some(hrd_parameters) = PicTiming::get_hrd_parameters(sps) {
+            descriptor_writer.append_u(hrd_parameters.cpb_removal_delay_length_minus1 + 1, self.cpb_removal_delay);
+            descriptor_writer.append_u(hrd_parameters.dpb_output_delay_length_minus1 + 1, self.dpb_output_delay);
+        }
+        if sps.vui_parameters.as_ref().is_some_and(|vui_parameters| vui_parameters.pic_struct_present_flag) {
+            descriptor_writer.append_u(4, self.pic_struct.into());
+            let time_offset_length = PicTiming::get_time_offset_length(sps);
+            for clock_timestamp in &self.clock_timestamps {
+                descriptor_writer.append_u1(clock_timestamp.is_some());
+                if let Some(clock_timestamp) = clock_timestamp {
+                    clock_timestamp.write(descriptor_writer, time_offset_length);
+                }
+            }
+        }
+    }
+
+    // NumClockTS from Table D-1
+    fn get_num_clock_ts(pic_struct: u8) -> Result<usize> {
+        match pic_struct {
+            0..=2 => Ok(1),
+            3 | 4 | 7 => Ok(2),
+            5 | 6 | 8 => Ok(3),
+            _ => Err(Error::spec_violation(format!("reserved pic_struct {}", pic_struct)))
+        }
+    }
+
+    // time_offset_length is inferred to be 24 without HRD parameters (E.2.2)
+    fn get_time_offset_length(sps: &SpsNalu) -> u8 {
+        PicTiming::get_hrd_parameters(sps).map_or(24, |hrd_parameters| hrd_parameters.time_offset_length)
+    }
+
+    // CpbDpbDelaysPresentFlag is set by either HRD, whose delay lengths must then be equal
+    fn get_hrd_parameters(sps: &SpsNalu) -> Option<&HrdParameters> {
+        let vui_parameters = sps.vui_parameters.as_ref()?;
+        vui_parameters.nal_hrd_parameters.as_ref().or(vui_parameters.vcl_hrd_parameters.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use crate::{h264::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, sps_nalu::SpsNalu}, test_util::{BASELINE_SPS, to_bytes}};
+
+    use super::PicTiming;
+
+    // Main 320x240 with pic_struct_present_flag and NAL HRD parameters of 24-bit delays and time_offset
+    const HRD_SPS: [u8; 26] = [0x4d, 0x00, 0x1e, 0xf4, 0x21, 0x34, 0x20, 0x00, 0x00, 0x7d, 0x20, 0x00, 0x1d, 0x4c, 0x1c, 0x00, 0x00, 0x27, 0x10, 0x00, 0x09, 0xc4, 0x17, 0xbd, 0xf0, 0x50];
+
+    fn round_trip(sps: &[u8], rbsp: Vec<u8>) -> PicTiming {
+        let sps = SpsNalu::read(&mut Cursor::new(sps), sps.len() as u32, 0x67).unwrap();
+        let pic_timing = PicTiming::read(&mut DescriptorReader::from_rbsp(rbsp.clone()), &sps).unwrap();
+
+        let mut sink = io::sink();
+        let mut descriptor_writer = DescriptorWriter::new(&mut sink);
+        pic_timing.write(&mut descriptor_writer, &sps);
+        descriptor_writer.append_rbsp_trailing_bits();
+        assert_eq!(descriptor_writer.into_rbsp(), rbsp);
+        pic_timing
+    }
+
+    #[test]
+    fn round_trip_with_hrd() {
+        // delays 2 and 4, pic_struct 0 and a clock timestamp with seconds 5 and minutes 3, time_offset 5
+        let pic_timing = round_trip(&HRD_SPS, to_bytes(concat!(
+            "000000000000000000000010 000000000000000000000100 0000 ",
+            "1 00 0 00000 0 0 0 00001100 1 000101 1 000011 0 000000000000000000000101"
+        )));
+        assert_eq!((pic_timing.cpb_removal_delay, pic_timing.dpb_output_delay), (2, 4));
+        assert_eq!(pic_timing.pic_struct, 0);
+        let clock_timestamp = pic_timing.clock_timestamps[0].as_ref().unwrap();
+        assert!(!clock_timestamp.full_timestamp_flag);
+        assert_eq!(clock_timestamp.n_frames, 12);
+        assert_eq!((clock_timestamp.seconds_value, clock_timestamp.minutes_value), (5, 3));
+        assert!(!clock_timestamp.hours_flag);
+        assert_eq!(clock_timestamp.time_offset, 5);
+    }
+
+    #[test]
+    fn round_trip_without_hrd() {
+        // pic_struct 3 with only the second of its two clock timestamps, full with a 24-bit time_offset of -1
+        let pic_timing = round_trip(&BASELINE_SPS, to_bytes(concat!(
+            "0011 0 ",
+            "1 01 1 00000 1 0 0 00000001 111011 111011 10111 111111111111111111111111"
+        )));
+        assert_eq!((pic_timing.cpb_removal_delay, pic_timing.dpb_output_delay), (0, 0));
+        assert_eq!(pic_timing.pic_struct, 3);
+        assert_eq!(pic_timing.clock_timestamps.len(), 2);
+        assert!(pic_timing.clock_timestamps[0].is_none());
+        let clock_timestamp = pic_timing.clock_timestamps[1].as_ref().unwrap();
+        assert_eq!((clock_timestamp.ct_type, clock_timestamp.nuit_field_based_flag), (1, true));
+        assert_eq!((clock_timestamp.hours_value, clock_timestamp.minutes_value, clock_timestamp.seconds_value), (23, 59, 59));
+        assert_eq!(clock_timestamp.time_offset, -1);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::{Error, Result};
+
+use super::{dec_ref_pic_marking::DecRefPicMarking, pic_order_cnt::PicOrderCnt, slice_header::SliceHeader, sps_nalu::SpsNalu};
+
+// derives picture order counts of coded pictures in decoding order (8.2.1)
+#[derive(Default)]
+pub struct PocDecoder {
+    prev_pic_order_cnt_msb: i64,
+    prev_pic_order_cnt_lsb: i64,
+    prev_frame_num_offset: i64,
+    prev_frame_num: u64,
+    current: Option<(u8, SliceHeader, PicOrderCnt)>     // first slice of the last decoded picture and its counts
+}
+
+impl PocDecoder {
+    pub fn new() -> Self {
+        PocDecoder::default()
+    }
+
+    // counts of the picture a slice belongs to, with slices passed in decoding order; a picture with
+    // memory_management_control_operation 5 gets its counts after the reset, relative to the pictures following it
+    pub fn decode(&mut self, nal_ref_idc: u8, slice_header: &SliceHeader, sps: &SpsNalu) -> Result<PicOrderCnt> {
+        if let Some((current_nal_ref_idc, current, pic_order_cnt)) = &self.current {
+            let covers_first_mb_again = slice_header.redundant_pic_cnt == 0 && slice_header.first_mb_in_slice == 0 && current.first_mb_in_slice == 0;
+            if !slice_header.differs_in_picture(nal_ref_idc, current, *current_nal_ref_idc) && !covers_first_mb_again {
+                return Ok(*pic_order_cnt);
+            }
+        }
+
+        let max_frame_num: i64 = 1 << (sps.log2_max_frame_num_minus4 + 4);
+        let frame_num = slice_header.frame_num as i64;
+        let frame_num_offset = if slice_header.idr_pic_flag {
+            0
+        } else if self.prev_frame_num > slice_header.frame_num {
+            self.prev_frame_num_offset + max_frame_num
+        } else {
+            self.prev_frame_num_offset
+        };
+
+        let mut pic_order_cnt_msb = 0;
+        let (top_field_order_cnt, bottom_field_order_cnt) = match sps.pic_order_cnt_type {
+            // 8.2.1.1
+            0 => {
+                let (prev_pic_order_cnt_msb, prev_pic_order_cnt_lsb) = if slice_header.idr_pic_flag {
+                    (0, 0)
+                } else {
+                    (self.prev_pic_order_cnt_msb, self.prev_pic_order_cnt_lsb)
+                };
+                let max_pic_order_cnt_lsb: i64 = 1 << (sps.log2_max_pic_order_cnt_lsb_minus4 + 4);
+                let pic_order_cnt_lsb = slice_header.pic_order_cnt_lsb as i64;
+                pic_order_cnt_msb = if pic_order_cnt_lsb < prev_pic_order_cnt_lsb && prev_pic_order_cnt_lsb - pic_order_cnt_lsb >= max_pic_order_cnt_lsb / 2 {
+                    prev_pic_order_cnt_msb + max_pic_order_cnt_lsb
+                } else if pic_order_cnt_lsb > prev_pic_order_cnt_lsb && pic_order_cnt_lsb - prev_pic_order_cnt_lsb > max_pic_order_cnt_lsb / 2 {
+                    prev_pic_order_cnt_msb - max_pic_order_cnt_lsb
+                } else {
+                    prev_pic_order_cnt_msb
+                };
+                let top_field_order_cnt = pic_order_cnt_msb + pic_order_cnt_lsb;
+                if slice_header.field_pic_flag {
+                    (top_field_order_cnt, top_field_order_cnt)
+                } else {
+                    (top_field_order_cnt, top_field_order_cnt + slice_header.delta_pic_order_cnt_bottom)
+                }
+            }
+            // 8.2.1.2
+            1 => {
+                let num_ref_frames_in_pic_order_cnt_cycle = sps.offset_for_ref_frame.len() as i64;
+                let mut abs_frame_num = if num_ref_frames_in_pic_order_cnt_cycle != 0 { frame_num_offset + frame_num } else { 0 };
+                if nal_ref_idc == 0 && abs_frame_num > 0 {
+                    abs_frame_num -= 1;
+                }
+                let mut expected_pic_order_cnt = 0;
+                if abs_frame_num > 0 {
+                    let pic_order_cnt_cycle_cnt = (abs_frame_num - 1) / num_ref_frames_in_pic_order_cnt_cycle;
+                    let frame_num_in_pic_order_cnt_cycle = ((abs_frame_num - 1) % num_ref_frames_in_pic_order_cnt_cycle) as usize;
+                    let expected_delta_per_pic_order_cnt_cycle: i64 = sps.offset_for_ref_frame.iter().sum();
+                    expected_pic_order_cnt = pic_order_cnt_cycle_cnt * expected_delta_per_pic_order_cnt_cycle
+                        + sps.offset_for_ref_frame[..=frame_num_in_pic_order_cnt_cycle].iter().sum::<i64>();
+                }
+                if nal_ref_idc == 0 {
+                    expected_pic_order_cnt += sps.offset_for_non_ref_pic;
+                }
+                let top_field_order_cnt = expected_pic_order_cnt + slice_header.delta_pic_order_cnt[0];
+                if slice_header.field_pic_flag {
+                    (top_field_order_cnt, expected_pic_order_cnt + sps.offset_for_top_to_bottom_field + slice_header.delta_pic_order_cnt[0])
+                } else {
+                    (top_field_order_cnt, top_field_order_cnt + sps.offset_for_top_to_bottom_field + slice_header.delta_pic_order_cnt[1])
+                }
+            }
+            // 8.2.1.3
+            2 => {
+                let temp_pic_order_cnt = if slice_header.idr_pic_flag {
+                    0
+                } else if nal_ref_idc == 0 {
+                    2 * (frame_num_offset + frame_num) - 1
+                } else {
+                    2 * (frame_num_offset + frame_num)
+                };
+                (temp_pic_order_cnt, temp_pic_order_cnt)
+            }
+            pic_order_cnt_type => return Err(Error::spec_violation(format!("pic_order_cnt_type {} out of range", pic_order_cnt_type)))
+        };
+
+        let mut pic_order_cnt = if !slice_header.field_pic_flag {
+            PicOrderCnt {
+                top_field_order_cnt: Some(top_field_order_cnt),
+                bottom_field_order_cnt: Some(bottom_field_order_cnt),
+                pic_order_cnt: top_field_order_cnt.min(bottom_field_order_cnt)
+            }
+        } else if slice_header.bottom_field_flag {
+            PicOrderCnt {
+                top_field_order_cnt: None,
+                bottom_field_order_cnt: Some(bottom_field_order_cnt),
+                pic_order_cnt: bottom_field_order_cnt
+            }
+        } else {
+            PicOrderCnt {
+                top_field_order_cnt: Some(top_field_order_cnt),
+                bottom_field_order_cnt: None,
+                pic_order_cnt: top_field_order_cnt
+            }
+        };
+
+        let has_memory_management_control_operation_5 = slice_header.dec_ref_pic_marking.as_ref()
+            .is_some_and(DecRefPicMarking::has_memory_management_control_operation_5);
+        if has_memory_management_control_operation_5 {
+            let temp_pic_order_cnt = pic_order_cnt.pic_order_cnt;
+            pic_order_cnt.top_field_order_cnt = pic_order_cnt.top_field_order_cnt.map(|cnt| cnt - temp_pic_order_cnt);
+            pic_order_cnt.bottom_field_order_cnt = pic_order_cnt.bottom_field_order_cnt.map(|cnt| cnt - temp_pic_order_cnt);
+            pic_order_cnt.pic_order_cnt = 0;
+        }
+
+        // type 0 counts from the previous reference picture, types 1 and 2 from the previous picture
+        if nal_ref_idc != 0 {
+            if has_memory_management_control_operation_5 {
+                self.prev_pic_order_cnt_msb = 0;
+                self.prev_pic_order_cnt_lsb = if slice_header.bottom_field_flag { 0 } else { pic_order_cnt.top_field_order_cnt.unwrap_or(0) };
+            } else {
+                self.prev_pic_order_cnt_msb = pic_order_cnt_msb;
+                self.prev_pic_order_cnt_lsb = slice_header.pic_order_cnt_lsb as i64;
+            }
+        }
+        if has_memory_management_control_operation_5 {
+            self.prev_frame_num_offset = 0;
+            self.prev_frame_num = 0;
+        } else {
+            self.prev_frame_num_offset = frame_num_offset;
+            self.prev_frame_num = slice_header.frame_num;
+        }
+        self.current = Some((nal_ref_idc, slice_header.clone(), pic_order_cnt));
+        Ok(pic_order_cnt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{h264::{dec_ref_pic_marking::DecRefPicMarking, descriptor_reader::DescriptorReader, parameter_sets::ParameterSets, pps_nalu::PpsNalu, slice_header::SliceHeader, sps_nalu::SpsNalu}, test_util::{BASELINE_SPS, to_bytes}};
+
+    use super::PocDecoder;
+
+    // SPS with a 4-bit frame_num and pic_order_cnt_type 2, and the header of an IDR I slice referring to it
+    fn get_sps_and_slice_header() -> (SpsNalu, SliceHeader) {
+        let sps = SpsNalu::read(&mut Cursor::new(BASELINE_SPS), BASELINE_SPS.len() as u32, 0x67).unwrap();
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert_sps(sps.clone());
+        let pps = to_bytes("1 1 0 0 1 1 1 0 00 1 1 1 1 0 0");
+        parameter_sets.insert_pps(PpsNalu::read(&mut Cursor::new(&pps), pps.len() as u32, 0x68, &parameter_sets).unwrap());
+        let rbsp = to_bytes("1 0001000 1 0000 1 0 0 1 1 1 1");
+        (sps, SliceHeader::read(&mut DescriptorReader::from_rbsp(rbsp), 0x65, &parameter_sets).unwrap())
+    }
+
+    // the first slice of a picture following the IDR picture
+    fn get_picture(slice_header: &SliceHeader, frame_num: u64, pic_order_cnt_lsb: u64) -> SliceHeader {
+        let mut picture = slice_header.clone();
+        picture.idr_pic_flag = false;
+        picture.frame_num = frame_num;
+        picture.pic_order_cnt_lsb = pic_order_cnt_lsb;
+        picture
+    }
+
+    fn decode(sps: &SpsNalu, pictures: &[(u8, SliceHeader)]) -> Vec<i64> {
+        let mut poc_decoder = PocDecoder::new();
+        pictures.iter().map(|(nal_ref_idc, slice_header)| poc_decoder.decode(*nal_ref_idc, slice_header, sps).unwrap().pic_order_cnt).collect()
+    }
+
+    #[test]
+    fn decode_type_0_lsb_wrap() {
+        let (mut sps, idr) = get_sps_and_slice_header();
+        sps.pic_order_cnt_type = 0;
+        sps.log2_max_pic_order_cnt_lsb_minus4 = 0;
+        let mut second_slice = get_picture(&idr, 3, 2);
+        second_slice.first_mb_in_slice = 10;
+        // lsb 2 after 14 wraps forward, non-reference lsb 15 after 2 wraps back; only reference pictures count
+        assert_eq!(decode(&sps, &[
+            (3, idr.clone()),
+            (2, get_picture(&idr, 1, 8)),
+            (2, get_picture(&idr, 2, 14)),
+            (2, get_picture(&idr, 3, 2)),
+            (2, second_slice),
+            (0, get_picture(&idr, 4, 15)),
+            (2, get_picture(&idr, 4, 4))
+        ]), [0, 8, 14, 18, 18, 15, 20]);
+
+        // the bottom field of a frame may come first
+        let mut poc_decoder = PocDecoder::new();
+        let mut bottom_first = idr;
+        bottom_first.delta_pic_order_cnt_bottom = -1;
+        let pic_order_cnt = poc_decoder.decode(3, &bottom_first, &sps).unwrap();
+        assert_eq!((pic_order_cnt.top_field_order_cnt, pic_order_cnt.bottom_field_order_cnt, pic_order_cnt.pic_order_cnt), (Some(0), Some(-1), -1));
+    }
+
+    #[test]
+    fn decode_type_1_offset_cycle() {
+        let (mut sps, idr) = get_sps_and_slice_header();
+        sps.pic_order_cnt_type = 1;
+        sps.offset_for_non_ref_pic = -2;
+        sps.offset_for_ref_frame = vec![4, 2];
+        let mut non_reference = get_picture(&idr, 4, 0);
+        non_reference.delta_pic_order_cnt[0] = 1;
+        // a cycle of two reference frames adds 6; the non-reference picture counts from the previous one
+        assert_eq!(decode(&sps, &[
+            (3, idr.clone()),
+            (2, get_picture(&idr, 1, 0)),
+            (2, get_picture(&idr, 2, 0)),
+            (2, get_picture(&idr, 3, 0)),
+            (0, non_reference),
+            (2, get_picture(&idr, 4, 0))
+        ]), [0, 4, 6, 10, 9, 12]);
+    }
+
+    #[test]
+    fn decode_type_2_non_reference_pictures() {
+        let (sps, idr) = get_sps_and_slice_header();
+        // frame_num wraps from 15 to 0; a non-reference picture comes just before the next reference one
+        assert_eq!(decode(&sps, &[
+            (3, idr.clone()),
+            (2, get_picture(&idr, 1, 0)),
+            (0, get_picture(&idr, 2, 0)),
+            (2, get_picture(&idr, 2, 0)),
+            (2, get_picture(&idr, 15, 0)),
+            (0, get_picture(&idr, 0, 0)),
+            (2, get_picture(&idr, 0, 0))
+        ]), [0, 2, 3, 4, 30, 31, 32]);
+    }
+
+    #[test]
+    fn decode_memory_management_control_operation_5() {
+        let (mut sps, idr) = get_sps_and_slice_header();
+        sps.pic_order_cnt_type = 0;
+        sps.log2_max_pic_order_cnt_lsb_minus4 = 0;
+        let mut reset = get_picture(&idr, 2, 8);
+        reset.dec_ref_pic_marking = Some(DecRefPicMarking {
+            adaptive_ref_pic_marking_mode_flag: true,
+            memory_management_control_operation: vec![5],
+            difference_of_pic_nums_minus1: vec![0],
+            long_term_pic_num: vec![0],
+            long_term_frame_idx: vec![0],
+            max_long_term_frame_idx_plus1: vec![0],
+            ..Default::default()
+        });
+        // the picture with the reset counts 0 and the next ones count from it
+        assert_eq!(decode(&sps, &[
+            (3, idr.clone()),
+            (2, get_picture(&idr, 1, 4)),
+            (2, reset),
+            (2, get_picture(&idr, 1, 4)),
+            (0, get_picture(&idr, 2, 2))
+        ]), [0, 4, 0, 4, 2]);
+    }
+}
@@ -1,14 +1,25 @@
-use std::{any::Any, fmt, io::{self, Read, Write}};
+use std::{any::Any, fmt, io::{Read, Write}};
 
-use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, sps_pps_provider::SpsPpsProvider};
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, scaling_list::ScalingList, scaling_matrix::ScalingMatrix, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider};
 
 #[derive(Debug, Clone)]
 pub struct PpsNalu {
+    pub header: u8,
     pub pic_parameter_set_id: u64,
     pub seq_parameter_set_id: u64,
     pub entropy_coding_mode_flag: bool,
     pub bottom_field_pic_order_in_frame_present_flag: bool,
     pub num_slice_groups_minus1: u64,
+    pub slice_group_map_type: u64,
+    pub run_length_minus1: Vec<u64>,
+    pub top_left: Vec<u64>,
+    pub bottom_right: Vec<u64>,
+    pub slice_group_change_direction_flag: bool,
+    pub slice_group_change_rate_minus1: u64,
+    pub pic_size_in_map_units_minus1: u64,
+    pub slice_group_id: Vec<u64>,
     pub num_ref_idx_10_default_active_minus1: u64,
     pub num_ref_idx_l1_default_active_minus1: u64,
     pub weighted_pred_flag: bool,
@@ -19,43 +30,116 @@ pub struct PpsNalu {
     pub deblocking_filter_control_present_flag: bool,
     pub constrained_intra_pred_flag: bool,
     pub redundant_pic_cnt_present_flag: bool,
+    pub more_rbsp_data: bool,       // transform_8x8_mode_flag and the fields after it are present
+    pub transform_8x8_mode_flag: bool,
+    pub pic_scaling_matrix_present_flag: bool,
+    pub pic_scaling_lists: Vec<Option<ScalingList>>,   // None where pic_scaling_list_present_flag is 0
+    pub second_chroma_qp_index_offset: i64,
     pub payload_size: u32
 }
 
 impl PpsNalu {
-    pub fn read(rdr: &mut impl Read, len: u32) -> io::Result<Self> {
-        let mut descriptor_reader = DescriptorReader::new(rdr, len);
-        let pic_parameter_set_id = descriptor_reader.read_ue_v();
-        let seq_parameter_set_id = descriptor_reader.read_ue_v();
-        let entropy_coding_mode_flag = descriptor_reader.read_u1();
-        let bottom_field_pic_order_in_frame_present_flag = descriptor_reader.read_u1();
-        let num_slice_groups_minus1 = descriptor_reader.read_ue_v();
+    pub fn read(rdr: &mut impl Read, len: u32, header: u8, sps_pps_provider: &impl SpsPpsProvider) -> Result<Self> {
+        let mut descriptor_reader = DescriptorReader::new(rdr, len)?;
+        let pic_parameter_set_id = descriptor_reader.read_ue_v()?;
+        let seq_parameter_set_id = descriptor_reader.read_ue_v()?;
+        let entropy_coding_mode_flag = descriptor_reader.read_u1()?;
+        let bottom_field_pic_order_in_frame_present_flag = descriptor_reader.read_u1()?;
+        let num_slice_groups_minus1 = descriptor_reader.read_ue_v()?;
+        if num_slice_groups_minus1 > 7 {
+            return Err(Error::spec_violation(format!("num_slice_groups_minus1 {} out of range", num_slice_groups_minus1)));
+        }
+        let mut slice_group_map_type = 0;
+        let mut run_length_minus1 = vec![];
+        let mut top_left = vec![];
+        let mut bottom_right = vec![];
+        let mut slice_group_change_direction_flag = false;
+        let mut slice_group_change_rate_minus1 = 0;
+        let mut pic_size_in_map_units_minus1 = 0;
+        let mut slice_group_id = vec![];
         if num_slice_groups_minus1 > 0 {
-            todo!("num_slice_groups_minus1 > 0")
+            slice_group_map_type = descriptor_reader.read_ue_v()?;
+            match slice_group_map_type {
+                0 => {
+                    for _i_group in 0..=num_slice_groups_minus1 {
+                        run_length_minus1.push(descriptor_reader.read_ue_v()?);
+                    }
+                }
+                2 => {
+                    for _i_group in 0..num_slice_groups_minus1 {
+                        top_left.push(descriptor_reader.read_ue_v()?);
+                        bottom_right.push(descriptor_reader.read_ue_v()?);
+                    }
+                }
+                3..=5 => {
+                    slice_group_change_direction_flag = descriptor_reader.read_u1()?;
+                    slice_group_change_rate_minus1 = descriptor_reader.read_ue_v()?;
+                }
+                6 => {
+                    pic_size_in_map_units_minus1 = descriptor_reader.read_ue_v()?;
+                    let bits = PpsNalu::get_slice_group_id_length(num_slice_groups_minus1);
+                    for _i in 0..=pic_size_in_map_units_minus1 {
+                        slice_group_id.push(descriptor_reader.read_u(bits)?);
+                    }
+                }
+                1 => {}
+                _ => return Err(Error::spec_violation(format!("slice_group_map_type {} out of range", slice_group_map_type)))
+            }
         }
-        let num_ref_idx_10_default_active_minus1 = descriptor_reader.read_ue_v();
-        let num_ref_idx_l1_default_active_minus1 = descriptor_reader.read_ue_v();
-        let weighted_pred_flag = descriptor_reader.read_u1();
-        let weighted_bipred_idc = u8::try_from(descriptor_reader.read_u(2)).unwrap();
-        let pic_init_qp_minus26 = descriptor_reader.read_se_v();
-        let pic_init_qs_minus26 = descriptor_reader.read_se_v();
-        let chroma_qp_index_offset = descriptor_reader.read_se_v();
-        let deblocking_filter_control_present_flag = descriptor_reader.read_u1();
-        let constrained_intra_pred_flag = descriptor_reader.read_u1();
-        let redundant_pic_cnt_present_flag = descriptor_reader.read_u1();
-        
-        if descriptor_reader.more_rbsp_data() {
-            todo!("more rbsp data in pps");
+        let num_ref_idx_10_default_active_minus1 = descriptor_reader.read_ue_v()?;
+        let num_ref_idx_l1_default_active_minus1 = descriptor_reader.read_ue_v()?;
+        let weighted_pred_flag = descriptor_reader.read_u1()?;
+        let weighted_bipred_idc = descriptor_reader.read_u(2)? as u8;
+        let pic_init_qp_minus26 = descriptor_reader.read_se_v()?;
+        let pic_init_qs_minus26 = descriptor_reader.read_se_v()?;
+        let chroma_qp_index_offset = descriptor_reader.read_se_v()?;
+        let deblocking_filter_control_present_flag = descriptor_reader.read_u1()?;
+        let constrained_intra_pred_flag = descriptor_reader.read_u1()?;
+        let redundant_pic_cnt_present_flag = descriptor_reader.read_u1()?;
+        let more_rbsp_data = descriptor_reader.more_rbsp_data();
+        let mut transform_8x8_mode_flag = false;
+        let mut pic_scaling_matrix_present_flag = false;
+        let mut pic_scaling_lists = vec![];
+        let mut second_chroma_qp_index_offset = chroma_qp_index_offset;
+        if more_rbsp_data {
+            transform_8x8_mode_flag = descriptor_reader.read_u1()?;
+            pic_scaling_matrix_present_flag = descriptor_reader.read_u1()?;
+            if pic_scaling_matrix_present_flag {
+                let mut scaling_list_count = 6;
+                if transform_8x8_mode_flag {
+                    let sps = sps_pps_provider.get_sps(seq_parameter_set_id)
+                        .ok_or_else(|| Error::spec_violation(format!("PPS refers to missing SPS {}", seq_parameter_set_id)))?;
+                    scaling_list_count += if sps.chroma_format_idc != 3 { 2 } else { 6 };
+                }
+                for i in 0..scaling_list_count {
+                    let pic_scaling_list_present_flag = descriptor_reader.read_u1()?;
+                    if pic_scaling_list_present_flag {
+                        pic_scaling_lists.push(Some(ScalingList::read(&mut descriptor_reader, if i < 6 { 16 } else { 64 })?));
+                    } else {
+                        pic_scaling_lists.push(None);
+                    }
+                }
+            }
+            second_chroma_qp_index_offset = descriptor_reader.read_se_v()?;
         }
 
-        descriptor_reader.read_rbsp_trailing_bits();
+        descriptor_reader.read_rbsp_trailing_bits()?;
 
         Ok(PpsNalu {
+            header,
             pic_parameter_set_id,
             seq_parameter_set_id,
             entropy_coding_mode_flag,
             bottom_field_pic_order_in_frame_present_flag,
             num_slice_groups_minus1,
+            slice_group_map_type,
+            run_length_minus1,
+            top_left,
+            bottom_right,
+            slice_group_change_direction_flag,
+            slice_group_change_rate_minus1,
+            pic_size_in_map_units_minus1,
+            slice_group_id,
             num_ref_idx_10_default_active_minus1,
             num_ref_idx_l1_default_active_minus1,
             weighted_pred_flag,
@@ -66,13 +150,50 @@ impl PpsNalu {
             deblocking_filter_control_present_flag,
             constrained_intra_pred_flag,
             redundant_pic_cnt_present_flag,
+            more_rbsp_data,
+            transform_8x8_mode_flag,
+            pic_scaling_matrix_present_flag,
+            pic_scaling_lists,
+            second_chroma_qp_index_offset,
             payload_size: len
         })
     }
+
+    // Ceil(Log2(PicSizeInMapUnits ÷ SliceGroupChangeRate + 1)) bits of slice_group_change_cycle
+    pub fn get_slice_group_change_cycle_length(&self, sps: &SpsNalu) -> u8 {
+        let pic_size_in_map_units = (sps.pic_width_in_mbs_minus1 + 1) * (sps.pic_height_in_map_units_minus1 + 1);
+        let slice_group_change_rate = self.slice_group_change_rate_minus1 + 1;
+        let mut bits = 0;
+        while (slice_group_change_rate << bits) < pic_size_in_map_units + slice_group_change_rate {
+            bits += 1;
+        }
+        bits
+    }
+
+    // Ceil(Log2(num_slice_groups_minus1 + 1)) bits of slice_group_id
+    fn get_slice_group_id_length(num_slice_groups_minus1: u64) -> u8 {
+        let mut bits = 0;
+        while (1 << bits) < num_slice_groups_minus1 + 1 {
+            bits += 1;
+        }
+        bits
+    }
+
+    // scaling lists after fall-back rule A, or rule B when the SPS has a scaling matrix;
+    // the SPS ones when pic_scaling_matrix_present_flag is 0
+    pub fn get_scaling_matrix(&self, sps: &SpsNalu) -> ScalingMatrix {
+        if !self.pic_scaling_matrix_present_flag {
+            sps.get_scaling_matrix()
+        } else if sps.seq_scaling_matrix_present_flag {
+            ScalingMatrix::resolve(&self.pic_scaling_lists, Some(&sps.get_scaling_matrix()))
+        } else {
+            ScalingMatrix::resolve(&self.pic_scaling_lists, None)
+        }
+    }
 }
 
 impl Nalu for PpsNalu {
-    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) {
+    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
         let mut descriptor_writer = DescriptorWriter::new(wtr);
         descriptor_writer.append_ue_v(self.pic_parameter_set_id);
         descriptor_writer.append_ue_v(self.seq_parameter_set_id);
@@ -80,7 +201,32 @@ impl Nalu for PpsNalu {
         descriptor_writer.append_u1(self.bottom_field_pic_order_in_frame_present_flag);
         descriptor_writer.append_ue_v(self.num_slice_groups_minus1);
         if self.num_slice_groups_minus1 > 0 {
-            todo!("num_slice_groups_minus1 > 0")
+            descriptor_writer.append_ue_v(self.slice_group_map_type);
+            match self.slice_group_map_type {
+                0 => {
+                    for &run_length_minus1 in &self.run_length_minus1 {
+                        descriptor_writer.append_ue_v(run_length_minus1);
+                    }
+                }
+                2 => {
+                    for (&top_left, &bottom_right) in self.top_left.iter().zip(&self.bottom_right) {
+                        descriptor_writer.append_ue_v(top_left);
+                        descriptor_writer.append_ue_v(bottom_right);
+                    }
+                }
+                3..=5 => {
+                    descriptor_writer.append_u1(self.slice_group_change_direction_flag);
+                    descriptor_writer.append_ue_v(self.slice_group_change_rate_minus1);
+                }
+                6 => {
+                    descriptor_writer.append_ue_v(self.pic_size_in_map_units_minus1);
+                    let bits = PpsNalu::get_slice_group_id_length(self.num_slice_groups_minus1);
+                    for &slice_group_id in &self.slice_group_id {
+                        descriptor_writer.append_u(bits, slice_group_id);
+                    }
+                }
+                _ => {}
+            }
         }
         descriptor_writer.append_ue_v(self.num_ref_idx_10_default_active_minus1);
         descriptor_writer.append_ue_v(self.num_ref_idx_l1_default_active_minus1);
@@ -92,8 +238,25 @@ impl Nalu for PpsNalu {
         descriptor_writer.append_u1(self.deblocking_filter_control_present_flag);
         descriptor_writer.append_u1(self.constrained_intra_pred_flag);
         descriptor_writer.append_u1(self.redundant_pic_cnt_present_flag);
+        if self.more_rbsp_data {
+            descriptor_writer.append_u1(self.transform_8x8_mode_flag);
+            descriptor_writer.append_u1(self.pic_scaling_matrix_present_flag);
+            if self.pic_scaling_matrix_present_flag {
+                for pic_scaling_list in &self.pic_scaling_lists {
+                    descriptor_writer.append_u1(pic_scaling_list.is_some());
+                    if let Some(pic_scaling_list) = pic_scaling_list {
+                        pic_scaling_list.write(&mut descriptor_writer);
+                    }
+                }
+            }
+            descriptor_writer.append_se_v(self.second_chroma_qp_index_offset);
+        }
         descriptor_writer.append_rbsp_trailing_bits();
-        descriptor_writer.write_with_header(0x68);
+        descriptor_writer.write_with_header(self.header)
+    }
+
+    fn get_nal_unit_type(&self) -> u8 {
+        8
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -110,3 +273,58 @@ impl fmt::Display for PpsNalu {
         write!(f, "[PPS(pic_parameter_set_id={}, seq_parameter_set_id={})]", self.pic_parameter_set_id, self.seq_parameter_set_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{h264::{nalu::Nalu, parameter_sets::ParameterSets, sps_nalu::SpsNalu}, test_util::{BASELINE_SPS, to_bytes, ue}};
+
+    use super::PpsNalu;
+
+    // PPS 0 of the Baseline SPS, 300 macroblocks, with the given slice groups
+    fn round_trip(num_slice_groups_minus1: u64, slice_group_map_type: u64, slice_group_bits: &str) -> PpsNalu {
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert_sps(SpsNalu::read(&mut Cursor::new(BASELINE_SPS), BASELINE_SPS.len() as u32, 0x67).unwrap());
+        let rbsp = to_bytes(&format!(
+            "1 1 0 0 {} {} {} 1 1 0 00 1 1 1 1 0 0",
+            ue(num_slice_groups_minus1), ue(slice_group_map_type), slice_group_bits
+        ));
+        let pps = PpsNalu::read(&mut Cursor::new(&rbsp), rbsp.len() as u32, 0x68, &parameter_sets).unwrap();
+        assert_eq!((pps.num_slice_groups_minus1, pps.slice_group_map_type), (num_slice_groups_minus1, slice_group_map_type));
+        let mut bytes = vec![];
+        pps.write(&mut bytes, &parameter_sets).unwrap();
+        assert_eq!(bytes, [&[0x68], &rbsp[..]].concat());
+        pps
+    }
+
+    #[test]
+    fn round_trip_interleaved_slice_groups() {
+        let pps = round_trip(1, 0, &format!("{} {}", ue(9), ue(19)));
+        assert_eq!(pps.run_length_minus1, [9, 19]);
+    }
+
+    #[test]
+    fn round_trip_foreground_slice_groups() {
+        // two rectangles over the left-over slice group
+        let pps = round_trip(2, 2, &format!("{} {} {} {}", ue(21), ue(63), ue(105), ue(148)));
+        assert_eq!((&pps.top_left[..], &pps.bottom_right[..]), (&[21, 105][..], &[63, 148][..]));
+    }
+
+    #[test]
+    fn round_trip_raster_scan_slice_groups() {
+        let pps = round_trip(1, 4, &format!("1 {}", ue(9)));
+        assert!(pps.slice_group_change_direction_flag);
+        assert_eq!(pps.slice_group_change_rate_minus1, 9);
+    }
+
+    #[test]
+    fn round_trip_explicit_slice_groups() {
+        // 2 bits of slice_group_id for each of 3 slice groups
+        let slice_group_id: Vec<u64> = (0..300).map(|i| i % 3).collect();
+        let slice_group_id_bits: Vec<String> = slice_group_id.iter().map(|id| format!("{:02b}", id)).collect();
+        let pps = round_trip(2, 6, &format!("{} {}", ue(299), slice_group_id_bits.join(" ")));
+        assert_eq!(pps.pic_size_in_map_units_minus1, 299);
+        assert_eq!(pps.slice_group_id, slice_group_id);
+    }
+}
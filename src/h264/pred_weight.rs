@@ -0,0 +1,58 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// weights and offsets of one reference index; those not present in the stream hold their inferred values
+#[derive(Debug, Clone)]
+pub struct PredWeight {
+    pub luma_weight_flag: bool,
+    pub luma_weight: i64,
+    pub luma_offset: i64,
+    pub chroma_weight_flag: bool,   // false when ChromaArrayType is 0
+    pub chroma_weight: [i64; 2],
+    pub chroma_offset: [i64; 2]
+}
+
+impl PredWeight {
+    pub fn read(descriptor_reader: &mut DescriptorReader, luma_log2_weight_denom: u64, chroma_log2_weight_denom: u64, chroma_array_type: u64) -> Result<Self> {
+        let mut pred_weight = PredWeight {
+            luma_weight_flag: descriptor_reader.read_u1()?,
+            luma_weight: 1 << luma_log2_weight_denom,
+            luma_offset: 0,
+            chroma_weight_flag: false,
+            chroma_weight: [1 << chroma_log2_weight_denom; 2],
+            chroma_offset: [0; 2]
+        };
+        if pred_weight.luma_weight_flag {
+            pred_weight.luma_weight = descriptor_reader.read_se_v()?;
+            pred_weight.luma_offset = descriptor_reader.read_se_v()?;
+        }
+        if chroma_array_type != 0 {
+            pred_weight.chroma_weight_flag = descriptor_reader.read_u1()?;
+            if pred_weight.chroma_weight_flag {
+                for j in 0..2 {
+                    pred_weight.chroma_weight[j] = descriptor_reader.read_se_v()?;
+                    pred_weight.chroma_offset[j] = descriptor_reader.read_se_v()?;
+                }
+            }
+        }
+        Ok(pred_weight)
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, chroma_array_type: u64) {
+        descriptor_writer.append_u1(self.luma_weight_flag);
+        if self.luma_weight_flag {
+            descriptor_writer.append_se_v(self.luma_weight);
+            descriptor_writer.append_se_v(self.luma_offset);
+        }
+        if chroma_array_type != 0 {
+            descriptor_writer.append_u1(self.chroma_weight_flag);
+            if self.chroma_weight_flag {
+                for j in 0..2 {
+                    descriptor_writer.append_se_v(self.chroma_weight[j]);
+                    descriptor_writer.append_se_v(self.chroma_offset[j]);
+                }
+            }
+        }
+    }
+}
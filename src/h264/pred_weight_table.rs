@@ -0,0 +1,59 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, pred_weight::PredWeight};
+
+#[derive(Debug, Clone)]
+pub struct PredWeightTable {
+    pub luma_log2_weight_denom: u64,
+    pub chroma_log2_weight_denom: u64,
+    pub l0: Vec<PredWeight>,        // one per reference index, num_ref_idx_l0_active_minus1 + 1
+    pub l1: Vec<PredWeight>         // one per reference index for B slices, otherwise empty
+}
+
+impl PredWeightTable {
+    pub fn read(descriptor_reader: &mut DescriptorReader, slice_type: u64, num_ref_idx_l0_active_minus1: u64, num_ref_idx_l1_active_minus1: u64, chroma_array_type: u64) -> Result<Self> {
+        let luma_log2_weight_denom = descriptor_reader.read_ue_v()?;
+        if luma_log2_weight_denom > 7 {
+            return Err(Error::spec_violation(format!("luma_log2_weight_denom {} out of range", luma_log2_weight_denom)));
+        }
+        let mut chroma_log2_weight_denom = 0;
+        if chroma_array_type != 0 {
+            chroma_log2_weight_denom = descriptor_reader.read_ue_v()?;
+            if chroma_log2_weight_denom > 7 {
+                return Err(Error::spec_violation(format!("chroma_log2_weight_denom {} out of range", chroma_log2_weight_denom)));
+            }
+        }
+        let mut l0 = vec![];
+        for _i in 0..=num_ref_idx_l0_active_minus1 {
+            l0.push(PredWeight::read(descriptor_reader, luma_log2_weight_denom, chroma_log2_weight_denom, chroma_array_type)?);
+        }
+        let mut l1 = vec![];
+        if slice_type % 5 == 1 {
+            for _i in 0..=num_ref_idx_l1_active_minus1 {
+                l1.push(PredWeight::read(descriptor_reader, luma_log2_weight_denom, chroma_log2_weight_denom, chroma_array_type)?);
+            }
+        }
+        Ok(PredWeightTable {
+            luma_log2_weight_denom,
+            chroma_log2_weight_denom,
+            l0,
+            l1
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, slice_type: u64, num_ref_idx_l0_active_minus1: u64, num_ref_idx_l1_active_minus1: u64, chroma_array_type: u64) -> Result<()> {
+        let l1_len = if slice_type % 5 == 1 { num_ref_idx_l1_active_minus1 + 1 } else { 0 };
+        if self.l0.len() as u64 != num_ref_idx_l0_active_minus1 + 1 || self.l1.len() as u64 != l1_len {
+            return Err(Error::spec_violation(format!("pred_weight_table with {} and {} weights for {} and {} active references",
+                self.l0.len(), self.l1.len(), num_ref_idx_l0_active_minus1 + 1, l1_len)));
+        }
+        descriptor_writer.append_ue_v(self.luma_log2_weight_denom);
+        if chroma_array_type != 0 {
+            descriptor_writer.append_ue_v(self.chroma_log2_weight_denom);
+        }
+        for pred_weight in self.l0.iter().chain(&self.l1) {
+            pred_weight.write(descriptor_writer, chroma_array_type);
+        }
+        Ok(())
+    }
+}
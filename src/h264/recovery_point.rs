@@ -0,0 +1,34 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// recovery_point SEI message (D.1.8)
+#[derive(Debug, Clone)]
+pub struct RecoveryPoint {
+    pub recovery_frame_cnt: u64,
+    pub exact_match_flag: bool,
+    pub broken_link_flag: bool,
+    pub changing_slice_group_idc: u8
+}
+
+impl RecoveryPoint {
+    pub fn read(descriptor_reader: &mut DescriptorReader) -> Result<Self> {
+        let recovery_frame_cnt = descriptor_reader.read_ue_v()?;
+        let exact_match_flag = descriptor_reader.read_u1()?;
+        let broken_link_flag = descriptor_reader.read_u1()?;
+        let changing_slice_group_idc = descriptor_reader.read_u(2)? as u8;
+        Ok(RecoveryPoint {
+            recovery_frame_cnt,
+            exact_match_flag,
+            broken_link_flag,
+            changing_slice_group_idc
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        descriptor_writer.append_ue_v(self.recovery_frame_cnt);
+        descriptor_writer.append_u1(self.exact_match_flag);
+        descriptor_writer.append_u1(self.broken_link_flag);
+        descriptor_writer.append_u(2, self.changing_slice_group_idc.into());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// the terminating modification_of_pic_nums_idc 3 is not stored
+#[derive(Debug, Clone, Default)]
+pub struct RefPicListModification {
+    pub ref_pic_list_modification_flag_l0: bool,
+    pub modification_of_pic_nums_idc_l0: Vec<u64>,
+    pub abs_diff_pic_num_minus1_l0: Vec<u64>,       // 0 unless modification_of_pic_nums_idc is 0 or 1
+    pub long_term_pic_num_l0: Vec<u64>,             // 0 unless modification_of_pic_nums_idc is 2
+    pub ref_pic_list_modification_flag_l1: bool,
+    pub modification_of_pic_nums_idc_l1: Vec<u64>,
+    pub abs_diff_pic_num_minus1_l1: Vec<u64>,
+    pub long_term_pic_num_l1: Vec<u64>
+}
+
+impl RefPicListModification {
+    pub fn read(descriptor_reader: &mut DescriptorReader, slice_type: u64) -> Result<Self> {
+        let mut ref_pic_list_modification = RefPicListModification::default();
+        if slice_type % 5 != 2 && slice_type % 5 != 4 {
+            ref_pic_list_modification.ref_pic_list_modification_flag_l0 = descriptor_reader.read_u1()?;
+            if ref_pic_list_modification.ref_pic_list_modification_flag_l0 {
+                RefPicListModification::read_modifications(
+                    descriptor_reader,
+                    &mut ref_pic_list_modification.modification_of_pic_nums_idc_l0,
+                    &mut ref_pic_list_modification.abs_diff_pic_num_minus1_l0,
+                    &mut ref_pic_list_modification.long_term_pic_num_l0)?;
+            }
+        }
+        if slice_type % 5 == 1 {
+            ref_pic_list_modification.ref_pic_list_modification_flag_l1 = descriptor_reader.read_u1()?;
+            if ref_pic_list_modification.ref_pic_list_modification_flag_l1 {
+                RefPicListModification::read_modifications(
+                    descriptor_reader,
+                    &mut ref_pic_list_modification.modification_of_pic_nums_idc_l1,
+                    &mut ref_pic_list_modification.abs_diff_pic_num_minus1_l1,
+                    &mut ref_pic_list_modification.long_term_pic_num_l1)?;
+            }
+        }
+        Ok(ref_pic_list_modification)
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, slice_type: u64) {
+        if slice_type % 5 != 2 && slice_type % 5 != 4 {
+            descriptor_writer.append_u1(self.ref_pic_list_modification_flag_l0);
+            if self.ref_pic_list_modification_flag_l0 {
+                RefPicListModification::write_modifications(descriptor_writer, &self.modification_of_pic_nums_idc_l0, &self.abs_diff_pic_num_minus1_l0, &self.long_term_pic_num_l0);
+            }
+        }
+        if slice_type % 5 == 1 {
+            descriptor_writer.append_u1(self.ref_pic_list_modification_flag_l1);
+            if self.ref_pic_list_modification_flag_l1 {
+                RefPicListModification::write_modifications(descriptor_writer, &self.modification_of_pic_nums_idc_l1, &self.abs_diff_pic_num_minus1_l1, &self.long_term_pic_num_l1);
+            }
+        }
+    }
+
+    fn read_modifications(descriptor_reader: &mut DescriptorReader, modification_of_pic_nums_idc: &mut Vec<u64>, abs_diff_pic_num_minus1: &mut Vec<u64>, long_term_pic_num: &mut Vec<u64>) -> Result<()> {
+        loop {
+            let idc = descriptor_reader.read_ue_v()?;
+            match idc {
+                0 | 1 => {
+                    abs_diff_pic_num_minus1.push(descriptor_reader.read_ue_v()?);
+                    long_term_pic_num.push(0);
+                },
+                2 => {
+                    abs_diff_pic_num_minus1.push(0);
+                    long_term_pic_num.push(descriptor_reader.read_ue_v()?);
+                },
+                3 => return Ok(()),
+                _ => return Err(Error::spec_violation(format!("modification_of_pic_nums_idc {}", idc)))
+            }
+            modification_of_pic_nums_idc.push(idc);
+        }
+    }
+
+    fn write_modifications(descriptor_writer: &mut DescriptorWriter, modification_of_pic_nums_idc: &[u64], abs_diff_pic_num_minus1: &[u64], long_term_pic_num: &[u64]) {
+        for (i, &idc) in modification_of_pic_nums_idc.iter().enumerate() {
+            descriptor_writer.append_ue_v(idc);
+            match idc {
+                0 | 1 => descriptor_writer.append_ue_v(abs_diff_pic_num_minus1[i]),
+                2 => descriptor_writer.append_ue_v(long_term_pic_num[i]),
+                _ => {}
+            }
+        }
+        descriptor_writer.append_ue_v(3);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// delta_scale values are kept as coded, up to the one that makes nextScale 0 if any;
+// the remaining entries repeat the last scale
+#[derive(Debug, Clone, Default)]
+pub struct ScalingList {
+    pub delta_scale: Vec<i64>
+}
+
+impl ScalingList {
+    pub fn read(descriptor_reader: &mut DescriptorReader, size: usize) -> Result<Self> {
+        let mut delta_scale = vec![];
+        let mut next_scale = 8;
+        for _j in 0..size {
+            let delta = descriptor_reader.read_se_v()?;
+            if !(-128..=127).contains(&delta) {
+                return Err(Error::spec_violation(format!("delta_scale {} out of range", delta)));
+            }
+            delta_scale.push(delta);
+            next_scale = (next_scale + delta + 256) % 256;
+            if next_scale == 0 {
+                break;
+            }
+        }
+        Ok(ScalingList {
+            delta_scale
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        for &delta in &self.delta_scale {
+            descriptor_writer.append_se_v(delta);
+        }
+    }
+
+    // useDefaultScalingMatrixFlag
+    pub fn use_default_scaling_matrix(&self) -> bool {
+        self.delta_scale.first().is_some_and(|&delta| (8 + delta + 256) % 256 == 0)
+    }
+
+    // scales in zig-zag order, None when the default scaling matrix is to be used
+    pub fn get_scales<const N: usize>(&self) -> Option<[u8; N]> {
+        if self.use_default_scaling_matrix() {
+            return None;
+        }
+        let mut scales = [0; N];
+        let mut last_scale = 8;
+        let mut next_scale = 8;
+        for (j, scale) in scales.iter_mut().enumerate() {
+            if next_scale != 0 {
+                next_scale = (last_scale + self.delta_scale.get(j).copied().unwrap_or(0) + 256) % 256;
+            }
+            if next_scale != 0 {
+                last_scale = next_scale;
+            }
+            *scale = last_scale as u8;
+        }
+        Some(scales)
+    }
+}
@@ -0,0 +1,163 @@
+use super::scaling_list::ScalingList;
+
+// Table 7-3
+const DEFAULT_4X4_INTRA: [u8; 16] = [6, 13, 13, 20, 20, 20, 28, 28, 28, 28, 32, 32, 32, 37, 37, 42];
+const DEFAULT_4X4_INTER: [u8; 16] = [10, 14, 14, 20, 20, 20, 24, 24, 24, 24, 27, 27, 27, 30, 30, 34];
+
+// Table 7-4
+const DEFAULT_8X8_INTRA: [u8; 64] = [
+    6, 10, 10, 13, 11, 13, 16, 16, 16, 16, 18, 18, 18, 18, 18, 23,
+    23, 23, 23, 23, 23, 25, 25, 25, 25, 25, 25, 25, 27, 27, 27, 27,
+    27, 27, 27, 27, 29, 29, 29, 29, 29, 29, 29, 31, 31, 31, 31, 31,
+    31, 33, 33, 33, 33, 33, 36, 36, 36, 36, 38, 38, 38, 40, 40, 42
+];
+const DEFAULT_8X8_INTER: [u8; 64] = [
+    9, 13, 13, 15, 13, 15, 17, 17, 17, 17, 19, 19, 19, 19, 19, 21,
+    21, 21, 21, 21, 21, 22, 22, 22, 22, 22, 22, 22, 24, 24, 24, 24,
+    24, 24, 24, 24, 25, 25, 25, 25, 25, 25, 25, 27, 27, 27, 27, 27,
+    27, 28, 28, 28, 28, 28, 30, 30, 30, 30, 32, 32, 32, 33, 33, 35
+];
+
+// scaling lists in zig-zag order as used for decoding: 4x4 lists for intra Y, Cb, Cr and inter Y, Cb, Cr,
+// 8x8 lists for intra Y, inter Y, intra Cb, inter Cb, intra Cr and inter Cr
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScalingMatrix {
+    pub scaling_lists_4x4: [[u8; 16]; 6],
+    pub scaling_lists_8x8: [[u8; 64]; 6]
+}
+
+impl ScalingMatrix {
+    // Flat_4x4_16 and Flat_8x8_16 when no scaling matrix is present
+    pub fn flat() -> Self {
+        ScalingMatrix {
+            scaling_lists_4x4: [[16; 16]; 6],
+            scaling_lists_8x8: [[16; 64]; 6]
+        }
+    }
+
+    // lists that are not present fall back per Table 7-2, to the default lists (rule A)
+    // or, given the sequence-level matrix, to the sequence-level lists (rule B)
+    pub fn resolve(scaling_lists: &[Option<ScalingList>], sequence_matrix: Option<&ScalingMatrix>) -> Self {
+        let mut matrix = ScalingMatrix::flat();
+        for i in 0..6 {
+            let default = if i < 3 { DEFAULT_4X4_INTRA } else { DEFAULT_4X4_INTER };
+            matrix.scaling_lists_4x4[i] = match scaling_lists.get(i).and_then(Option::as_ref) {
+                Some(scaling_list) => scaling_list.get_scales().unwrap_or(default),
+                None => match (i, sequence_matrix) {
+                    (0 | 3, Some(sequence_matrix)) => sequence_matrix.scaling_lists_4x4[i],
+                    (0 | 3, None) => default,
+                    _ => matrix.scaling_lists_4x4[i - 1]
+                }
+            };
+        }
+        for i in 0..6 {
+            let default = if i % 2 == 0 { DEFAULT_8X8_INTRA } else { DEFAULT_8X8_INTER };
+            matrix.scaling_lists_8x8[i] = match scaling_lists.get(6 + i).and_then(Option::as_ref) {
+                Some(scaling_list) => scaling_list.get_scales().unwrap_or(default),
+                None => match (i, sequence_matrix) {
+                    (0 | 1, Some(sequence_matrix)) => sequence_matrix.scaling_lists_8x8[i],
+                    (0 | 1, None) => default,
+                    _ => matrix.scaling_lists_8x8[i - 2]
+                }
+            };
+        }
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{h264::{nalu::Nalu, parameter_sets::ParameterSets, pps_nalu::PpsNalu, sps_nalu::SpsNalu}, test_util::{se, to_bytes, ue}};
+
+    use super::{ScalingMatrix, DEFAULT_4X4_INTER, DEFAULT_4X4_INTRA, DEFAULT_8X8_INTER, DEFAULT_8X8_INTRA};
+
+    // present lists followed by their delta_scale values, absent ones by nothing
+    fn get_scaling_list_bits(scaling_lists: &[Option<&[i64]>]) -> String {
+        scaling_lists.iter()
+            .map(|scaling_list| match scaling_list {
+                Some(delta_scale) => format!("1 {}", delta_scale.iter().map(|&delta| se(delta)).collect::<Vec<_>>().join(" ")),
+                None => "0".to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // High or High 4:4:4 SPS 0 of 320x240 with a scaling matrix of the given lists
+    fn get_sps(chroma_format_idc: u64, scaling_lists: &[Option<&[i64]>]) -> (SpsNalu, Vec<u8>) {
+        let profile_idc = if chroma_format_idc == 3 { "11110100" } else { "01100100" };
+        let separate_colour_plane_flag = if chroma_format_idc == 3 { "0" } else { "" };
+        let rbsp = to_bytes(&format!(
+            "{} 00000000 00011110 1 {} {} 1 1 0 1 {} 1 011 010 0 000010100 0001111 1 1 0 0",
+            profile_idc, ue(chroma_format_idc), separate_colour_plane_flag, get_scaling_list_bits(scaling_lists)
+        ));
+        (SpsNalu::read(&mut Cursor::new(&rbsp), rbsp.len() as u32, 0x67).unwrap(), rbsp)
+    }
+
+    // PPS 0 with transform_8x8_mode_flag and a scaling matrix of the given lists
+    fn get_pps(scaling_lists: &[Option<&[i64]>], parameter_sets: &ParameterSets) -> (PpsNalu, Vec<u8>) {
+        let rbsp = to_bytes(&format!("1 1 0 0 1 1 1 0 00 1 1 1 1 0 0 1 1 {} 011", get_scaling_list_bits(scaling_lists)));
+        (PpsNalu::read(&mut Cursor::new(&rbsp), rbsp.len() as u32, 0x68, parameter_sets).unwrap(), rbsp)
+    }
+
+    fn assert_round_trip(nalu: &dyn Nalu, header: u8, rbsp: &[u8], parameter_sets: &ParameterSets) {
+        let mut bytes = vec![];
+        nalu.write(&mut bytes, parameter_sets).unwrap();
+        assert_eq!(bytes[0], header);
+        assert_eq!(bytes[1..], *rbsp);
+    }
+
+    #[test]
+    fn resolve_8_lists() {
+        // flat 16 given as 8 then 0, the default intra 4x4 list as 0 right away, flat 12 for 8x8 intra Y
+        let (sps, sps_rbsp) = get_sps(1, &[Some(&[8, -16]), None, Some(&[-8]), None, None, None, Some(&[4, -12]), None]);
+        assert!(sps.seq_scaling_lists[2].as_ref().unwrap().use_default_scaling_matrix());
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert_sps(sps.clone());
+        assert_round_trip(&sps, 0x67, &sps_rbsp, &parameter_sets);
+
+        // absent lists fall back to the previous list of their kind or, for the first one, to the default list (rule A)
+        let sequence_matrix = sps.get_scaling_matrix();
+        assert_eq!(sequence_matrix.scaling_lists_4x4, [[16; 16], [16; 16], DEFAULT_4X4_INTRA, DEFAULT_4X4_INTER, DEFAULT_4X4_INTER, DEFAULT_4X4_INTER]);
+        assert_eq!(sequence_matrix.scaling_lists_8x8, [[12; 64], DEFAULT_8X8_INTER, [12; 64], DEFAULT_8X8_INTER, [12; 64], DEFAULT_8X8_INTER]);
+
+        let (pps, pps_rbsp) = get_pps(&[None, Some(&[-8]), None, None, Some(&[2, -10]), None, None, Some(&[-8])], &parameter_sets);
+        assert_eq!(pps.pic_scaling_lists.len(), 8);
+        assert_round_trip(&pps, 0x68, &pps_rbsp, &parameter_sets);
+
+        // the first lists of each kind fall back to those of the SPS instead (rule B)
+        let picture_matrix = pps.get_scaling_matrix(&sps);
+        assert_eq!(picture_matrix.scaling_lists_4x4, [[16; 16], DEFAULT_4X4_INTRA, DEFAULT_4X4_INTRA, DEFAULT_4X4_INTER, [10; 16], [10; 16]]);
+        assert_eq!(picture_matrix.scaling_lists_8x8, [[12; 64], DEFAULT_8X8_INTER, [12; 64], DEFAULT_8X8_INTER, [12; 64], DEFAULT_8X8_INTER]);
+
+        // without a scaling matrix in the SPS, the PPS falls back to the default lists, and the SPS is flat
+        let mut sps_without_matrix = sps;
+        sps_without_matrix.seq_scaling_matrix_present_flag = false;
+        assert_eq!(sps_without_matrix.get_scaling_matrix(), ScalingMatrix::flat());
+        assert_eq!(pps.get_scaling_matrix(&sps_without_matrix).scaling_lists_4x4[0], DEFAULT_4X4_INTRA);
+        assert_eq!(pps.get_scaling_matrix(&sps_without_matrix).scaling_lists_8x8[0], DEFAULT_8X8_INTRA);
+    }
+
+    #[test]
+    fn resolve_12_lists() {
+        // 4:4:4 has 8x8 lists for Cb and Cr as well
+        let mut sps_lists: Vec<Option<&[i64]>> = vec![None; 12];
+        sps_lists[8] = Some(&[-8]);
+        sps_lists[9] = Some(&[8, -16]);
+        let (sps, sps_rbsp) = get_sps(3, &sps_lists);
+        assert_eq!(sps.seq_scaling_lists.len(), 12);
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert_sps(sps.clone());
+        assert_round_trip(&sps, 0x67, &sps_rbsp, &parameter_sets);
+        assert_eq!(sps.get_scaling_matrix().scaling_lists_8x8, [DEFAULT_8X8_INTRA, DEFAULT_8X8_INTER, DEFAULT_8X8_INTRA, [16; 64], DEFAULT_8X8_INTRA, [16; 64]]);
+
+        let mut pps_lists: Vec<Option<&[i64]>> = vec![None; 12];
+        pps_lists[11] = Some(&[-8]);
+        let (pps, pps_rbsp) = get_pps(&pps_lists, &parameter_sets);
+        assert_eq!(pps.pic_scaling_lists.len(), 12);
+        assert_round_trip(&pps, 0x68, &pps_rbsp, &parameter_sets);
+        // the Cb lists fall back to the Y lists of the PPS, not to the flat Cb list of the SPS
+        assert_eq!(pps.get_scaling_matrix(&sps).scaling_lists_8x8, [DEFAULT_8X8_INTRA, DEFAULT_8X8_INTER, DEFAULT_8X8_INTRA, DEFAULT_8X8_INTER, DEFAULT_8X8_INTRA, DEFAULT_8X8_INTER]);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, sei_payload::SeiPayload, sps_pps_provider::SpsPpsProvider};
+
+// payloadSize is not stored but follows from the payload when written
+#[derive(Debug, Clone)]
+pub struct SeiMessage {
+    pub payload_type: u64,
+    pub payload: SeiPayload
+}
+
+impl SeiMessage {
+    pub fn read(descriptor_reader: &mut DescriptorReader, sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Result<Self> {
+        let payload_type = SeiMessage::read_value(descriptor_reader)?;
+        let payload_size = SeiMessage::read_value(descriptor_reader)?;
+        let mut bytes = vec![];
+        for _i in 0..payload_size {
+            bytes.push(descriptor_reader.read_u8()?);
+        }
+        Ok(SeiMessage {
+            payload_type,
+            payload: SeiPayload::read(payload_type, &bytes, sps_pps_provider, seq_parameter_set_id)
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Result<()> {
+        let bytes = self.payload.to_bytes(sps_pps_provider, seq_parameter_set_id)?;
+        SeiMessage::write_value(descriptor_writer, self.payload_type);
+        SeiMessage::write_value(descriptor_writer, bytes.len() as u64);
+        for byte in bytes {
+            descriptor_writer.append_u8(byte);
+        }
+        Ok(())
+    }
+
+    // payloadType and payloadSize are coded as a run of 0xFF bytes plus a last byte below 0xFF
+    fn read_value(descriptor_reader: &mut DescriptorReader) -> Result<u64> {
+        let mut value = 0;
+        loop {
+            let byte = descriptor_reader.read_u8()?;
+            value += u64::from(byte);
+            if byte != 0xFF {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn write_value(descriptor_writer: &mut DescriptorWriter, value: u64) {
+        let mut remaining = value;
+        while remaining >= 0xFF {
+            descriptor_writer.append_u8(0xFF);
+            remaining -= 0xFF;
+        }
+        descriptor_writer.append_u8(remaining as u8);
+    }
+}
@@ -1,31 +1,69 @@
-use std::{any::Any, fmt, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, opaque_data::OpaqueData, sps_pps_provider::SpsPpsProvider};
+use crate::error::Result;
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, sei_message::SeiMessage, sei_payload::SeiPayload, sps_pps_provider::SpsPpsProvider};
 
 pub struct SeiNalu {
-    remaining: OpaqueData,
-    pub payload_size: u32
+    pub sei_messages: Vec<SeiMessage>,
+    pub seq_parameter_set_id: u64   // SPS active for the access unit, which pic_timing is coded against
 }
 
 impl SeiNalu {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u32) -> io::Result<Self> {
-        let mut descriptor_reader = DescriptorReader::new(rdr, len);
-        let remaining = descriptor_reader.read_to_end();
-        descriptor_reader.read_rbsp_trailing_bits();
+    pub fn new(sei_messages: Vec<SeiMessage>) -> Self {
+        SeiNalu {
+            sei_messages,
+            seq_parameter_set_id: 0
+        }
+    }
+
+    pub fn read(rdr: &mut (impl Read + Seek), len: u32, sps_pps_provider: &impl SpsPpsProvider, seq_parameter_set_id: u64) -> Result<Self> {
+        let mut descriptor_reader = DescriptorReader::new(rdr, len)?;
+        let mut sei_messages = vec![];
+        while descriptor_reader.more_rbsp_data() {
+            sei_messages.push(SeiMessage::read(&mut descriptor_reader, sps_pps_provider, seq_parameter_set_id)?);
+        }
+        descriptor_reader.read_rbsp_trailing_bits()?;
 
         Ok(SeiNalu {
-            remaining,
-            payload_size: len
+            sei_messages,
+            seq_parameter_set_id
         })
     }
+
+    // the messages with pic_timing read again against another SPS, for when the active SPS of the
+    // access unit is only known from the slices after the SEI
+    pub fn get_sei_messages_for_sps(&self, seq_parameter_set_id: u64, sps_pps_provider: &dyn SpsPpsProvider) -> Result<Vec<SeiMessage>> {
+        let mut sei_messages = vec![];
+        for sei_message in &self.sei_messages {
+            let payload = match &sei_message.payload {
+                SeiPayload::PicTiming(_) | SeiPayload::Unparsed { .. } if sei_message.payload_type == 1 => {
+                    let bytes = sei_message.payload.to_bytes(sps_pps_provider, self.seq_parameter_set_id)?;
+                    SeiPayload::read(sei_message.payload_type, &bytes, sps_pps_provider, seq_parameter_set_id)
+                },
+                payload => payload.clone()
+            };
+            sei_messages.push(SeiMessage {
+                payload_type: sei_message.payload_type,
+                payload
+            });
+        }
+        Ok(sei_messages)
+    }
 }
 
 impl Nalu for SeiNalu {
-    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) {
+    fn write(&self, wtr: &mut dyn Write, sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
         let mut descriptor_writer = DescriptorWriter::new(wtr);
-        descriptor_writer.append_all(&self.remaining);
+        for sei_message in &self.sei_messages {
+            sei_message.write(&mut descriptor_writer, sps_pps_provider, self.seq_parameter_set_id)?;
+        }
         descriptor_writer.append_rbsp_trailing_bits();
-        descriptor_writer.write_with_header(0x06);
+        descriptor_writer.write_with_header(0x06)
+    }
+
+    fn get_nal_unit_type(&self) -> u8 {
+        6
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -40,6 +78,8 @@ impl Nalu for SeiNalu {
 impl fmt::Debug for SeiNalu {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SeiNalu")
+            .field("sei_messages", &self.sei_messages)
+            .field("seq_parameter_set_id", &self.seq_parameter_set_id)
             .finish()
     }
 }
@@ -0,0 +1,114 @@
+use std::io;
+
+use crate::error::{Error, Result};
+
+use super::{buffering_period::BufferingPeriod, content_light_level_info::ContentLightLevelInfo, descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, frame_packing_arrangement::FramePackingArrangement, mastering_display_colour_volume::MasteringDisplayColourVolume, pic_timing::PicTiming, recovery_point::RecoveryPoint, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider, user_data_registered_itu_t_t35::UserDataRegisteredItuTT35, user_data_unregistered::UserDataUnregistered};
+
+#[derive(Debug, Clone)]
+pub enum SeiPayload {
+    BufferingPeriod(BufferingPeriod),
+    PicTiming(PicTiming),
+    UserDataRegisteredItuTT35(UserDataRegisteredItuTT35),
+    UserDataUnregistered(UserDataUnregistered),
+    RecoveryPoint(RecoveryPoint),
+    FramePackingArrangement(FramePackingArrangement),
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    ContentLightLevelInfo(ContentLightLevelInfo),
+    Unknown(Vec<u8>),   // payload bytes of any other type
+    Unparsed {          // payload bytes of a known type that fails to parse or does not write back identically
+        bytes: Vec<u8>,
+        reason: String
+    }
+}
+
+impl SeiPayload {
+    // pic_timing is parsed against the SPS of seq_parameter_set_id
+    pub fn read(payload_type: u64, bytes: &[u8], sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Self {
+        let payload = match SeiPayload::read_typed(payload_type, bytes, sps_pps_provider, seq_parameter_set_id) {
+            Ok(Some(payload)) => payload,
+            Ok(None) => return SeiPayload::Unknown(bytes.to_vec()),
+            Err(err) => return SeiPayload::Unparsed {
+                bytes: bytes.to_vec(),
+                reason: err.to_string()
+            }
+        };
+        let reason = match payload.to_bytes(sps_pps_provider, seq_parameter_set_id) {
+            Ok(written) if written == bytes => return payload,
+            Ok(_) => "payload does not write back identically".to_owned(),
+            Err(err) => err.to_string()
+        };
+        SeiPayload::Unparsed {
+            bytes: bytes.to_vec(),
+            reason
+        }
+    }
+
+    pub fn to_bytes(&self, sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Result<Vec<u8>> {
+        let mut sink = io::sink();
+        let mut descriptor_writer = DescriptorWriter::new(&mut sink);
+        match self {
+            SeiPayload::BufferingPeriod(buffering_period) => buffering_period.write(&mut descriptor_writer, sps_pps_provider)?,
+            SeiPayload::PicTiming(pic_timing) => pic_timing.write(&mut descriptor_writer, SeiPayload::get_sps(sps_pps_provider, seq_parameter_set_id)?),
+            SeiPayload::UserDataRegisteredItuTT35(user_data) => user_data.write(&mut descriptor_writer),
+            SeiPayload::UserDataUnregistered(user_data) => user_data.write(&mut descriptor_writer),
+            SeiPayload::RecoveryPoint(recovery_point) => recovery_point.write(&mut descriptor_writer),
+            SeiPayload::FramePackingArrangement(frame_packing_arrangement) => frame_packing_arrangement.write(&mut descriptor_writer),
+            SeiPayload::MasteringDisplayColourVolume(mastering_display_colour_volume) => mastering_display_colour_volume.write(&mut descriptor_writer),
+            SeiPayload::ContentLightLevelInfo(content_light_level_info) => content_light_level_info.write(&mut descriptor_writer),
+            SeiPayload::Unknown(bytes) | SeiPayload::Unparsed { bytes, .. } => return Ok(bytes.clone())
+        }
+        // bit_equal_to_one and bit_equal_to_zero up to the byte boundary
+        if !descriptor_writer.is_byte_aligned() {
+            descriptor_writer.append_rbsp_trailing_bits();
+        }
+        Ok(descriptor_writer.into_rbsp())
+    }
+
+    fn read_typed(payload_type: u64, bytes: &[u8], sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Result<Option<Self>> {
+        let mut descriptor_reader = DescriptorReader::from_rbsp(bytes.to_vec());
+        let payload = match payload_type {
+            0 => SeiPayload::BufferingPeriod(BufferingPeriod::read(&mut descriptor_reader, sps_pps_provider)?),
+            1 => SeiPayload::PicTiming(PicTiming::read(&mut descriptor_reader, SeiPayload::get_sps(sps_pps_provider, seq_parameter_set_id)?)?),
+            4 => SeiPayload::UserDataRegisteredItuTT35(UserDataRegisteredItuTT35::read(&mut descriptor_reader, bytes.len())?),
+            5 => SeiPayload::UserDataUnregistered(UserDataUnregistered::read(&mut descriptor_reader, bytes.len())?),
+            6 => SeiPayload::RecoveryPoint(RecoveryPoint::read(&mut descriptor_reader)?),
+            45 => SeiPayload::FramePackingArrangement(FramePackingArrangement::read(&mut descriptor_reader)?),
+            137 => SeiPayload::MasteringDisplayColourVolume(MasteringDisplayColourVolume::read(&mut descriptor_reader)?),
+            144 => SeiPayload::ContentLightLevelInfo(ContentLightLevelInfo::read(&mut descriptor_reader)?),
+            _ => return Ok(None)
+        };
+        Ok(Some(payload))
+    }
+
+    fn get_sps(sps_pps_provider: &dyn SpsPpsProvider, seq_parameter_set_id: u64) -> Result<&SpsNalu> {
+        sps_pps_provider.get_sps(seq_parameter_set_id)
+            .ok_or_else(|| Error::spec_violation(format!("pic_timing refers to missing SPS {}", seq_parameter_set_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::h264::parameter_sets::ParameterSets;
+
+    use super::SeiPayload;
+
+    #[test]
+    fn read_unparsed_payloads() {
+        let parameter_sets = ParameterSets::new();
+        // recovery_frame_cnt 0 and exact_match_flag, then byte alignment
+        assert!(matches!(SeiPayload::read(6, &[0xC4], &parameter_sets, 0), SeiPayload::RecoveryPoint(_)));
+        match SeiPayload::read(6, &[0xC4, 0x00], &parameter_sets, 0) {
+            SeiPayload::Unparsed { bytes, reason } => {
+                assert_eq!(bytes, [0xC4, 0x00]);
+                assert_eq!(reason, "payload does not write back identically");
+            },
+            payload => panic!("unexpected payload {:?}", payload)
+        }
+        // pic_timing cannot be read without its SPS
+        match SeiPayload::read(1, &[0x80], &parameter_sets, 0) {
+            SeiPayload::Unparsed { reason, .. } => assert!(reason.contains("missing SPS 0"), "{}", reason),
+            payload => panic!("unexpected payload {:?}", payload)
+        }
+        assert!(matches!(SeiPayload::read(200, &[0x80], &parameter_sets, 0), SeiPayload::Unknown(_)));
+    }
+}
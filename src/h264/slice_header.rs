@@ -1,7 +1,10 @@
 use std::fmt;
 
-use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, sps_pps_provider::SpsPpsProvider};
+use crate::error::{Error, Result};
 
+use super::{dec_ref_pic_marking::DecRefPicMarking, descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, pred_weight_table::PredWeightTable, ref_pic_list_modification::RefPicListModification, sps_pps_provider::SpsPpsProvider};
+
+#[derive(Clone)]
 pub struct SliceHeader {
     pub idr_pic_flag: bool,
     pub first_mb_in_slice: u64,
@@ -13,39 +16,136 @@ pub struct SliceHeader {
     pub bottom_field_flag: bool,
     pub idr_pic_id: u64,
     pub pic_order_cnt_lsb: u64,
+    pub delta_pic_order_cnt_bottom: i64,
+    pub delta_pic_order_cnt: [i64; 2],
+    pub redundant_pic_cnt: u64,
+    pub direct_spatial_mv_pred_flag: bool,
+    pub num_ref_idx_active_override_flag: bool,
+    pub num_ref_idx_l0_active_minus1: u64,      // PPS default unless overridden
+    pub num_ref_idx_l1_active_minus1: u64,      // PPS default unless overridden
+    pub ref_pic_list_modification: RefPicListModification,
+    pub pred_weight_table: Option<PredWeightTable>,
+    pub dec_ref_pic_marking: Option<DecRefPicMarking>,     // present for reference pictures only
+    pub cabac_init_idc: u64,
+    pub slice_qp_delta: i64,
+    pub sp_for_switch_flag: bool,
+    pub slice_qs_delta: i64,
+    pub disable_deblocking_filter_idc: u64,
+    pub slice_alpha_c0_offset_div2: i64,
+    pub slice_beta_offset_div2: i64,
+    pub slice_group_change_cycle: u64
 }
 
 impl SliceHeader {
-    pub fn read(descriptor_reader: &mut DescriptorReader, idr_pic_flag: bool, sps_pps_provider: &impl SpsPpsProvider) -> Self {
-        let first_mb_in_slice = descriptor_reader.read_ue_v();
-        let slice_type = descriptor_reader.read_ue_v();
-        let pic_parameter_set_id = descriptor_reader.read_ue_v();
-        let pps = sps_pps_provider.get_pps(pic_parameter_set_id).unwrap();
-        let sps = sps_pps_provider.get_sps(pps.seq_parameter_set_id).unwrap();
+    pub fn read(descriptor_reader: &mut DescriptorReader, header: u8, sps_pps_provider: &impl SpsPpsProvider) -> Result<Self> {
+        let idr_pic_flag = header & 0b00011111 == 5;
+        let nal_ref_idc = (header & 0b01100000) >> 5;
+        let first_mb_in_slice = descriptor_reader.read_ue_v()?;
+        let slice_type = descriptor_reader.read_ue_v()?;
+        if slice_type > 9 {
+            return Err(Error::spec_violation(format!("slice_type {}", slice_type)));
+        }
+        let pic_parameter_set_id = descriptor_reader.read_ue_v()?;
+        let pps = sps_pps_provider.get_pps(pic_parameter_set_id)
+            .ok_or_else(|| Error::spec_violation(format!("slice refers to missing PPS {}", pic_parameter_set_id)))?;
+        let sps = sps_pps_provider.get_sps(pps.seq_parameter_set_id)
+            .ok_or_else(|| Error::spec_violation(format!("PPS refers to missing SPS {}", pps.seq_parameter_set_id)))?;
         let mut colour_plane_id: u8 = 0;
         if sps.separate_colour_plane_flag {
-            colour_plane_id = descriptor_reader.read_u(2).try_into().unwrap();
+            colour_plane_id = descriptor_reader.read_u(2)? as u8;
         }
         let frame_num_bits = sps.log2_max_frame_num_minus4 + 4;
-        let frame_num = descriptor_reader.read_u(u8::try_from(frame_num_bits).unwrap());
+        let frame_num = descriptor_reader.read_u(u8::try_from(frame_num_bits)?)?;
         let mut field_pic_flag = false;
         let mut bottom_field_flag = false;
         if !sps.frame_mbs_only_flag {
-            field_pic_flag = descriptor_reader.read_u1();
+            field_pic_flag = descriptor_reader.read_u1()?;
             if field_pic_flag {
-                bottom_field_flag = descriptor_reader.read_u1();
+                bottom_field_flag = descriptor_reader.read_u1()?;
             }
         }
         let mut idr_pic_id = 0;
         if idr_pic_flag {
-            idr_pic_id = descriptor_reader.read_ue_v();
+            idr_pic_id = descriptor_reader.read_ue_v()?;
         }
         let mut pic_order_cnt_lsb = 0;
+        let mut delta_pic_order_cnt_bottom = 0;
         if sps.pic_order_cnt_type == 0 {
             let pic_order_cnt_lsb_bits = sps.log2_max_pic_order_cnt_lsb_minus4 + 4;
-            pic_order_cnt_lsb = descriptor_reader.read_u(u8::try_from(pic_order_cnt_lsb_bits).unwrap());
+            pic_order_cnt_lsb = descriptor_reader.read_u(u8::try_from(pic_order_cnt_lsb_bits)?)?;
+            if pps.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag {
+                delta_pic_order_cnt_bottom = descriptor_reader.read_se_v()?;
+            }
+        }
+        let mut delta_pic_order_cnt = [0; 2];
+        if sps.pic_order_cnt_type == 1 && !sps.delta_pic_order_always_zero_flag {
+            delta_pic_order_cnt[0] = descriptor_reader.read_se_v()?;
+            if pps.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag {
+                delta_pic_order_cnt[1] = descriptor_reader.read_se_v()?;
+            }
+        }
+        let mut redundant_pic_cnt = 0;
+        if pps.redundant_pic_cnt_present_flag {
+            redundant_pic_cnt = descriptor_reader.read_ue_v()?;
+        }
+        let is_b = slice_type % 5 == 1;
+        let is_p_or_sp = matches!(slice_type % 5, 0 | 3);
+        let mut direct_spatial_mv_pred_flag = false;
+        if is_b {
+            direct_spatial_mv_pred_flag = descriptor_reader.read_u1()?;
+        }
+        let mut num_ref_idx_active_override_flag = false;
+        let mut num_ref_idx_l0_active_minus1 = pps.num_ref_idx_10_default_active_minus1;
+        let mut num_ref_idx_l1_active_minus1 = pps.num_ref_idx_l1_default_active_minus1;
+        if is_p_or_sp || is_b {
+            num_ref_idx_active_override_flag = descriptor_reader.read_u1()?;
+            if num_ref_idx_active_override_flag {
+                num_ref_idx_l0_active_minus1 = descriptor_reader.read_ue_v()?;
+                if is_b {
+                    num_ref_idx_l1_active_minus1 = descriptor_reader.read_ue_v()?;
+                }
+            }
+        }
+        if num_ref_idx_l0_active_minus1 > 31 || num_ref_idx_l1_active_minus1 > 31 {
+            return Err(Error::spec_violation("num_ref_idx_active_minus1 out of range"));
+        }
+        let ref_pic_list_modification = RefPicListModification::read(descriptor_reader, slice_type)?;
+        let mut pred_weight_table = None;
+        if (pps.weighted_pred_flag && is_p_or_sp) || (pps.weighted_bipred_idc == 1 && is_b) {
+            pred_weight_table = Some(PredWeightTable::read(descriptor_reader, slice_type, num_ref_idx_l0_active_minus1, num_ref_idx_l1_active_minus1, sps.get_chroma_array_type())?);
+        }
+        let mut dec_ref_pic_marking = None;
+        if nal_ref_idc != 0 {
+            dec_ref_pic_marking = Some(DecRefPicMarking::read(descriptor_reader, idr_pic_flag)?);
+        }
+        let mut cabac_init_idc = 0;
+        if pps.entropy_coding_mode_flag && slice_type % 5 != 2 && slice_type % 5 != 4 {
+            cabac_init_idc = descriptor_reader.read_ue_v()?;
+        }
+        let slice_qp_delta = descriptor_reader.read_se_v()?;
+        let mut sp_for_switch_flag = false;
+        let mut slice_qs_delta = 0;
+        if slice_type % 5 == 3 || slice_type % 5 == 4 {
+            if slice_type % 5 == 3 {
+                sp_for_switch_flag = descriptor_reader.read_u1()?;
+            }
+            slice_qs_delta = descriptor_reader.read_se_v()?;
         }
-        SliceHeader {
+        let mut disable_deblocking_filter_idc = 0;
+        let mut slice_alpha_c0_offset_div2 = 0;
+        let mut slice_beta_offset_div2 = 0;
+        if pps.deblocking_filter_control_present_flag {
+            disable_deblocking_filter_idc = descriptor_reader.read_ue_v()?;
+            if disable_deblocking_filter_idc != 1 {
+                slice_alpha_c0_offset_div2 = descriptor_reader.read_se_v()?;
+                slice_beta_offset_div2 = descriptor_reader.read_se_v()?;
+            }
+        }
+        let mut slice_group_change_cycle = 0;
+        if pps.num_slice_groups_minus1 > 0 && (3..=5).contains(&pps.slice_group_map_type) {
+            slice_group_change_cycle = descriptor_reader.read_u(pps.get_slice_group_change_cycle_length(sps))?;
+        }
+        Ok(SliceHeader {
             idr_pic_flag,
             first_mb_in_slice,
             slice_type,
@@ -55,13 +155,33 @@ impl SliceHeader {
             field_pic_flag,
             bottom_field_flag,
             idr_pic_id,
-            pic_order_cnt_lsb
-        }
+            pic_order_cnt_lsb,
+            delta_pic_order_cnt_bottom,
+            delta_pic_order_cnt,
+            redundant_pic_cnt,
+            direct_spatial_mv_pred_flag,
+            num_ref_idx_active_override_flag,
+            num_ref_idx_l0_active_minus1,
+            num_ref_idx_l1_active_minus1,
+            ref_pic_list_modification,
+            pred_weight_table,
+            dec_ref_pic_marking,
+            cabac_init_idc,
+            slice_qp_delta,
+            sp_for_switch_flag,
+            slice_qs_delta,
+            disable_deblocking_filter_idc,
+            slice_alpha_c0_offset_div2,
+            slice_beta_offset_div2,
+            slice_group_change_cycle
+        })
     }
 
-    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, sps_pps_provider: &dyn SpsPpsProvider) {
-        let pps = sps_pps_provider.get_pps(self.pic_parameter_set_id).unwrap();
-        let sps = sps_pps_provider.get_sps(pps.seq_parameter_set_id).unwrap();
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter, sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
+        let pps = sps_pps_provider.get_pps(self.pic_parameter_set_id)
+            .ok_or_else(|| Error::spec_violation(format!("slice refers to missing PPS {}", self.pic_parameter_set_id)))?;
+        let sps = sps_pps_provider.get_sps(pps.seq_parameter_set_id)
+            .ok_or_else(|| Error::spec_violation(format!("PPS refers to missing SPS {}", pps.seq_parameter_set_id)))?;
 
         descriptor_writer.append_ue_v(self.first_mb_in_slice);
         descriptor_writer.append_ue_v(self.slice_type);
@@ -70,7 +190,7 @@ impl SliceHeader {
             descriptor_writer.append_u(2, self.colour_plane_id.into());
         }
         let frame_num_bits = sps.log2_max_frame_num_minus4 + 4;
-        descriptor_writer.append_u(u8::try_from(frame_num_bits).unwrap(), self.frame_num);
+        descriptor_writer.append_u(u8::try_from(frame_num_bits)?, self.frame_num);
         if !sps.frame_mbs_only_flag {
             descriptor_writer.append_u1(self.field_pic_flag);
             if self.field_pic_flag {
@@ -82,8 +202,82 @@ impl SliceHeader {
         }
         if sps.pic_order_cnt_type == 0 {
             let pic_order_cnt_lsb_bits = sps.log2_max_pic_order_cnt_lsb_minus4 + 4;
-            descriptor_writer.append_u(u8::try_from(pic_order_cnt_lsb_bits).unwrap(), self.pic_order_cnt_lsb);
+            descriptor_writer.append_u(u8::try_from(pic_order_cnt_lsb_bits)?, self.pic_order_cnt_lsb);
+            if pps.bottom_field_pic_order_in_frame_present_flag && !self.field_pic_flag {
+                descriptor_writer.append_se_v(self.delta_pic_order_cnt_bottom);
+            }
+        }
+        if sps.pic_order_cnt_type == 1 && !sps.delta_pic_order_always_zero_flag {
+            descriptor_writer.append_se_v(self.delta_pic_order_cnt[0]);
+            if pps.bottom_field_pic_order_in_frame_present_flag && !self.field_pic_flag {
+                descriptor_writer.append_se_v(self.delta_pic_order_cnt[1]);
+            }
+        }
+        if pps.redundant_pic_cnt_present_flag {
+            descriptor_writer.append_ue_v(self.redundant_pic_cnt);
+        }
+        let is_b = self.slice_type % 5 == 1;
+        let is_p_or_sp = matches!(self.slice_type % 5, 0 | 3);
+        if is_b {
+            descriptor_writer.append_u1(self.direct_spatial_mv_pred_flag);
+        }
+        if is_p_or_sp || is_b {
+            descriptor_writer.append_u1(self.num_ref_idx_active_override_flag);
+            if self.num_ref_idx_active_override_flag {
+                descriptor_writer.append_ue_v(self.num_ref_idx_l0_active_minus1);
+                if is_b {
+                    descriptor_writer.append_ue_v(self.num_ref_idx_l1_active_minus1);
+                }
+            }
+        }
+        self.ref_pic_list_modification.write(descriptor_writer, self.slice_type);
+        if (pps.weighted_pred_flag && is_p_or_sp) || (pps.weighted_bipred_idc == 1 && is_b) {
+            let pred_weight_table = self.pred_weight_table.as_ref()
+                .ok_or_else(|| Error::spec_violation("pred_weight_table required by the PPS is missing"))?;
+            pred_weight_table.write(descriptor_writer, self.slice_type, self.num_ref_idx_l0_active_minus1, self.num_ref_idx_l1_active_minus1, sps.get_chroma_array_type())?;
+        }
+        if let Some(dec_ref_pic_marking) = &self.dec_ref_pic_marking {
+            dec_ref_pic_marking.write(descriptor_writer, self.idr_pic_flag);
+        }
+        if pps.entropy_coding_mode_flag && self.slice_type % 5 != 2 && self.slice_type % 5 != 4 {
+            descriptor_writer.append_ue_v(self.cabac_init_idc);
         }
+        descriptor_writer.append_se_v(self.slice_qp_delta);
+        if self.slice_type % 5 == 3 || self.slice_type % 5 == 4 {
+            if self.slice_type % 5 == 3 {
+                descriptor_writer.append_u1(self.sp_for_switch_flag);
+            }
+            descriptor_writer.append_se_v(self.slice_qs_delta);
+        }
+        if pps.deblocking_filter_control_present_flag {
+            descriptor_writer.append_ue_v(self.disable_deblocking_filter_idc);
+            if self.disable_deblocking_filter_idc != 1 {
+                descriptor_writer.append_se_v(self.slice_alpha_c0_offset_div2);
+                descriptor_writer.append_se_v(self.slice_beta_offset_div2);
+            }
+        }
+        if pps.num_slice_groups_minus1 > 0 && (3..=5).contains(&pps.slice_group_map_type) {
+            descriptor_writer.append_u(pps.get_slice_group_change_cycle_length(sps), self.slice_group_change_cycle);
+        }
+        Ok(())
+    }
+
+    // whether this slice differs from the previous primary slice in a way that makes it the first slice of a new primary coded picture (7.4.1.2.4)
+    pub fn differs_in_picture(&self, nal_ref_idc: u8, previous: &SliceHeader, previous_nal_ref_idc: u8) -> bool {
+        // slices of redundant coded pictures belong to the primary picture before them
+        if self.redundant_pic_cnt > 0 {
+            return false;
+        }
+        self.frame_num != previous.frame_num
+            || self.pic_parameter_set_id != previous.pic_parameter_set_id
+            || self.field_pic_flag != previous.field_pic_flag
+            || self.bottom_field_flag != previous.bottom_field_flag
+            || (nal_ref_idc == 0) != (previous_nal_ref_idc == 0)
+            || self.pic_order_cnt_lsb != previous.pic_order_cnt_lsb
+            || self.delta_pic_order_cnt_bottom != previous.delta_pic_order_cnt_bottom
+            || self.delta_pic_order_cnt != previous.delta_pic_order_cnt
+            || self.idr_pic_flag != previous.idr_pic_flag
+            || (self.idr_pic_flag && self.idr_pic_id != previous.idr_pic_id)
     }
 }
 
@@ -97,7 +291,7 @@ impl fmt::Debug for SliceHeader {
         // };
 
         f.debug_struct("SliceHeader")
-            .field("idr_pic_flag", &self.slice_type)
+            .field("idr_pic_flag", &self.idr_pic_flag)
             .field("first_mb_in_slice", &self.first_mb_in_slice)
             .field("slice_type", &self.slice_type)
             .field("pic_parameter_set_id", &self.pic_parameter_set_id)
@@ -107,6 +301,133 @@ impl fmt::Debug for SliceHeader {
             .field("bottom_field_flag", &self.bottom_field_flag)
             .field("idr_pic_id", &self.idr_pic_id)
             .field("pic_order_cnt_lsb", &self.pic_order_cnt_lsb)
+            .field("delta_pic_order_cnt_bottom", &self.delta_pic_order_cnt_bottom)
+            .field("delta_pic_order_cnt", &self.delta_pic_order_cnt)
+            .field("redundant_pic_cnt", &self.redundant_pic_cnt)
+            .field("direct_spatial_mv_pred_flag", &self.direct_spatial_mv_pred_flag)
+            .field("num_ref_idx_active_override_flag", &self.num_ref_idx_active_override_flag)
+            .field("num_ref_idx_l0_active_minus1", &self.num_ref_idx_l0_active_minus1)
+            .field("num_ref_idx_l1_active_minus1", &self.num_ref_idx_l1_active_minus1)
+            .field("ref_pic_list_modification", &self.ref_pic_list_modification)
+            .field("pred_weight_table", &self.pred_weight_table)
+            .field("dec_ref_pic_marking", &self.dec_ref_pic_marking)
+            .field("cabac_init_idc", &self.cabac_init_idc)
+            .field("slice_qp_delta", &self.slice_qp_delta)
+            .field("sp_for_switch_flag", &self.sp_for_switch_flag)
+            .field("slice_qs_delta", &self.slice_qs_delta)
+            .field("disable_deblocking_filter_idc", &self.disable_deblocking_filter_idc)
+            .field("slice_alpha_c0_offset_div2", &self.slice_alpha_c0_offset_div2)
+            .field("slice_beta_offset_div2", &self.slice_beta_offset_div2)
+            .field("slice_group_change_cycle", &self.slice_group_change_cycle)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use crate::{h264::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, parameter_sets::ParameterSets, pps_nalu::PpsNalu, sps_nalu::SpsNalu}, test_util::{BASELINE_SPS, to_bytes, ue}, ErrorKind};
+
+    use super::SliceHeader;
+
+    fn get_parameter_sets() -> ParameterSets {
+        let mut parameter_sets = ParameterSets::new();
+        parameter_sets.insert_sps(SpsNalu::read(&mut Cursor::new(BASELINE_SPS), BASELINE_SPS.len() as u32, 0x67).unwrap());
+        // CAVLC with weighted_pred_flag and deblocking_filter_control_present_flag
+        let pps = to_bytes("1 1 0 0 1 1 1 1 00 1 1 1 1 0 0");
+        parameter_sets.insert_pps(PpsNalu::read(&mut Cursor::new(&pps), pps.len() as u32, 0x68, &parameter_sets).unwrap());
+        parameter_sets
+    }
+
+    #[test]
+    fn round_trip_p_slice_header() {
+        let parameter_sets = get_parameter_sets();
+        let rbsp = to_bytes(concat!(
+            "1 00110 1 0011 ",                          // first_mb_in_slice 0, slice_type 5, PPS 0, frame_num 3
+            "1 010 ",                                   // num_ref_idx_l0_active_minus1 1
+            "1 1 011 011 1 00100 ",                     // ref_pic_list_modification: short-term -3, long-term 0
+            "00110 010 1 00111 00100 0 0 1 010 011 1 1 ",   // pred_weight_table of two references
+            "1 010 1 00100 010 1 1 ",                   // dec_ref_pic_marking: MMCO 1, 3 then end
+            "00101 1 010 011"                           // slice_qp_delta -2, deblocking filter offsets 1 and -1
+        ));
+        let slice_header = SliceHeader::read(&mut DescriptorReader::from_rbsp(rbsp.clone()), 0x41, &parameter_sets).unwrap();
+
+        assert_eq!(slice_header.slice_type, 5);
+        assert_eq!(slice_header.frame_num, 3);
+        assert_eq!(slice_header.num_ref_idx_l0_active_minus1, 1);
+        let ref_pic_list_modification = &slice_header.ref_pic_list_modification;
+        assert!(ref_pic_list_modification.ref_pic_list_modification_flag_l0);
+        assert_eq!(ref_pic_list_modification.modification_of_pic_nums_idc_l0, [0, 2]);
+        assert_eq!(ref_pic_list_modification.abs_diff_pic_num_minus1_l0, [2, 0]);
+        assert_eq!(ref_pic_list_modification.long_term_pic_num_l0, [0, 0]);
+        let pred_weight_table = slice_header.pred_weight_table.as_ref().unwrap();
+        assert_eq!(pred_weight_table.luma_log2_weight_denom, 5);
+        assert_eq!(pred_weight_table.chroma_log2_weight_denom, 1);
+        assert_eq!(pred_weight_table.l0.len(), 2);
+        assert!(pred_weight_table.l1.is_empty());
+        assert!(pred_weight_table.l0[0].luma_weight_flag && !pred_weight_table.l0[0].chroma_weight_flag);
+        assert_eq!((pred_weight_table.l0[0].luma_weight, pred_weight_table.l0[0].luma_offset), (-3, 2));
+        assert_eq!(pred_weight_table.l0[0].chroma_weight, [2, 2]);
+        assert!(!pred_weight_table.l0[1].luma_weight_flag && pred_weight_table.l0[1].chroma_weight_flag);
+        assert_eq!((pred_weight_table.l0[1].luma_weight, pred_weight_table.l0[1].luma_offset), (32, 0));
+        assert_eq!(pred_weight_table.l0[1].chroma_weight, [1, 0]);
+        assert_eq!(pred_weight_table.l0[1].chroma_offset, [-1, 0]);
+        let dec_ref_pic_marking = slice_header.dec_ref_pic_marking.as_ref().unwrap();
+        assert!(dec_ref_pic_marking.adaptive_ref_pic_marking_mode_flag);
+        assert_eq!(dec_ref_pic_marking.memory_management_control_operation, [1, 3]);
+        assert_eq!(dec_ref_pic_marking.difference_of_pic_nums_minus1, [0, 1]);
+        assert_eq!(dec_ref_pic_marking.long_term_frame_idx, [0, 0]);
+        assert_eq!(slice_header.slice_qp_delta, -2);
+        assert_eq!((slice_header.slice_alpha_c0_offset_div2, slice_header.slice_beta_offset_div2), (1, -1));
+
+        let mut sink = io::sink();
+        let mut descriptor_writer = DescriptorWriter::new(&mut sink);
+        slice_header.write(&mut descriptor_writer, &parameter_sets).unwrap();
+        descriptor_writer.append_rbsp_trailing_bits();
+        assert_eq!(descriptor_writer.into_rbsp(), rbsp);
+    }
+
+    #[test]
+    fn reject_weight_denominator_out_of_range() {
+        let parameter_sets = get_parameter_sets();
+        for luma_log2_weight_denom in ["0001001", "0000001000001"] {   // 8 and 64
+            let rbsp = to_bytes(&format!("1 00110 1 0011 1 010 0 {} 010 0 0 0 0 1 00101 1 010 011", luma_log2_weight_denom));
+            let result = SliceHeader::read(&mut DescriptorReader::from_rbsp(rbsp), 0x01, &parameter_sets);
+            assert!(matches!(result.unwrap_err().kind, ErrorKind::SpecViolation(_)));
+        }
+    }
+
+    #[test]
+    fn reject_weights_not_matching_active_references() {
+        let parameter_sets = get_parameter_sets();
+        let rbsp = to_bytes("1 00110 1 0011 1 010 0 00110 010 0 0 0 0 00101 1 010 011");
+        let mut slice_header = SliceHeader::read(&mut DescriptorReader::from_rbsp(rbsp), 0x01, &parameter_sets).unwrap();
+        slice_header.num_ref_idx_l0_active_minus1 = 2;
+
+        let mut sink = io::sink();
+        let result = slice_header.write(&mut DescriptorWriter::new(&mut sink), &parameter_sets);
+        assert!(matches!(result.unwrap_err().kind, ErrorKind::SpecViolation(_)));
+    }
+
+    #[test]
+    fn round_trip_slice_group_change_cycle() {
+        // Ceil(Log2(300 / SliceGroupChangeRate + 1)) bits for the 300 macroblocks of the Baseline SPS
+        for (slice_group_map_type, slice_group_change_rate_minus1, slice_group_change_cycle) in [(3, 0, "100101100"), (4, 9, "10110"), (5, 299, "1")] {
+            let mut parameter_sets = ParameterSets::new();
+            parameter_sets.insert_sps(SpsNalu::read(&mut Cursor::new(BASELINE_SPS), BASELINE_SPS.len() as u32, 0x67).unwrap());
+            let pps = to_bytes(&format!("1 1 0 0 010 {} 0 {} 1 1 0 00 1 1 1 1 0 0", ue(slice_group_map_type), ue(slice_group_change_rate_minus1)));
+            parameter_sets.insert_pps(PpsNalu::read(&mut Cursor::new(&pps), pps.len() as u32, 0x68, &parameter_sets).unwrap());
+            // I slice of an IDR picture
+            let rbsp = to_bytes(&format!("1 0001000 1 0000 1 0 0 1 010 {}", slice_group_change_cycle));
+            let slice_header = SliceHeader::read(&mut DescriptorReader::from_rbsp(rbsp.clone()), 0x65, &parameter_sets).unwrap();
+            assert_eq!(slice_header.slice_group_change_cycle, u64::from_str_radix(slice_group_change_cycle, 2).unwrap());
+
+            let mut sink = io::sink();
+            let mut descriptor_writer = DescriptorWriter::new(&mut sink);
+            slice_header.write(&mut descriptor_writer, &parameter_sets).unwrap();
+            descriptor_writer.append_rbsp_trailing_bits();
+            assert_eq!(descriptor_writer.into_rbsp(), rbsp);
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use std::{any::Any, fmt, io::{self, Read, Write}};
+use std::{any::Any, fmt, io::{Read, Write}};
 
-use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, sps_pps_provider::SpsPpsProvider, vui_parameters::VuiParameters};
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, scaling_list::ScalingList, scaling_matrix::ScalingMatrix, sps_pps_provider::SpsPpsProvider, vui_parameters::VuiParameters};
 
 #[derive(Debug, Clone)]
 pub struct SpsNalu {
+    pub header: u8,
     pub profile_idc: u8,
     pub constraint_set0_flag: bool,
     pub constraint_set1_flag: bool,
@@ -19,9 +22,14 @@ pub struct SpsNalu {
     pub bit_depth_chroma_minus8: u64,
     pub qpprime_y_zero_transform_bypass_flag: bool,
     pub seq_scaling_matrix_present_flag: bool,
+    pub seq_scaling_lists: Vec<Option<ScalingList>>,   // None where seq_scaling_list_present_flag is 0
     pub log2_max_frame_num_minus4: u64,
     pub pic_order_cnt_type: u64,
     pub log2_max_pic_order_cnt_lsb_minus4: u64,
+    pub delta_pic_order_always_zero_flag: bool,
+    pub offset_for_non_ref_pic: i64,
+    pub offset_for_top_to_bottom_field: i64,
+    pub offset_for_ref_frame: Vec<i64>,
     pub max_num_ref_frames: u64,
     pub gaps_in_frame_num_value_allowed_flag: bool,
     pub pic_width_in_mbs_minus1: u64,
@@ -39,81 +47,106 @@ pub struct SpsNalu {
 }
 
 impl SpsNalu {
-    pub fn read(rdr: &mut impl Read, len: u32) -> io::Result<Self> {
-        let mut descriptor_reader = DescriptorReader::new(rdr, len);
-        let profile_idc = descriptor_reader.read_u8();
-        let constraint_set0_flag = descriptor_reader.read_u1();
-        let constraint_set1_flag = descriptor_reader.read_u1();
-        let constraint_set2_flag = descriptor_reader.read_u1();
-        let constraint_set3_flag = descriptor_reader.read_u1();
-        let constraint_set4_flag = descriptor_reader.read_u1();
-        let constraint_set5_flag = descriptor_reader.read_u1();
-        let _reserved_zero_2bits = descriptor_reader.read_u(2);
-        let level_idc: u8 = descriptor_reader.read_u8();
-        let seq_parameter_set_id = descriptor_reader.read_ue_v();
-        let mut chroma_format_idc = 0;
+    pub fn read(rdr: &mut impl Read, len: u32, header: u8) -> Result<Self> {
+        let mut descriptor_reader = DescriptorReader::new(rdr, len)?;
+        let profile_idc = descriptor_reader.read_u8()?;
+        let constraint_set0_flag = descriptor_reader.read_u1()?;
+        let constraint_set1_flag = descriptor_reader.read_u1()?;
+        let constraint_set2_flag = descriptor_reader.read_u1()?;
+        let constraint_set3_flag = descriptor_reader.read_u1()?;
+        let constraint_set4_flag = descriptor_reader.read_u1()?;
+        let constraint_set5_flag = descriptor_reader.read_u1()?;
+        let _reserved_zero_2bits = descriptor_reader.read_u(2)?;
+        let level_idc: u8 = descriptor_reader.read_u8()?;
+        let seq_parameter_set_id = descriptor_reader.read_ue_v()?;
+        let mut chroma_format_idc = 1;     // inferred 4:2:0 when not present
         let mut separate_colour_plane_flag = false;
         let mut bit_depth_luma_minus8 = 0;
         let mut bit_depth_chroma_minus8 = 0;
         let mut qpprime_y_zero_transform_bypass_flag = false;
         let mut seq_scaling_matrix_present_flag = false;
+        let mut seq_scaling_lists = vec![];
         match profile_idc {
             100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 => {
-                chroma_format_idc = descriptor_reader.read_ue_v();
+                chroma_format_idc = descriptor_reader.read_ue_v()?;
                 if chroma_format_idc == 3 {
-                    separate_colour_plane_flag = descriptor_reader.read_u1();
+                    separate_colour_plane_flag = descriptor_reader.read_u1()?;
                 }
-                bit_depth_luma_minus8 = descriptor_reader.read_ue_v();
-                bit_depth_chroma_minus8 = descriptor_reader.read_ue_v();
-                qpprime_y_zero_transform_bypass_flag = descriptor_reader.read_u1();
-                seq_scaling_matrix_present_flag = descriptor_reader.read_u1();
+                bit_depth_luma_minus8 = descriptor_reader.read_ue_v()?;
+                bit_depth_chroma_minus8 = descriptor_reader.read_ue_v()?;
+                qpprime_y_zero_transform_bypass_flag = descriptor_reader.read_u1()?;
+                seq_scaling_matrix_present_flag = descriptor_reader.read_u1()?;
                 if seq_scaling_matrix_present_flag {
-                    for _i in 0..=(if chroma_format_idc != 3 { 8 } else { 12 }) {
-                        let seq_scaling_list_present_flag = descriptor_reader.read_u1();
+                    for i in 0..(if chroma_format_idc != 3 { 8 } else { 12 }) {
+                        let seq_scaling_list_present_flag = descriptor_reader.read_u1()?;
                         if seq_scaling_list_present_flag {
-                            todo!()
+                            seq_scaling_lists.push(Some(ScalingList::read(&mut descriptor_reader, if i < 6 { 16 } else { 64 })?));
+                        } else {
+                            seq_scaling_lists.push(None);
                         }
                     }
                 }
             }
             _ => {}
         }
-        let log2_max_frame_num_minus4 = descriptor_reader.read_ue_v();
-        let pic_order_cnt_type = descriptor_reader.read_ue_v();
+        let log2_max_frame_num_minus4 = descriptor_reader.read_ue_v()?;
+        if log2_max_frame_num_minus4 > 12 {
+            return Err(Error::spec_violation(format!("log2_max_frame_num_minus4 {} out of range", log2_max_frame_num_minus4)));
+        }
+        let pic_order_cnt_type = descriptor_reader.read_ue_v()?;
         let mut log2_max_pic_order_cnt_lsb_minus4 = 0;
+        let mut delta_pic_order_always_zero_flag = false;
+        let mut offset_for_non_ref_pic = 0;
+        let mut offset_for_top_to_bottom_field = 0;
+        let mut offset_for_ref_frame = vec![];
         if pic_order_cnt_type == 0 {
-            log2_max_pic_order_cnt_lsb_minus4 = descriptor_reader.read_ue_v();
+            log2_max_pic_order_cnt_lsb_minus4 = descriptor_reader.read_ue_v()?;
+            if log2_max_pic_order_cnt_lsb_minus4 > 12 {
+                return Err(Error::spec_violation(format!("log2_max_pic_order_cnt_lsb_minus4 {} out of range", log2_max_pic_order_cnt_lsb_minus4)));
+            }
         } else if pic_order_cnt_type == 1 {
-            todo!()
+            delta_pic_order_always_zero_flag = descriptor_reader.read_u1()?;
+            offset_for_non_ref_pic = descriptor_reader.read_se_v()?;
+            offset_for_top_to_bottom_field = descriptor_reader.read_se_v()?;
+            let num_ref_frames_in_pic_order_cnt_cycle = descriptor_reader.read_ue_v()?;
+            if num_ref_frames_in_pic_order_cnt_cycle > 255 {
+                return Err(Error::spec_violation(format!("num_ref_frames_in_pic_order_cnt_cycle {} out of range", num_ref_frames_in_pic_order_cnt_cycle)));
+            }
+            for _i in 0..num_ref_frames_in_pic_order_cnt_cycle {
+                offset_for_ref_frame.push(descriptor_reader.read_se_v()?);
+            }
+        } else if pic_order_cnt_type != 2 {
+            return Err(Error::spec_violation(format!("pic_order_cnt_type {} out of range", pic_order_cnt_type)));
         }
-        let max_num_ref_frames = descriptor_reader.read_ue_v();
-        let gaps_in_frame_num_value_allowed_flag = descriptor_reader.read_u1();
-        let pic_width_in_mbs_minus1 = descriptor_reader.read_ue_v();
-        let pic_height_in_map_units_minus1 = descriptor_reader.read_ue_v();
-        let frame_mbs_only_flag = descriptor_reader.read_u1();
+        let max_num_ref_frames = descriptor_reader.read_ue_v()?;
+        let gaps_in_frame_num_value_allowed_flag = descriptor_reader.read_u1()?;
+        let pic_width_in_mbs_minus1 = descriptor_reader.read_ue_v()?;
+        let pic_height_in_map_units_minus1 = descriptor_reader.read_ue_v()?;
+        let frame_mbs_only_flag = descriptor_reader.read_u1()?;
         let mut mb_adaptive_frame_field_flag = false;
         if !frame_mbs_only_flag {
-            mb_adaptive_frame_field_flag = descriptor_reader.read_u1();
+            mb_adaptive_frame_field_flag = descriptor_reader.read_u1()?;
         }
-        let direct_8x8_inference_flag = descriptor_reader.read_u1();
-        let frame_cropping_flag = descriptor_reader.read_u1();
+        let direct_8x8_inference_flag = descriptor_reader.read_u1()?;
+        let frame_cropping_flag = descriptor_reader.read_u1()?;
         let mut frame_crop_left_offset = 0;
         let mut frame_crop_right_offset = 0;
         let mut frame_crop_top_offset = 0;
         let mut frame_crop_bottom_offset = 0;
         if frame_cropping_flag {
-            frame_crop_left_offset = descriptor_reader.read_ue_v();
-            frame_crop_right_offset = descriptor_reader.read_ue_v();
-            frame_crop_top_offset = descriptor_reader.read_ue_v();
-            frame_crop_bottom_offset = descriptor_reader.read_ue_v();
+            frame_crop_left_offset = descriptor_reader.read_ue_v()?;
+            frame_crop_right_offset = descriptor_reader.read_ue_v()?;
+            frame_crop_top_offset = descriptor_reader.read_ue_v()?;
+            frame_crop_bottom_offset = descriptor_reader.read_ue_v()?;
         }
-        let vui_parameters_present_flag = descriptor_reader.read_u1();
+        let vui_parameters_present_flag = descriptor_reader.read_u1()?;
         let mut vui_parameters = Option::None;
         if vui_parameters_present_flag {
-            vui_parameters = Option::Some(VuiParameters::read(&mut descriptor_reader));
+            vui_parameters = Option::Some(VuiParameters::read(&mut descriptor_reader)?);
         }
 
         Ok(SpsNalu {
+            header,
             profile_idc,
             constraint_set0_flag,
             constraint_set1_flag,
@@ -127,11 +160,16 @@ impl SpsNalu {
             bit_depth_chroma_minus8,
             qpprime_y_zero_transform_bypass_flag,
             seq_scaling_matrix_present_flag,
+            seq_scaling_lists,
             chroma_format_idc,
             separate_colour_plane_flag,
             log2_max_frame_num_minus4,
             pic_order_cnt_type,
             log2_max_pic_order_cnt_lsb_minus4,
+            delta_pic_order_always_zero_flag,
+            offset_for_non_ref_pic,
+            offset_for_top_to_bottom_field,
+            offset_for_ref_frame,
             max_num_ref_frames,
             gaps_in_frame_num_value_allowed_flag,
             pic_width_in_mbs_minus1,
@@ -148,10 +186,46 @@ impl SpsNalu {
             payload_size: len
         })
     }
+
+    // scaling lists after fall-back rule A, flat when seq_scaling_matrix_present_flag is 0
+    pub fn get_scaling_matrix(&self) -> ScalingMatrix {
+        if self.seq_scaling_matrix_present_flag {
+            ScalingMatrix::resolve(&self.seq_scaling_lists, None)
+        } else {
+            ScalingMatrix::flat()
+        }
+    }
+
+    pub fn get_chroma_array_type(&self) -> u64 {
+        if self.separate_colour_plane_flag { 0 } else { self.chroma_format_idc }
+    }
+
+    // frame width in luma samples after cropping (7.4.2.1.1)
+    pub fn get_frame_width(&self) -> Result<u64> {
+        let crop_unit_x = match self.get_chroma_array_type() {
+            1 | 2 => 2,
+            _ => 1
+        };
+        let crop = crop_unit_x * (self.frame_crop_left_offset + self.frame_crop_right_offset);
+        ((self.pic_width_in_mbs_minus1 + 1) * 16).checked_sub(crop)
+            .ok_or_else(|| Error::spec_violation(format!("frame cropping of {} columns beyond the width of {} macroblocks", crop, self.pic_width_in_mbs_minus1 + 1)))
+    }
+
+    // frame height in luma samples after cropping (7.4.2.1.1)
+    pub fn get_frame_height(&self) -> Result<u64> {
+        let frame_height_factor = if self.frame_mbs_only_flag { 1 } else { 2 };
+        let crop_unit_y = match self.get_chroma_array_type() {
+            1 => 2 * frame_height_factor,
+            _ => frame_height_factor
+        };
+        let crop = crop_unit_y * (self.frame_crop_top_offset + self.frame_crop_bottom_offset);
+        (frame_height_factor * (self.pic_height_in_map_units_minus1 + 1) * 16).checked_sub(crop)
+            .ok_or_else(|| Error::spec_violation(format!("frame cropping of {} rows beyond the height of {} map units", crop, self.pic_height_in_map_units_minus1 + 1)))
+    }
 }
 
 impl Nalu for SpsNalu {
-    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) {
+    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
         let mut descriptor_writer = DescriptorWriter::new(wtr);
         descriptor_writer.append_u8(self.profile_idc);
         descriptor_writer.append_u1(self.constraint_set0_flag);
@@ -174,8 +248,11 @@ impl Nalu for SpsNalu {
                 descriptor_writer.append_u1(self.qpprime_y_zero_transform_bypass_flag);
                 descriptor_writer.append_u1(self.seq_scaling_matrix_present_flag);
                 if self.seq_scaling_matrix_present_flag {
-                    for _i in 0..=(if self.chroma_format_idc != 3 { 8 } else { 12 }) {
-                        descriptor_writer.append_u1(false);
+                    for seq_scaling_list in &self.seq_scaling_lists {
+                        descriptor_writer.append_u1(seq_scaling_list.is_some());
+                        if let Some(seq_scaling_list) = seq_scaling_list {
+                            seq_scaling_list.write(&mut descriptor_writer);
+                        }
                     }
                 }
             }
@@ -186,7 +263,13 @@ impl Nalu for SpsNalu {
         if self.pic_order_cnt_type == 0 {
             descriptor_writer.append_ue_v(self.log2_max_pic_order_cnt_lsb_minus4);
         } else if self.pic_order_cnt_type == 1 {
-            todo!()
+            descriptor_writer.append_u1(self.delta_pic_order_always_zero_flag);
+            descriptor_writer.append_se_v(self.offset_for_non_ref_pic);
+            descriptor_writer.append_se_v(self.offset_for_top_to_bottom_field);
+            descriptor_writer.append_ue_v(self.offset_for_ref_frame.len() as u64);
+            for offset in &self.offset_for_ref_frame {
+                descriptor_writer.append_se_v(*offset);
+            }
         }
         descriptor_writer.append_ue_v(self.max_num_ref_frames);
         descriptor_writer.append_u1(self.gaps_in_frame_num_value_allowed_flag);
@@ -213,7 +296,11 @@ impl Nalu for SpsNalu {
         }
 
         descriptor_writer.append_rbsp_trailing_bits();
-        descriptor_writer.write_with_header(0x67);
+        descriptor_writer.write_with_header(self.header)
+    }
+
+    fn get_nal_unit_type(&self) -> u8 {
+        7
     }
 
     fn as_any(&self) -> &dyn Any {
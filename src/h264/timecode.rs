@@ -0,0 +1,35 @@
+use std::fmt;
+
+// SMPTE timecode of a frame, shown as hh:mm:ss:ff, or hh:mm:ss;ff for drop-frame timecode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub drop_frame: bool
+}
+
+impl Timecode {
+    // frames since 00:00:00:00 at a nominal frame rate, e.g. 30 for 29.97; drop-frame timecode
+    // skips the first frame numbers of every minute except every tenth minute
+    pub fn get_frame_number(&self, frames_per_second: u32) -> u64 {
+        let frames_per_second = u64::from(frames_per_second);
+        let total_minutes = u64::from(self.hours) * 60 + u64::from(self.minutes);
+        let frame_number = (total_minutes * 60 + u64::from(self.seconds)) * frames_per_second + u64::from(self.frames);
+        if self.drop_frame {
+            // 2 frame numbers at 30, 4 at 60
+            let dropped_per_minute = frames_per_second / 15;
+            frame_number.saturating_sub(dropped_per_minute * (total_minutes - total_minutes / 10))
+        } else {
+            frame_number
+        }
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        write!(f, "{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, separator, self.frames)
+    }
+}
@@ -0,0 +1,80 @@
+use super::{clock_timestamp::ClockTimestamp, pic_timing::PicTiming, timecode::Timecode};
+
+// timecodes of pictures from the clock timestamps of their pic_timing SEI (D.2.3); seconds, minutes
+// and hours left out of a clock timestamp are those of the previous clock timestamp in decoding order
+#[derive(Default)]
+pub struct TimecodeDecoder {
+    previous: Option<Timecode>
+}
+
+impl TimecodeDecoder {
+    pub fn new() -> Self {
+        TimecodeDecoder {
+            previous: None
+        }
+    }
+
+    // timecode of the first clock timestamp of a picture, given in decoding order; None when the
+    // picture has none, or when it leaves out values and no earlier clock timestamp gave them
+    pub fn decode(&mut self, pic_timing: &PicTiming) -> Option<Timecode> {
+        let mut timecode = None;
+        for clock_timestamp in pic_timing.clock_timestamps.iter().flatten() {
+            let clock_timecode = self.decode_clock_timestamp(clock_timestamp);
+            timecode = timecode.or(clock_timecode);
+        }
+        timecode
+    }
+
+    fn decode_clock_timestamp(&mut self, clock_timestamp: &ClockTimestamp) -> Option<Timecode> {
+        let (hours, minutes, seconds) = match (clock_timestamp.seconds_flag, clock_timestamp.minutes_flag, clock_timestamp.hours_flag) {
+            (true, true, true) => (clock_timestamp.hours_value, clock_timestamp.minutes_value, clock_timestamp.seconds_value),
+            (true, true, false) => (self.previous?.hours, clock_timestamp.minutes_value, clock_timestamp.seconds_value),
+            (true, false, _) => (self.previous?.hours, self.previous?.minutes, clock_timestamp.seconds_value),
+            (false, _, _) => (self.previous?.hours, self.previous?.minutes, self.previous?.seconds)
+        };
+        let timecode = Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames: clock_timestamp.n_frames,
+            // counting_type 4 drops individual zero values of n_frames (Table D-3)
+            drop_frame: clock_timestamp.counting_type == 4
+        };
+        self.previous = Some(timecode);
+        Some(timecode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{h264::{descriptor_reader::DescriptorReader, pic_timing::PicTiming, sps_nalu::SpsNalu, timecode::Timecode}, test_util::{BASELINE_SPS, to_bytes}};
+
+    use super::TimecodeDecoder;
+
+    #[test]
+    fn decode_pic_timing_without_hrd() {
+        let sps = SpsNalu::read(&mut Cursor::new(BASELINE_SPS), BASELINE_SPS.len() as u32, 0x67).unwrap();
+        let vui_parameters = sps.vui_parameters.as_ref().unwrap();
+        assert!(vui_parameters.pic_struct_present_flag);
+        assert!(vui_parameters.nal_hrd_parameters.is_none() && vui_parameters.vcl_hrd_parameters.is_none());
+
+        // pic_struct 0 and one clock timestamp: counting_type 4, full_timestamp_flag, n_frames 12,
+        // 01:56:34, then a 24-bit time_offset of -2
+        let rbsp = to_bytes("0000 1 00 0 00100 1 0 1 00001100 100010 111000 00001 111111111111111111111110");
+        let pic_timing = PicTiming::read(&mut DescriptorReader::from_rbsp(rbsp), &sps).unwrap();
+        let clock_timestamp = pic_timing.clock_timestamps[0].as_ref().unwrap();
+        assert_eq!(clock_timestamp.time_offset, -2);
+
+        let timecode = TimecodeDecoder::new().decode(&pic_timing).unwrap();
+        assert_eq!(timecode, Timecode {
+            hours: 1,
+            minutes: 56,
+            seconds: 34,
+            frames: 12,
+            drop_frame: true
+        });
+        assert_eq!(timecode.to_string(), "01:56:34;12");
+    }
+}
@@ -1,4 +1,6 @@
-use std::{any::Any, fmt, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use crate::error::{Error, Result};
 
 use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, nalu::Nalu, opaque_data::OpaqueData, sps_pps_provider::SpsPpsProvider};
 
@@ -9,10 +11,15 @@ pub struct UnknownNalu {
 }
 
 impl UnknownNalu {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u32, nal_unit_type: u8) -> io::Result<Self> {
-        let mut descriptor_reader = DescriptorReader::new(rdr, len);
-        let remaining = descriptor_reader.read_to_end();
-        descriptor_reader.read_rbsp_trailing_bits();
+    pub fn read(rdr: &mut (impl Read + Seek), len: u32, nal_unit_type: u8) -> Result<Self> {
+        let mut descriptor_reader = DescriptorReader::new(rdr, len)?;
+        if !UnknownNalu::has_rbsp_trailing_bits(nal_unit_type) && len > 0 {
+            return Err(Error::spec_violation(format!("NAL unit type {} with a payload of {} bytes", nal_unit_type, len)));
+        }
+        let remaining = descriptor_reader.read_to_end()?;
+        if UnknownNalu::has_rbsp_trailing_bits(nal_unit_type) {
+            descriptor_reader.read_rbsp_trailing_bits()?;
+        }
 
         Ok(UnknownNalu {
             nal_unit_type,
@@ -20,14 +27,25 @@ impl UnknownNalu {
             payload_size: len
         })
     }
+
+    // end_of_seq_rbsp and end_of_stream_rbsp are empty
+    fn has_rbsp_trailing_bits(nal_unit_type: u8) -> bool {
+        !matches!(nal_unit_type, 10 | 11)
+    }
 }
 
 impl Nalu for UnknownNalu {
-    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) {
+    fn write(&self, wtr: &mut dyn Write, _sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
         let mut descriptor_writer = DescriptorWriter::new(wtr);
         descriptor_writer.append_all(&self.remaining);
-        descriptor_writer.append_rbsp_trailing_bits();
-        descriptor_writer.write_with_header(self.nal_unit_type);
+        if UnknownNalu::has_rbsp_trailing_bits(self.nal_unit_type) {
+            descriptor_writer.append_rbsp_trailing_bits();
+        }
+        descriptor_writer.write_with_header(self.nal_unit_type)
+    }
+
+    fn get_nal_unit_type(&self) -> u8 {
+        self.nal_unit_type
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -0,0 +1,47 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// user_data_registered_itu_t_t35 SEI message (D.1.6); itu_t_t35_country_code_extension_byte
+// is present only when itu_t_t35_country_code is 0xFF
+#[derive(Debug, Clone)]
+pub struct UserDataRegisteredItuTT35 {
+    pub itu_t_t35_country_code: u8,
+    pub itu_t_t35_country_code_extension_byte: u8,
+    pub itu_t_t35_payload_byte: Vec<u8>
+}
+
+impl UserDataRegisteredItuTT35 {
+    pub fn read(descriptor_reader: &mut DescriptorReader, payload_size: usize) -> Result<Self> {
+        if payload_size == 0 {
+            return Err(Error::spec_violation("empty user_data_registered_itu_t_t35"));
+        }
+        let itu_t_t35_country_code = descriptor_reader.read_u8()?;
+        let mut i = 1;
+        let mut itu_t_t35_country_code_extension_byte = 0;
+        if itu_t_t35_country_code == 0xFF {
+            itu_t_t35_country_code_extension_byte = descriptor_reader.read_u8()?;
+            i += 1;
+        }
+        let mut itu_t_t35_payload_byte = vec![];
+        while i < payload_size {
+            itu_t_t35_payload_byte.push(descriptor_reader.read_u8()?);
+            i += 1;
+        }
+        Ok(UserDataRegisteredItuTT35 {
+            itu_t_t35_country_code,
+            itu_t_t35_country_code_extension_byte,
+            itu_t_t35_payload_byte
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        descriptor_writer.append_u8(self.itu_t_t35_country_code);
+        if self.itu_t_t35_country_code == 0xFF {
+            descriptor_writer.append_u8(self.itu_t_t35_country_code_extension_byte);
+        }
+        for &byte in &self.itu_t_t35_payload_byte {
+            descriptor_writer.append_u8(byte);
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::error::{Error, Result};
+
+use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter};
+
+// user_data_unregistered SEI message (D.1.7)
+#[derive(Debug, Clone)]
+pub struct UserDataUnregistered {
+    pub uuid_iso_iec_11578: [u8; 16],
+    pub user_data_payload_byte: Vec<u8>
+}
+
+impl UserDataUnregistered {
+    pub fn read(descriptor_reader: &mut DescriptorReader, payload_size: usize) -> Result<Self> {
+        if payload_size < 16 {
+            return Err(Error::spec_violation(format!("user_data_unregistered of {} bytes has no room for the UUID", payload_size)));
+        }
+        let mut uuid_iso_iec_11578 = [0; 16];
+        for byte in &mut uuid_iso_iec_11578 {
+            *byte = descriptor_reader.read_u8()?;
+        }
+        let mut user_data_payload_byte = vec![];
+        for _i in 16..payload_size {
+            user_data_payload_byte.push(descriptor_reader.read_u8()?);
+        }
+        Ok(UserDataUnregistered {
+            uuid_iso_iec_11578,
+            user_data_payload_byte
+        })
+    }
+
+    pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
+        for &byte in self.uuid_iso_iec_11578.iter().chain(&self.user_data_payload_byte) {
+            descriptor_writer.append_u8(byte);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::error::Result;
+
 use super::{descriptor_reader::DescriptorReader, descriptor_writer::DescriptorWriter, hrd_parameters::HrdParameters};
 
 #[derive(Debug, Clone)]
@@ -18,6 +20,8 @@ pub struct VuiParameters {
     pub transfer_characteristics: u8,
     pub matrix_coefficients: u8,
     pub chroma_loc_info_present_flag: bool,
+    pub chroma_sample_loc_type_top_field: u64,
+    pub chroma_sample_loc_type_bottom_field: u64,
     pub timing_info_present_flag: bool,
     pub num_units_in_tick: Option<u32>,
     pub time_scale: Option<u32>,
@@ -39,24 +43,24 @@ pub struct VuiParameters {
 }
 
 impl VuiParameters {
-    pub fn read(descriptor_reader: &mut DescriptorReader) -> Self {
-        let aspect_ratio_info_present_flag = descriptor_reader.read_u1();
+    pub fn read(descriptor_reader: &mut DescriptorReader) -> Result<Self> {
+        let aspect_ratio_info_present_flag = descriptor_reader.read_u1()?;
         let mut aspect_ratio_idc = 0;
         let mut sar_width = 0;
         let mut sar_height = 0;
         if aspect_ratio_info_present_flag {
-            aspect_ratio_idc = descriptor_reader.read_u8();
+            aspect_ratio_idc = descriptor_reader.read_u8()?;
             if aspect_ratio_idc == 255 {    // Extended_SAR
-                sar_width = descriptor_reader.read_u16();
-                sar_height = descriptor_reader.read_u16();
+                sar_width = descriptor_reader.read_u16()?;
+                sar_height = descriptor_reader.read_u16()?;
             }
         }
-        let overscan_info_present_flag = descriptor_reader.read_u1();
+        let overscan_info_present_flag = descriptor_reader.read_u1()?;
         let mut overscan_appropriate_flag = false;
         if overscan_info_present_flag {
-            overscan_appropriate_flag = descriptor_reader.read_u1();
+            overscan_appropriate_flag = descriptor_reader.read_u1()?;
         }
-        let video_signal_type_present_flag = descriptor_reader.read_u1();
+        let video_signal_type_present_flag = descriptor_reader.read_u1()?;
         let mut video_format = 0;
         let mut video_full_range_flag = false;
         let mut colour_description_present_flag = false;
@@ -64,44 +68,47 @@ impl VuiParameters {
         let mut transfer_characteristics = 0;
         let mut matrix_coefficients = 0;
         if video_signal_type_present_flag {
-            video_format = descriptor_reader.read_u(3);
-            video_full_range_flag = descriptor_reader.read_u1();
-            colour_description_present_flag = descriptor_reader.read_u1();
+            video_format = descriptor_reader.read_u(3)?;
+            video_full_range_flag = descriptor_reader.read_u1()?;
+            colour_description_present_flag = descriptor_reader.read_u1()?;
             if colour_description_present_flag {
-                colour_primaries = descriptor_reader.read_u8();
-                transfer_characteristics = descriptor_reader.read_u8();
-                matrix_coefficients = descriptor_reader.read_u8();
+                colour_primaries = descriptor_reader.read_u8()?;
+                transfer_characteristics = descriptor_reader.read_u8()?;
+                matrix_coefficients = descriptor_reader.read_u8()?;
             }
         }
-        let chroma_loc_info_present_flag = descriptor_reader.read_u1();
+        let chroma_loc_info_present_flag = descriptor_reader.read_u1()?;
+        let mut chroma_sample_loc_type_top_field = 0;
+        let mut chroma_sample_loc_type_bottom_field = 0;
         if chroma_loc_info_present_flag {
-            todo!()
+            chroma_sample_loc_type_top_field = descriptor_reader.read_ue_v()?;
+            chroma_sample_loc_type_bottom_field = descriptor_reader.read_ue_v()?;
         }
-        let timing_info_present_flag = descriptor_reader.read_u1();
+        let timing_info_present_flag = descriptor_reader.read_u1()?;
         let mut num_units_in_tick = Option::None;
         let mut time_scale = Option::None;
         let mut fixed_frame_rate_flag = Option::None;
         if timing_info_present_flag {
-            num_units_in_tick = Option::Some(descriptor_reader.read_u32());
-            time_scale = Option::Some(descriptor_reader.read_u32());
-            fixed_frame_rate_flag = Option::Some(descriptor_reader.read_u1());
+            num_units_in_tick = Option::Some(descriptor_reader.read_u32()?);
+            time_scale = Option::Some(descriptor_reader.read_u32()?);
+            fixed_frame_rate_flag = Option::Some(descriptor_reader.read_u1()?);
         }
-        let nal_hrd_parameters_present_flag = descriptor_reader.read_u1();
+        let nal_hrd_parameters_present_flag = descriptor_reader.read_u1()?;
         let mut nal_hrd_parameters = None;
         if nal_hrd_parameters_present_flag {
-            nal_hrd_parameters = Some(HrdParameters::read(descriptor_reader));
+            nal_hrd_parameters = Some(HrdParameters::read(descriptor_reader)?);
         }
-        let vcl_hrd_parameters_present_flag = descriptor_reader.read_u1();
+        let vcl_hrd_parameters_present_flag = descriptor_reader.read_u1()?;
         let mut vcl_hrd_parameters = None;
         if vcl_hrd_parameters_present_flag{
-            vcl_hrd_parameters = Some(HrdParameters::read(descriptor_reader));
+            vcl_hrd_parameters = Some(HrdParameters::read(descriptor_reader)?);
         }
         let mut low_delay_hrd_flag = false;
         if nal_hrd_parameters_present_flag || vcl_hrd_parameters_present_flag {
-            low_delay_hrd_flag = descriptor_reader.read_u1();
+            low_delay_hrd_flag = descriptor_reader.read_u1()?;
         }
-        let pic_struct_present_flag = descriptor_reader.read_u1();
-        let bitstream_restriction_flag = descriptor_reader.read_u1();
+        let pic_struct_present_flag = descriptor_reader.read_u1()?;
+        let bitstream_restriction_flag = descriptor_reader.read_u1()?;
         let mut motion_vectors_over_pic_boundaries_flag = false;
         let mut max_bytes_per_pic_denom = 0;
         let mut max_bits_per_mb_denom = 0;
@@ -110,16 +117,16 @@ impl VuiParameters {
         let mut max_num_reorder_frames = 0;
         let mut max_dec_frame_buffering = 0;
         if bitstream_restriction_flag {
-            motion_vectors_over_pic_boundaries_flag = descriptor_reader.read_u1();
-            max_bytes_per_pic_denom = descriptor_reader.read_ue_v();
-            max_bits_per_mb_denom = descriptor_reader.read_ue_v();
-            log2_max_mv_length_horizontal = descriptor_reader.read_ue_v();
-            log2_max_mv_length_vertical = descriptor_reader.read_ue_v();
-            max_num_reorder_frames = descriptor_reader.read_ue_v();
-            max_dec_frame_buffering = descriptor_reader.read_ue_v();
+            motion_vectors_over_pic_boundaries_flag = descriptor_reader.read_u1()?;
+            max_bytes_per_pic_denom = descriptor_reader.read_ue_v()?;
+            max_bits_per_mb_denom = descriptor_reader.read_ue_v()?;
+            log2_max_mv_length_horizontal = descriptor_reader.read_ue_v()?;
+            log2_max_mv_length_vertical = descriptor_reader.read_ue_v()?;
+            max_num_reorder_frames = descriptor_reader.read_ue_v()?;
+            max_dec_frame_buffering = descriptor_reader.read_ue_v()?;
         }
 
-        VuiParameters {
+        Ok(VuiParameters {
             aspect_ratio_info_present_flag,
             aspect_ratio_idc,
             sar_width,
@@ -134,6 +141,8 @@ impl VuiParameters {
             transfer_characteristics,
             matrix_coefficients,
             chroma_loc_info_present_flag,
+            chroma_sample_loc_type_top_field,
+            chroma_sample_loc_type_bottom_field,
             timing_info_present_flag,
             num_units_in_tick,
             time_scale,
@@ -152,7 +161,7 @@ impl VuiParameters {
             log2_max_mv_length_vertical,
             max_num_reorder_frames,
             max_dec_frame_buffering
-        }
+        })
     }
 
     pub fn write(&self, descriptor_writer: &mut DescriptorWriter) {
@@ -184,7 +193,8 @@ impl VuiParameters {
 
         descriptor_writer.append_u1(self.chroma_loc_info_present_flag);
         if self.chroma_loc_info_present_flag {
-            todo!()
+            descriptor_writer.append_ue_v(self.chroma_sample_loc_type_top_field);
+            descriptor_writer.append_ue_v(self.chroma_sample_loc_type_bottom_field);
         }
 
         descriptor_writer.append_u1(self.timing_info_present_flag);
@@ -0,0 +1,9 @@
+pub mod error;
+pub mod mp4;
+pub mod h264;
+pub mod caption;
+#[cfg(test)]
+mod test_util;
+
+pub use error::{Error, ErrorKind, Result};
+pub use mp4::mp4_file::Mp4File;
@@ -1,102 +1,62 @@
-mod mp4;
-mod h264;
+use std::{env, path::Path, process};
 
-use std::{collections::HashMap, fs::File};
+use video_parse::{caption::{caption_source::CaptionSource, subtitle_format::SubtitleFormat}, h264::hrd_type::HrdType, mp4::parameter_set_placement::ParameterSetPlacement, Error, Mp4File, Result};
 
-use h264::{pps_nalu::PpsNalu, sps_nalu::SpsNalu};
-use mp4::{box_list::BoxList, mdat_box::MdatBox};
-
-use crate::h264::{idr_nalu::IdrNalu, non_idr_nalu::NonIdrNalu};
-
-fn get_pic_order_cnt(pic_order_cnt_lsb: u32, last_pic_order_cnt: u32, pic_order_cnt_bits: u8) -> u32 {
-    let candidate0: u32;
-    if last_pic_order_cnt >> 5 >= 1 {
-        candidate0 = (((last_pic_order_cnt >> pic_order_cnt_bits) - 1) << pic_order_cnt_bits) | pic_order_cnt_lsb;
-    } else {
-        candidate0 = u32::MAX;
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: {} <input.mp4> [output.mp4|output.h264|output.srt|output.vtt]", args[0]);
+        process::exit(2);
     }
-    let candidate1 = ((last_pic_order_cnt >> pic_order_cnt_bits) << pic_order_cnt_bits) | pic_order_cnt_lsb;
-    let candidate2 = (((last_pic_order_cnt >> pic_order_cnt_bits) + 1) << pic_order_cnt_bits) | pic_order_cnt_lsb;
-    let diff0 = (candidate0 as i64 - last_pic_order_cnt as i64).abs();
-    let diff1 = (candidate1 as i64 - last_pic_order_cnt as i64).abs();
-    let diff2 = (candidate2 as i64 - last_pic_order_cnt as i64).abs();
-    if diff0 <= diff1 && diff0 <= diff2 {
-        candidate0
-    } else if diff1 <= diff0 && diff1 <= diff2 {
-        candidate1
-    } else {
-        candidate2
+    if let Err(e) = run(&args[1], args.get(2)) {
+        eprintln!("{}", e);
+        process::exit(1);
     }
 }
 
-fn build_pps_map(box_list: &BoxList) -> HashMap<u64, PpsNalu> {
-    let mut map = HashMap::new();
-    for atom in &box_list.boxes {
-        if let Some(mdat) = atom.as_any().downcast_ref::<MdatBox>() {
-            for nalu in &mdat.nalu_list.units {
-                if let Some(pps) = nalu.as_any().downcast_ref::<PpsNalu>() {
-                    map.entry(pps.seq_parameter_set_id).or_insert(pps.clone());
-                }
-            }
+fn run(input: &str, output: Option<&String>) -> Result<()> {
+    let mut mp4_file = Mp4File::open(input)?;
+
+    for (track_index, trak) in mp4_file.tracks().enumerate() {
+        for (sample_index, sample_time) in trak.get_sample_times()?.iter().enumerate() {
+            println!("track #{} sample #{}: dts={} pts={}", track_index, sample_index, sample_time.dts, sample_time.pts);
         }
     }
-    map
-}
 
-fn build_sps_map(box_list: &BoxList) -> HashMap<u64, SpsNalu> {
-    let mut map = HashMap::new();
-    for atom in &box_list.boxes {
-        if let Some(mdat) = atom.as_any().downcast_ref::<MdatBox>() {
-            for nalu in &mdat.nalu_list.units {
-                if let Some(sps) = nalu.as_any().downcast_ref::<SpsNalu>() {
-                    map.entry(sps.seq_parameter_set_id).or_insert(sps.clone());
-                }
+    let h264_track_indices: Vec<usize> = mp4_file.tracks().enumerate()
+        .filter(|(_, trak)| trak.get_avc_configurations().iter().any(Option::is_some))
+        .map(|(track_index, _)| track_index)
+        .collect();
+    for track_index in h264_track_indices {
+        for (sample_index, timecode) in mp4_file.get_timecodes(track_index)?.iter().enumerate() {
+            if let Some(timecode) = timecode {
+                println!("track #{} sample #{}: timecode {}", track_index, sample_index, timecode);
             }
         }
-    }
-    map
-}
-
-fn main() {
-    let mut in_file = File::open("video.mp4").unwrap();
-    let mut box_list = mp4::box_list::BoxList::read(&mut in_file, 0);
-    
-    let sps_map = build_sps_map(&box_list);
-    let pps_map = build_pps_map(&box_list);
-    let mut pic_order_cnt_bits: Option<u8> = None;
-    let mut pic_order_cnt = 0;
-    let mut frame_num = 0;
-    for atom in &mut box_list.boxes {
-        if let Some(mdat) = atom.as_any_mut().downcast_mut::<MdatBox>() {
-            let nalu_list = &mut mdat.nalu_list;
-            for nalu in &mut nalu_list.units {
-                if let Some(sps) = nalu.as_any_mut().downcast_mut::<SpsNalu>() {
-                    println!("{:#?}", sps);
-
-                    // sps.vui_parameters.as_mut().unwrap().max_dec_frame_buffering = 4;
-                } else if let Some(idr) = nalu.as_any_mut().downcast_mut::<IdrNalu>() {
-                    println!("{:#?}", idr);
-
-                    let pps = pps_map.get(&idr.slice_header.pic_parameter_set_id).unwrap();
-                    let sps = sps_map.get(&pps.seq_parameter_set_id).unwrap();
-                    pic_order_cnt_bits = Some(u8::try_from(sps.log2_max_pic_order_cnt_lsb_minus4 + 4).unwrap());
-                    pic_order_cnt = get_pic_order_cnt(idr.slice_header.pic_order_cnt_lsb.try_into().unwrap(), pic_order_cnt, pic_order_cnt_bits.unwrap());
-                    frame_num = 0;
-                    // idr.slice_header.frame_num = frame_num;
-                } else if let Some(non_idr) = nalu.as_any_mut().downcast_mut::<NonIdrNalu>() {
-                    println!("{:#?}", non_idr);
-
-                    pic_order_cnt = get_pic_order_cnt(non_idr.slice_header.pic_order_cnt_lsb.try_into().unwrap(), pic_order_cnt, pic_order_cnt_bits.unwrap());
-                    if non_idr.slice_header.slice_type == 5 {
-                        frame_num += 1;
-                    }
-                    // non_idr.slice_header.frame_num = frame_num;
+        match mp4_file.verify_hrd(track_index, HrdType::Nal, 0) {
+            Ok(report) => {
+                for (sample_index, frame) in report.frames.iter().enumerate() {
+                    println!("track #{} sample #{}: CPB {} -> {} bits", track_index, sample_index, frame.fullness_before_removal, frame.fullness_after_removal);
                 }
-            }
+                for violation in &report.violations {
+                    println!("track #{}: {:?}", track_index, violation);
+                }
+            },
+            Err(e) => println!("track #{}: no NAL HRD verification: {}", track_index, e)
         }
     }
-    // println!("{:#?}", box_list);
 
-    let mut out_file = File::create("clone.mp4").unwrap();
-    box_list.write(&mut out_file);
+    if let Some(output) = output {
+        // first track with an avcC sample entry
+        let h264_track_index = || mp4_file.tracks()
+            .position(|trak| trak.get_avc_configurations().iter().any(Option::is_some))
+            .ok_or_else(|| Error::unsupported("no H.264 track"));
+        match Path::new(output).extension().and_then(|extension| extension.to_str()) {
+            Some("h264") => mp4_file.save_annex_b(h264_track_index()?, output, ParameterSetPlacement::EveryIdr)?,
+            Some("srt") => mp4_file.save_captions(h264_track_index()?, CaptionSource::Cea608Field1, output, SubtitleFormat::Srt)?,
+            Some("vtt") => mp4_file.save_captions(h264_track_index()?, CaptionSource::Cea608Field1, output, SubtitleFormat::WebVtt)?,
+            _ => mp4_file.save(output)?
+        }
+    }
+    Ok(())
 }
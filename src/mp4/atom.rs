@@ -1,8 +1,17 @@
-use std::{any::Any, fmt, fs::File};
+use std::{any::Any, fmt, io::Write};
+
+use crate::error::Result;
+
+use super::box_header::BoxHeader;
 
 pub trait Atom: fmt::Debug {
-    fn get_payload_size(&self) -> u64;
-    fn write(&self, wtr: &mut File);
+    fn get_header(&self) -> &BoxHeader;
+    fn get_payload_size(&self) -> Result<u64>;
+    fn get_size(&self) -> Result<u64> {
+        let payload_size = self.get_payload_size()?;
+        Ok(self.get_header().get_size(payload_size) + payload_size)
+    }
+    fn write(&self, wtr: &mut dyn Write) -> Result<()>;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
@@ -1,28 +1,35 @@
-use std::{any::Any, fmt, fs::File, io::{self, Read, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use super::{atom::Atom, box_list::BoxList};
+use crate::error::{Error, Result};
+
+use super::{atom::Atom, box_header::BoxHeader, box_list::BoxList};
 
 pub struct Avc1Box {
+    pub header: BoxHeader,
     pub data_reference_index: u16,
     pub visual_sample_entry_reserved: u16,
     pub width: u16,
     pub height: u16,
+    pub horizresolution: u32,       // 16.16 fixed point pixels per inch
+    pub vertresolution: u32,        // 16.16 fixed point pixels per inch
+    pub frame_count: u16,
     pub compressorname: [u8; 32],
-    pub box_list: BoxList,
-    pub payload_size: u64
+    pub depth: u16,
+    pub box_list: BoxList
 }
 
 impl Avc1Box {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let box_list_len = len.checked_sub(78).ok_or_else(|| Error::spec_violation(format!("avc1 payload of {} bytes", len)))?;
         let mut _reserved: [u8; 6] = [0; 6];
-        rdr.read(&mut _reserved).unwrap();
+        rdr.read_exact(&mut _reserved)?;
 
-        let data_reference_index = rdr.read_u16::<BigEndian>().unwrap();
+        let data_reference_index = rdr.read_u16::<BigEndian>()?;
 
-        let _pre_defined = rdr.read_u16::<BigEndian>();
-        let visual_sample_entry_reserved = rdr.read_u16::<BigEndian>().unwrap();
+        let _pre_defined = rdr.read_u16::<BigEndian>()?;
+        let visual_sample_entry_reserved = rdr.read_u16::<BigEndian>()?;
         for _i in 0..3 {
             rdr.read_u32::<BigEndian>()?;
         }
@@ -30,64 +37,70 @@ impl Avc1Box {
         let width = rdr.read_u16::<BigEndian>()?;
         let height = rdr.read_u16::<BigEndian>()?;
 
-        let _horizresolution = rdr.read_u32::<BigEndian>()?;
-        let _vertresolution = rdr.read_u32::<BigEndian>()?;
+        let horizresolution = rdr.read_u32::<BigEndian>()?;
+        let vertresolution = rdr.read_u32::<BigEndian>()?;
 
         let _reserved2 = rdr.read_u32::<BigEndian>()?;
 
-        let _frame_count = rdr.read_u16::<BigEndian>();
+        let frame_count = rdr.read_u16::<BigEndian>()?;
 
         let mut compressorname: [u8; 32] = [0; 32];
-        rdr.read(&mut compressorname).unwrap();
+        rdr.read_exact(&mut compressorname)?;
 
-        let _depth = rdr.read_u16::<BigEndian>();
+        let depth = rdr.read_u16::<BigEndian>()?;
 
-        let _pre_defined2 = rdr.read_i16::<BigEndian>();
+        let _pre_defined2 = rdr.read_i16::<BigEndian>()?;
 
-        let box_list = BoxList::read(rdr, len - 78);
+        let box_list = BoxList::read(rdr, box_list_len)?;
         Ok(Avc1Box {
+            header,
             data_reference_index,
             visual_sample_entry_reserved,
             width,
             height,
+            horizresolution,
+            vertresolution,
+            frame_count,
             compressorname,
-            box_list,
-            payload_size: len
+            depth,
+            box_list
         })
     }
 }
 
 impl Atom for Avc1Box {
-    fn get_payload_size(&self) -> u64 {
-        78 + self.box_list.get_size()
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(78 + self.box_list.get_size()?)
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"avc1").unwrap();
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
 
         let reserved: [u8; 6] = [0; 6];
-        wtr.write_all(&reserved).unwrap();
-        wtr.write_u16::<BigEndian>(self.data_reference_index).unwrap();
-        wtr.write_u16::<BigEndian>(0).unwrap();             // pre_defined
-        wtr.write_u16::<BigEndian>(self.visual_sample_entry_reserved).unwrap();
+        wtr.write_all(&reserved)?;
+        wtr.write_u16::<BigEndian>(self.data_reference_index)?;
+        wtr.write_u16::<BigEndian>(0)?;             // pre_defined
+        wtr.write_u16::<BigEndian>(self.visual_sample_entry_reserved)?;
 
         for _i in 0..3 {
-            wtr.write_u32::<BigEndian>(0).unwrap();
+            wtr.write_u32::<BigEndian>(0)?;
         }
 
-        wtr.write_u16::<BigEndian>(self.width).unwrap();
-        wtr.write_u16::<BigEndian>(self.height).unwrap();
-        wtr.write_u32::<BigEndian>(0x00480000).unwrap();    // horizresolution
-        wtr.write_u32::<BigEndian>(0x00480000).unwrap();    // vertresolution
-        wtr.write_u32::<BigEndian>(0).unwrap();             // reserved
-        wtr.write_u16::<BigEndian>(1).unwrap();             // frame_count
-        wtr.write_all(&self.compressorname).unwrap();
-        wtr.write_u16::<BigEndian>(0x0018).unwrap();        // depth
-        wtr.write_i16::<BigEndian>(-1).unwrap();            // pre_defined
-
-        self.box_list.write(wtr);
+        wtr.write_u16::<BigEndian>(self.width)?;
+        wtr.write_u16::<BigEndian>(self.height)?;
+        wtr.write_u32::<BigEndian>(self.horizresolution)?;
+        wtr.write_u32::<BigEndian>(self.vertresolution)?;
+        wtr.write_u32::<BigEndian>(0)?;             // reserved
+        wtr.write_u16::<BigEndian>(self.frame_count)?;
+        wtr.write_all(&self.compressorname)?;
+        wtr.write_u16::<BigEndian>(self.depth)?;
+        wtr.write_i16::<BigEndian>(-1)?;            // pre_defined
+
+        self.box_list.write(wtr)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -106,7 +119,9 @@ impl fmt::Debug for Avc1Box {
             .field("visual_sample_entry_reserved", &self.visual_sample_entry_reserved)
             .field("width", &self.width)
             .field("height", &self.height)
+            .field("frame_count", &self.frame_count)
             .field("compressorname", &self.compressorname)
+            .field("depth", &self.depth)
             .field("box_list", &self.box_list)
             .finish()
     }
@@ -1,7 +1,7 @@
 use std::{fmt, io::{Cursor, Read, Write}, vec};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::h264::{nalu::Nalu, pps_nalu::PpsNalu, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider};
+use crate::{error::{Error, Result}, h264::{nalu::Nalu, parameter_sets::ParameterSets, pps_nalu::PpsNalu, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider}};
 
 pub struct AvcDecoderConfigurationRecord {
     pub configuration_version: u8,
@@ -10,78 +10,135 @@ pub struct AvcDecoderConfigurationRecord {
     pub avc_level_indication: u8,
     pub length_size_minus_one: u8,
     pub sequence_parameter_set_nal_units: Vec<SpsNalu>,
-    pub picture_parameter_set_nal_units: Vec<PpsNalu>
+    pub picture_parameter_set_nal_units: Vec<PpsNalu>,
+    pub extension_present: bool,    // whether the fields below are stored, as profiles 100, 110, 122 and 144 require
+    pub chroma_format: u8,          // from the first SPS when not stored
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    pub sequence_parameter_set_ext_nal_units: Vec<Vec<u8>>
 }
 
 impl AvcDecoderConfigurationRecord {
-    pub fn read(rdr: &mut impl Read) -> (Self, u32) {
+    pub fn read(rdr: &mut impl Read, len: u64) -> Result<(Self, u32)> {
         let mut total_size: u32 = 7;
-        let configuration_version = rdr.read_u8().unwrap();
-        let avc_profile_indication = rdr.read_u8().unwrap();
-        let profile_compatibility = rdr.read_u8().unwrap();
-        let avc_level_indication = rdr.read_u8().unwrap();
-        let length_size_minus_one = rdr.read_u8().unwrap() & 0b00000011;
-        let num_of_sequence_parameter_sets = rdr.read_u8().unwrap() & 0b00011111;
+        let configuration_version = rdr.read_u8()?;
+        let avc_profile_indication = rdr.read_u8()?;
+        let profile_compatibility = rdr.read_u8()?;
+        let avc_level_indication = rdr.read_u8()?;
+        let length_size_minus_one = rdr.read_u8()? & 0b00000011;
+        let num_of_sequence_parameter_sets = rdr.read_u8()? & 0b00011111;
         let mut sequence_parameter_set_nal_units = vec![];
         for _i in 0..num_of_sequence_parameter_sets {
-            let sequence_parameter_set_length = rdr.read_u16::<BigEndian>().unwrap();
-            let _nalu_header = rdr.read_u8().unwrap();
-            let sps_unit = SpsNalu::read(rdr, (sequence_parameter_set_length - 1).into()).unwrap();
+            let sequence_parameter_set_length = rdr.read_u16::<BigEndian>()?;
+            if sequence_parameter_set_length == 0 {
+                return Err(Error::spec_violation("empty sequenceParameterSetNALUnit"));
+            }
+            let nalu_header = rdr.read_u8()?;
+            let sps_unit = SpsNalu::read(rdr, (sequence_parameter_set_length - 1).into(), nalu_header)?;
             sequence_parameter_set_nal_units.push(sps_unit);
             total_size += 2 + u32::from(sequence_parameter_set_length)
         }
-        let num_of_picture_parameter_sets = rdr.read_u8().unwrap();
+        let num_of_picture_parameter_sets = rdr.read_u8()?;
         let mut picture_parameter_set_nal_units = vec![];
+        let mut parameter_sets = ParameterSets::new();
+        for sequence_parameter_set_nal_unit in &sequence_parameter_set_nal_units {
+            parameter_sets.insert_sps(sequence_parameter_set_nal_unit.clone());
+        }
         for _i in 0..num_of_picture_parameter_sets {
-            let picture_parameter_set_length = rdr.read_u16::<BigEndian>().unwrap();
-            let _nalu_header = rdr.read_u8().unwrap();
-            let pps_unit = PpsNalu::read(rdr, (picture_parameter_set_length - 1).into()).unwrap();
+            let picture_parameter_set_length = rdr.read_u16::<BigEndian>()?;
+            if picture_parameter_set_length == 0 {
+                return Err(Error::spec_violation("empty pictureParameterSetNALUnit"));
+            }
+            let nalu_header = rdr.read_u8()?;
+            let pps_unit = PpsNalu::read(rdr, (picture_parameter_set_length - 1).into(), nalu_header, &parameter_sets)?;
             picture_parameter_set_nal_units.push(pps_unit);
             total_size += 2 + u32::from(picture_parameter_set_length)
         }
-        match avc_profile_indication {
-            100 | 110 | 122 | 144 => {
-                todo!()
+        let mut extension_present = false;
+        let (mut chroma_format, mut bit_depth_luma_minus8, mut bit_depth_chroma_minus8) = match sequence_parameter_set_nal_units.first() {
+            Some(sps) => (u8::try_from(sps.chroma_format_idc)?, u8::try_from(sps.bit_depth_luma_minus8)?, u8::try_from(sps.bit_depth_chroma_minus8)?),
+            None => (1, 0, 0)
+        };
+        let mut sequence_parameter_set_ext_nal_units = vec![];
+        // many files leave these fields out even for the profiles that require them
+        if matches!(avc_profile_indication, 100 | 110 | 122 | 144) && len >= u64::from(total_size) + 4 {
+            extension_present = true;
+            chroma_format = rdr.read_u8()? & 0b00000011;
+            bit_depth_luma_minus8 = rdr.read_u8()? & 0b00000111;
+            bit_depth_chroma_minus8 = rdr.read_u8()? & 0b00000111;
+            let num_of_sequence_parameter_set_ext = rdr.read_u8()?;
+            total_size += 4;
+            for _i in 0..num_of_sequence_parameter_set_ext {
+                let sequence_parameter_set_ext_length = rdr.read_u16::<BigEndian>()?;
+                let mut sequence_parameter_set_ext_nal_unit = vec![0u8; sequence_parameter_set_ext_length.into()];
+                rdr.read_exact(&mut sequence_parameter_set_ext_nal_unit)?;
+                sequence_parameter_set_ext_nal_units.push(sequence_parameter_set_ext_nal_unit);
+                total_size += 2 + u32::from(sequence_parameter_set_ext_length);
             }
-            _ => {}
         }
         
-        (AvcDecoderConfigurationRecord {
+        Ok((AvcDecoderConfigurationRecord {
             configuration_version,
             avc_profile_indication,
             profile_compatibility,
             avc_level_indication,
             length_size_minus_one,
             sequence_parameter_set_nal_units,
-            picture_parameter_set_nal_units
-        }, total_size)
+            picture_parameter_set_nal_units,
+            extension_present,
+            chroma_format,
+            bit_depth_luma_minus8,
+            bit_depth_chroma_minus8,
+            sequence_parameter_set_ext_nal_units
+        }, total_size))
+    }
+
+    pub fn write(&self, wtr: &mut impl Write) -> Result<()> {
+        wtr.write_u8(self.configuration_version)?;
+        wtr.write_u8(self.avc_profile_indication)?;
+        wtr.write_u8(self.profile_compatibility)?;
+        wtr.write_u8(self.avc_level_indication)?;
+        wtr.write_u8(0b11111100 | self.length_size_minus_one)?;
+        wtr.write_u8(0b11100000 | u8::try_from(self.sequence_parameter_set_nal_units.len())?)?;
+        for sequence_parameter_set_nal_unit in &self.sequence_parameter_set_nal_units {
+            let bytes = sequence_parameter_set_nal_unit.to_bytes(self)?;
+            wtr.write_u16::<BigEndian>(u16::try_from(bytes.len())?)?;
+            wtr.write_all(&bytes)?;
+        }
+        wtr.write_u8(self.picture_parameter_set_nal_units.len().try_into()?)?;
+        for picture_parameter_set_nal_unit in &self.picture_parameter_set_nal_units {
+            let bytes = picture_parameter_set_nal_unit.to_bytes(self)?;
+            wtr.write_u16::<BigEndian>(u16::try_from(bytes.len())?)?;
+            wtr.write_all(&bytes)?;
+        }
+        if self.extension_present {
+            wtr.write_u8(0b11111100 | self.chroma_format)?;
+            wtr.write_u8(0b11111000 | self.bit_depth_luma_minus8)?;
+            wtr.write_u8(0b11111000 | self.bit_depth_chroma_minus8)?;
+            wtr.write_u8(u8::try_from(self.sequence_parameter_set_ext_nal_units.len())?)?;
+            for sequence_parameter_set_ext_nal_unit in &self.sequence_parameter_set_ext_nal_units {
+                wtr.write_u16::<BigEndian>(u16::try_from(sequence_parameter_set_ext_nal_unit.len())?)?;
+                wtr.write_all(sequence_parameter_set_ext_nal_unit)?;
+            }
+        }
+        Ok(())
     }
 
-    pub fn write(&self, wtr: &mut impl Write) {
-        wtr.write_u8(self.configuration_version).unwrap();
-        wtr.write_u8(self.avc_profile_indication).unwrap();
-        wtr.write_u8(self.profile_compatibility).unwrap();
-        wtr.write_u8(self.avc_level_indication).unwrap();
-        wtr.write_u8(0b11111100 | self.length_size_minus_one).unwrap();
-        wtr.write_u8(0b11100000 | u8::try_from(self.sequence_parameter_set_nal_units.len()).unwrap()).unwrap();
+    pub fn get_parameter_sets(&self) -> ParameterSets {
+        let mut parameter_sets = ParameterSets::new();
         for sequence_parameter_set_nal_unit in &self.sequence_parameter_set_nal_units {
-            let bytes = sequence_parameter_set_nal_unit.to_bytes(self);
-            wtr.write_u16::<BigEndian>(u16::try_from(bytes.len()).unwrap()).unwrap();
-            wtr.write_all(&bytes).unwrap();
+            parameter_sets.insert_sps(sequence_parameter_set_nal_unit.clone());
         }
-        wtr.write_u8(self.picture_parameter_set_nal_units.len().try_into().unwrap()).unwrap();
         for picture_parameter_set_nal_unit in &self.picture_parameter_set_nal_units {
-            let bytes = picture_parameter_set_nal_unit.to_bytes(self);
-            wtr.write_u16::<BigEndian>(u16::try_from(bytes.len()).unwrap()).unwrap();
-            wtr.write_all(&bytes).unwrap();
+            parameter_sets.insert_pps(picture_parameter_set_nal_unit.clone());
         }
+        parameter_sets
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut cursor = Cursor::new(Vec::new());
-        self.write(&mut cursor);
-        let buffer = cursor.into_inner();
-        buffer
+        self.write(&mut cursor)?;
+        Ok(cursor.into_inner())
     }
 }
 
@@ -92,7 +149,7 @@ impl SpsPpsProvider for AvcDecoderConfigurationRecord {
                 return Some(nalu)
             }
         }
-        return None
+        None
     }
 
     fn get_sps(&self, id: u64) -> Option<&SpsNalu> {
@@ -101,7 +158,7 @@ impl SpsPpsProvider for AvcDecoderConfigurationRecord {
                 return Some(nalu)
             }
         }
-        return None
+        None
     }
 }
 
@@ -115,6 +172,59 @@ impl fmt::Debug for AvcDecoderConfigurationRecord {
             .field("length_size_minus_one", &self.length_size_minus_one)
             .field("sequence_parameter_set_nal_units", &"...")
             .field("picture_parameter_set_nal_units", &"...")
+            .field("extension_present", &self.extension_present)
+            .field("chroma_format", &self.chroma_format)
+            .field("bit_depth_luma_minus8", &self.bit_depth_luma_minus8)
+            .field("bit_depth_chroma_minus8", &self.bit_depth_chroma_minus8)
+            .field("sequence_parameter_set_ext_nal_units", &self.sequence_parameter_set_ext_nal_units.len())
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_bytes;
+
+    use super::AvcDecoderConfigurationRecord;
+
+    // High profile avcC with one SPS and PPS, followed by the given extension bytes
+    fn get_avcc(extension: &[u8]) -> Vec<u8> {
+        // 4:2:0 8-bit 320x240
+        let mut sps = vec![0x67];
+        sps.extend(to_bytes("01100100 00000000 00011110 1 010 1 1 0 0 1 011 010 0 000010100 0001111 1 1 0 0"));
+        // CABAC
+        let mut pps = vec![0x68];
+        pps.extend(to_bytes("1 1 1 0 1 1 1 0 00 1 1 1 1 0 0"));
+
+        let mut avcc = vec![1, 100, 0, 30, 0xff, 0xe1];
+        avcc.extend((sps.len() as u16).to_be_bytes());
+        avcc.extend(sps);
+        avcc.push(1);
+        avcc.extend((pps.len() as u16).to_be_bytes());
+        avcc.extend(pps);
+        avcc.extend(extension);
+        avcc
+    }
+
+    #[test]
+    fn round_trip_high_profile_extension() {
+        // chroma_format 1, bit depths 8 and one SPS extension unit
+        let avcc = get_avcc(&[0xfd, 0xf8, 0xf8, 1, 0, 2, 0x6d, 0x80]);
+        let (record, size) = AvcDecoderConfigurationRecord::read(&mut avcc.as_slice(), avcc.len() as u64).unwrap();
+        assert_eq!(size as usize, avcc.len());
+        assert!(record.extension_present);
+        assert_eq!((record.chroma_format, record.bit_depth_luma_minus8, record.bit_depth_chroma_minus8), (1, 0, 0));
+        assert_eq!(record.sequence_parameter_set_ext_nal_units, [vec![0x6d, 0x80]]);
+        assert_eq!(record.to_bytes().unwrap(), avcc);
+    }
+
+    #[test]
+    fn round_trip_high_profile_without_extension() {
+        let avcc = get_avcc(&[]);
+        let (record, size) = AvcDecoderConfigurationRecord::read(&mut avcc.as_slice(), avcc.len() as u64).unwrap();
+        assert_eq!(size as usize, avcc.len());
+        assert!(!record.extension_present);
+        assert_eq!(record.chroma_format, 1);
+        assert_eq!(record.to_bytes().unwrap(), avcc);
+    }
+}
@@ -1,43 +1,48 @@
-use std::{any::Any, fmt, fs::File, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::{Error, Result};
 
-use super::{atom::Atom, avc_decoder_configuration_record::AvcDecoderConfigurationRecord};
+use super::{atom::Atom, avc_decoder_configuration_record::AvcDecoderConfigurationRecord, box_header::BoxHeader};
 
 pub struct AvccBox {
+    pub header: BoxHeader,
     pub avc_decoder_configuration_record: AvcDecoderConfigurationRecord,
-    pub remaining: Vec<u8>,
-    pub payload_size: u64
+    pub remaining: Vec<u8>
 }
 
 impl AvccBox {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u64) -> io::Result<Self> {
-        let (avc_decoder_configuration_record, read_size) = AvcDecoderConfigurationRecord::read(rdr);
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let (avc_decoder_configuration_record, read_size) = AvcDecoderConfigurationRecord::read(rdr, len)?;
+        let remaining_len = len.checked_sub(read_size.into())
+            .ok_or_else(|| Error::spec_violation("AVCDecoderConfigurationRecord exceeds avcC box"))?;
 
-        let mut remaining = vec![0u8; (len - u64::from(read_size)).try_into().unwrap()];
-        rdr.read_exact(&mut remaining).unwrap();
+        let mut remaining = vec![0u8; remaining_len.try_into()?];
+        rdr.read_exact(&mut remaining)?;
 
         Ok(AvccBox {
+            header,
             avc_decoder_configuration_record,
-            remaining,
-            payload_size: len
+            remaining
         })
     }
 }
 
 impl Atom for AvccBox {
-    fn get_payload_size(&self) -> u64 {
-        let bytes = self.avc_decoder_configuration_record.to_bytes();
-        (bytes.len() + self.remaining.len()).try_into().unwrap()
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
 
-    fn write(&self, wtr: &mut File) {
-        let bytes = self.avc_decoder_configuration_record.to_bytes();
-        let total_size: usize = 8 + bytes.len() + self.remaining.len();
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"avcC").unwrap();
-        wtr.write_all(&bytes).unwrap();
-        wtr.write_all(&self.remaining).unwrap();
+    fn get_payload_size(&self) -> Result<u64> {
+        let bytes = self.avc_decoder_configuration_record.to_bytes()?;
+        Ok((bytes.len() + self.remaining.len()) as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        let bytes = self.avc_decoder_configuration_record.to_bytes()?;
+        self.header.write(wtr, (bytes.len() + self.remaining.len()) as u64)?;
+        wtr.write_all(&bytes)?;
+        wtr.write_all(&self.remaining)?;
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
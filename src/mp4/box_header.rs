@@ -0,0 +1,110 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Error, Result};
+
+use super::four_cc::FourCC;
+
+pub struct BoxHeader {
+    pub boxtype: FourCC,
+    pub usertype: Option<[u8; 16]>,     // extended type, only for boxtype 'uuid'
+    pub largesize: bool,                // size is stored in the 64-bit largesize field
+    pub extends_to_end: bool            // size 0, the box extends to the end of the file
+}
+
+impl BoxHeader {
+    pub fn new(boxtype: &[u8; 4]) -> Self {
+        BoxHeader {
+            boxtype: FourCC::new(*boxtype),
+            usertype: None,
+            largesize: false,
+            extends_to_end: false
+        }
+    }
+
+    // returns the header and the payload size, or None at the end of the stream
+    pub fn read(rdr: &mut (impl Read + Seek)) -> Result<Option<(Self, u64)>> {
+        let size = match rdr.read_u32::<BigEndian>() {
+            Ok(size) => size,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into())
+        };
+        let boxtype = FourCC::read(rdr)?;
+
+        let mut header_size = 8;
+        let total_size = match size {
+            0 => None,
+            1 => {
+                header_size += 8;
+                Some(rdr.read_u64::<BigEndian>()?)
+            },
+            size => Some(u64::from(size))
+        };
+
+        let mut usertype = None;
+        if boxtype == FourCC::new(*b"uuid") {
+            let mut extended_type = [0u8; 16];
+            rdr.read_exact(&mut extended_type)?;
+            usertype = Some(extended_type);
+            header_size += 16;
+        }
+
+        let payload_size = match total_size {
+            Some(total_size) => total_size.checked_sub(header_size)
+                .ok_or_else(|| Error::spec_violation(format!("box size {} smaller than its header", total_size)))?,
+            None => {
+                let payload_start = rdr.stream_position()?;
+                let end = rdr.seek(SeekFrom::End(0))?;
+                rdr.seek(SeekFrom::Start(payload_start))?;
+                end - payload_start
+            }
+        };
+
+        Ok(Some((BoxHeader {
+            boxtype,
+            usertype,
+            largesize: size == 1,
+            extends_to_end: size == 0
+        }, payload_size)))
+    }
+
+    pub fn get_size(&self, payload_size: u64) -> u64 {
+        let mut size = 8;
+        if self.uses_largesize(payload_size) {
+            size += 8;
+        }
+        if self.usertype.is_some() {
+            size += 16;
+        }
+        size
+    }
+
+    pub fn write(&self, wtr: &mut dyn Write, payload_size: u64) -> Result<()> {
+        let total_size = self.get_size(payload_size) + payload_size;
+        if self.extends_to_end {
+            wtr.write_u32::<BigEndian>(0)?;
+            self.boxtype.write(wtr)?;
+        } else if self.uses_largesize(payload_size) {
+            wtr.write_u32::<BigEndian>(1)?;
+            self.boxtype.write(wtr)?;
+            wtr.write_u64::<BigEndian>(total_size)?;
+        } else {
+            wtr.write_u32::<BigEndian>(total_size.try_into()?)?;
+            self.boxtype.write(wtr)?;
+        }
+        if let Some(usertype) = &self.usertype {
+            wtr.write_all(usertype)?;
+        }
+        Ok(())
+    }
+
+    // keeps a largesize header read from the file, and promotes to one when the box does not fit in 32 bits
+    fn uses_largesize(&self, payload_size: u64) -> bool {
+        if self.extends_to_end {
+            return false;
+        }
+        let compact_size = 8 + if self.usertype.is_some() { 16 } else { 0 } + payload_size;
+        self.largesize || compact_size > u32::MAX.into()
+    }
+}
@@ -1,71 +1,126 @@
-use std::{fmt, fs::File};
+use std::{fmt, io::{Cursor, Read, Seek, Write}};
 
-use byteorder::{BigEndian, ReadBytesExt};
+use crate::error::{Error, Result};
 
-use super::{atom::Atom, avc1_box::Avc1Box, avcc_box::AvccBox, four_cc::FourCC, ftyp_box, mdat_box, mdia_box::MdiaBox, minf_box::MinfBox, moov_box, mvhd_box, stbl_box::StblBox, stsd_box::StsdBox, stsz_box::StszBox, trak_box::TrakBox, unknown_box};
+use super::{atom::Atom, avc1_box::Avc1Box, avcc_box::AvccBox, box_header::BoxHeader, co64_box::Co64Box, ctts_box::CttsBox, ftyp_box, hdlr_box::HdlrBox, mdat_box, mdhd_box::MdhdBox, mdia_box::MdiaBox, minf_box::MinfBox, moov_box, mvhd_box, stbl_box::StblBox, stco_box::StcoBox, stsc_box::StscBox, stsd_box::StsdBox, stss_box::StssBox, stsz_box::StszBox, stts_box::SttsBox, tkhd_box::TkhdBox, trak_box::TrakBox, unknown_box};
 
 pub struct BoxList {
     pub boxes: Vec<Box<dyn Atom>>
 }
 
 impl BoxList {
-    pub fn read(rdr: &mut File, len: u64) -> Self {
+    // reads the boxes of a container payload of exactly len bytes
+    pub fn read(rdr: &mut (impl Read + Seek), len: u64) -> Result<Self> {
         let mut boxes = Vec::new();
         let mut read_len = 0;
-        while let Some(atom) = BoxList::read_atom(rdr) {
-            read_len += 8 + &atom.get_payload_size();      // TODO: handle large header
-            boxes.push(atom);
-            if len != 0 && read_len >= len {
-                break;
+        while read_len < len {
+            let offset = rdr.stream_position()?;
+            match BoxList::read_atom(rdr, Some(len - read_len))? {
+                Some(atom) => boxes.push(atom),
+                None => return Err(Error::truncated().at_offset(offset))
             }
+            read_len += rdr.stream_position()? - offset;
         }
-        BoxList {
+        Ok(BoxList {
             boxes
+        })
+    }
+
+    // reads top-level boxes until the end of the stream
+    pub fn read_to_end(rdr: &mut (impl Read + Seek)) -> Result<Self> {
+        let mut boxes = Vec::new();
+        while let Some(atom) = BoxList::read_atom(rdr, None)? {
+            boxes.push(atom);
         }
+        Ok(BoxList {
+            boxes
+        })
     }
 
-    pub fn write(&self, wtr: &mut File) {
+    pub fn write(&self, wtr: &mut dyn Write) -> Result<()> {
         for atom in &self.boxes {
-            atom.write(wtr);
+            atom.write(wtr)?;
         }
+        Ok(())
     }
 
-    pub fn get_size(&self) -> u64 {
-        let mut total = 0;
-        for b in &self.boxes {
-            total += b.get_payload_size() + 8
-        }
-        total
+    pub fn find<T: 'static>(&self) -> Option<&T> {
+        self.boxes.iter().find_map(|atom| atom.as_any().downcast_ref::<T>())
+    }
+
+    pub fn find_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.boxes.iter_mut().find_map(|atom| atom.as_any_mut().downcast_mut::<T>())
+    }
+
+    pub fn find_all<T: 'static>(&self) -> impl Iterator<Item = &T> {
+        self.boxes.iter().filter_map(|atom| atom.as_any().downcast_ref::<T>())
+    }
+
+    pub fn find_all_mut<T: 'static>(&mut self) -> impl Iterator<Item = &mut T> {
+        self.boxes.iter_mut().filter_map(|atom| atom.as_any_mut().downcast_mut::<T>())
+    }
+
+    pub fn get_size(&self) -> Result<u64> {
+        self.boxes.iter().map(|atom| atom.get_size()).sum()
     }
 
-    fn read_atom(rdr: &mut File) -> Option<Box<dyn Atom>> {
-        let size_u32 = rdr.read_u32::<BigEndian>();
-        let size: u64 = match size_u32 {
-            Ok(1) => rdr.read_u64::<BigEndian>().unwrap(),
-            Ok(s) => s.into(),
-            Err(_) => return None,
+    // a box of a container must fit in the remaining bytes of its container
+    fn read_atom(rdr: &mut (impl Read + Seek), remaining_len: Option<u64>) -> Result<Option<Box<dyn Atom>>> {
+        let offset = rdr.stream_position()?;
+        let (header, payload_size) = match BoxHeader::read(rdr).map_err(|e| e.at_offset(offset))? {
+            Some(header) => header,
+            None => return Ok(None)
         };
+        let name = header.boxtype.to_string();
+        if let Some(remaining_len) = remaining_len {
+            let header_size = rdr.stream_position()? - offset;
+            if header_size.checked_add(payload_size).is_none_or(|size| size > remaining_len) {
+                return Err(Error::spec_violation("box extends beyond its container").in_box(&name).at_offset(offset));
+            }
+        }
+        BoxList::read_payload(rdr, header, payload_size)
+            .map(Some)
+            .map_err(|e| e.in_box(&name).at_offset(offset))
+    }
 
-        let payload_size = size - 8;
-        let boxtype = FourCC::read(rdr).unwrap();
-        let name = boxtype.to_string();
+    fn read_payload(rdr: &mut (impl Read + Seek), header: BoxHeader, payload_size: u64) -> Result<Box<dyn Atom>> {
+        let name = header.boxtype.to_string();
         let name_str = name.as_str();
 
+        // containers read their boxes from the stream, other boxes only from the bytes of their payload
         match name_str {
-            "ftyp" => Some(Box::new(ftyp_box::FtypBox::read(rdr, payload_size).unwrap())),
-            "mdat" => Some(Box::new(mdat_box::MdatBox::read(rdr, payload_size).unwrap())),
-            "moov" => Some(Box::new(moov_box::MoovBox::read(rdr, payload_size).unwrap())),
-            "mvhd" => Some(Box::new(mvhd_box::MvhdBox::read(rdr, payload_size).unwrap())),
-            "trak" => Some(Box::new(TrakBox::read(rdr, payload_size).unwrap())),
-            "mdia" => Some(Box::new(MdiaBox::read(rdr, payload_size).unwrap())),
-            "minf" => Some(Box::new(MinfBox::read(rdr, payload_size).unwrap())),
-            "stbl" => Some(Box::new(StblBox::read(rdr, payload_size).unwrap())),
-            "stsd" => Some(Box::new(StsdBox::read(rdr, payload_size).unwrap())),
-            "avc1" => Some(Box::new(Avc1Box::read(rdr, payload_size).unwrap())),
-            "avcC" => Some(Box::new(AvccBox::read(rdr, payload_size).unwrap())),
-            "stsz" => Some(Box::new(StszBox::read(rdr, payload_size).unwrap())),
-            _ => Some(Box::new(unknown_box::UnknownBox::read(rdr, payload_size, boxtype).unwrap()))
+            "mdat" => return Ok(Box::new(mdat_box::MdatBox::read(rdr, header, payload_size)?)),
+            "moov" => return Ok(Box::new(moov_box::MoovBox::read(rdr, header, payload_size)?)),
+            "trak" => return Ok(Box::new(TrakBox::read(rdr, header, payload_size)?)),
+            "mdia" => return Ok(Box::new(MdiaBox::read(rdr, header, payload_size)?)),
+            "minf" => return Ok(Box::new(MinfBox::read(rdr, header, payload_size)?)),
+            "stbl" => return Ok(Box::new(StblBox::read(rdr, header, payload_size)?)),
+            "stsd" => return Ok(Box::new(StsdBox::read(rdr, header, payload_size)?)),
+            "avc1" => return Ok(Box::new(Avc1Box::read(rdr, header, payload_size)?)),
+            _ => ()
         }
+        let mut payload = vec![];
+        rdr.take(payload_size).read_to_end(&mut payload)?;
+        if (payload.len() as u64) < payload_size {
+            return Err(Error::truncated());
+        }
+        let rdr = &mut Cursor::new(payload);
+        Ok(match name_str {
+            "ftyp" => Box::new(ftyp_box::FtypBox::read(rdr, header, payload_size)?),
+            "mvhd" => Box::new(mvhd_box::MvhdBox::read(rdr, header, payload_size)?),
+            "tkhd" => Box::new(TkhdBox::read(rdr, header, payload_size)?),
+            "mdhd" => Box::new(MdhdBox::read(rdr, header, payload_size)?),
+            "hdlr" => Box::new(HdlrBox::read(rdr, header, payload_size)?),
+            "avcC" => Box::new(AvccBox::read(rdr, header, payload_size)?),
+            "stsz" => Box::new(StszBox::read(rdr, header, payload_size)?),
+            "stco" => Box::new(StcoBox::read(rdr, header, payload_size)?),
+            "co64" => Box::new(Co64Box::read(rdr, header, payload_size)?),
+            "stts" => Box::new(SttsBox::read(rdr, header, payload_size)?),
+            "ctts" => Box::new(CttsBox::read(rdr, header, payload_size)?),
+            "stss" => Box::new(StssBox::read(rdr, header, payload_size)?),
+            "stsc" => Box::new(StscBox::read(rdr, header, payload_size)?),
+            _ => Box::new(unknown_box::UnknownBox::read(rdr, header, payload_size)?)
+        })
     }
 }
 
@@ -76,3 +131,52 @@ impl fmt::Debug for BoxList {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{mp4::avc1_box::Avc1Box, ErrorKind};
+
+    use super::BoxList;
+
+    fn read_box(boxtype: &[u8; 4], payload: &[u8]) -> ErrorKind {
+        let mut bytes = (8 + payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(boxtype);
+        bytes.extend(payload);
+        BoxList::read_to_end(&mut Cursor::new(bytes)).unwrap_err().kind
+    }
+
+    #[test]
+    fn reject_payloads_shorter_than_their_fields() {
+        assert!(matches!(read_box(b"ftyp", &[0; 4]), ErrorKind::SpecViolation(_)));
+        assert!(matches!(read_box(b"stsd", &[0; 4]), ErrorKind::SpecViolation(_)));
+        assert!(matches!(read_box(b"avc1", &[0; 20]), ErrorKind::SpecViolation(_)));
+        // a full box header with no room for its entry count
+        assert!(matches!(read_box(b"stsz", &[0; 6]), ErrorKind::Truncated));
+    }
+
+    #[test]
+    fn round_trip_avc1_fields() {
+        let mut payload = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        payload.extend([1, 64, 0, 240, 0, 0x60, 0, 0, 0, 0x60, 0, 0, 0, 0, 0, 0, 0, 2]);    // 320x240 at 96 dpi, 2 frames
+        payload.extend([0; 32]);
+        payload.extend([0, 0x20, 0xff, 0xff]);     // depth 32
+        let mut bytes = (8 + payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(b"avc1");
+        bytes.extend(&payload);
+
+        let box_list = BoxList::read_to_end(&mut Cursor::new(&bytes)).unwrap();
+        let avc1 = box_list.find::<Avc1Box>().unwrap();
+        assert_eq!((avc1.frame_count, avc1.depth, avc1.horizresolution), (2, 32, 0x00600000));
+        let mut written = vec![];
+        box_list.write(&mut written).unwrap();
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn reject_box_beyond_its_container() {
+        let payload = [0, 0, 0, 100, b'f', b'r', b'e', b'e'];
+        assert!(matches!(read_box(b"moov", &payload), ErrorKind::SpecViolation(_)));
+    }
+}
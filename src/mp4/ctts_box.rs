@@ -0,0 +1,101 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Error, Result};
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct CttsBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub sample_counts: Vec<u32>,
+    pub sample_offsets: Vec<i64>    // unsigned in version 0, signed in version 1
+}
+
+impl CttsBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let entry_count = rdr.read_u32::<BigEndian>()?;
+        let mut sample_counts = vec![];
+        let mut sample_offsets = vec![];
+        for _i in 0..entry_count {
+            sample_counts.push(rdr.read_u32::<BigEndian>()?);
+            if version == 0 {
+                sample_offsets.push(rdr.read_u32::<BigEndian>()?.into());
+            } else {
+                sample_offsets.push(rdr.read_i32::<BigEndian>()?.into());
+            }
+        }
+
+        Ok(CttsBox {
+            header,
+            version,
+            flags,
+            sample_counts,
+            sample_offsets
+        })
+    }
+
+    // composition offset of each sample, to be added to its decoding time
+    pub fn get_composition_offsets(&self) -> Vec<i64> {
+        let mut composition_offsets = vec![];
+        for (&sample_count, &sample_offset) in self.sample_counts.iter().zip(&self.sample_offsets) {
+            for _i in 0..sample_count {
+                composition_offsets.push(sample_offset);
+            }
+        }
+        composition_offsets
+    }
+}
+
+impl Atom for CttsBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + 8 * self.sample_counts.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+
+        wtr.write_u32::<BigEndian>(self.sample_counts.len().try_into()?)?;
+        for (&sample_count, &sample_offset) in self.sample_counts.iter().zip(&self.sample_offsets) {
+            wtr.write_u32::<BigEndian>(sample_count)?;
+            if self.version == 0 {
+                let sample_offset = u32::try_from(sample_offset)
+                    .map_err(|_| Error::spec_violation(format!("composition offset {} in version 0 ctts", sample_offset)))?;
+                wtr.write_u32::<BigEndian>(sample_offset)?;
+            } else {
+                wtr.write_i32::<BigEndian>(sample_offset.try_into()?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for CttsBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CttsBox")
+            .field("version", &self.version)
+            .field("sample_counts", &self.sample_counts)
+            .field("sample_offsets", &self.sample_offsets)
+            .finish()
+    }
+}
@@ -1,35 +1,41 @@
 use std::{fmt, io::{self, Read, Write}};
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct FourCC {
     data: [u8; 4]
 }
 
 impl FourCC {
+    pub fn new(data: [u8; 4]) -> Self {
+        FourCC {
+            data
+        }
+    }
+
     pub fn read(rdr: &mut impl Read) -> io::Result<Self> {
         let mut data = [0; 4];
-        rdr.read(&mut data)?;
+        rdr.read_exact(&mut data)?;
 
         Ok(FourCC {
             data
         })
     }
 
-    pub fn write(&self, wtr: &mut impl Write) {
-        wtr.write(&self.data).unwrap();
+    pub fn write(&self, wtr: &mut (impl Write + ?Sized)) -> io::Result<()> {
+        wtr.write_all(&self.data)
     }
 }
 
 impl fmt::Display for FourCC {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let fourcc = std::str::from_utf8(&self.data).unwrap();
+        let fourcc = String::from_utf8_lossy(&self.data);
         write!(f, "{:}", fourcc)
     }
 }
 
 impl fmt::Debug for FourCC {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let fourcc = std::str::from_utf8(&self.data).unwrap();
+        let fourcc = String::from_utf8_lossy(&self.data);
         write!(f, "{:}", fourcc)
     }
 }
@@ -1,20 +1,22 @@
-use std::{any::Any, fmt, io::{self, Read, Write}};
+use std::{any::Any, fmt, io::{Read, Write}};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use super::{atom::Atom, four_cc::FourCC};
+use crate::error::{Error, Result};
+
+use super::{atom::Atom, box_header::BoxHeader, four_cc::FourCC};
 
 pub struct FtypBox {
+    pub header: BoxHeader,
     pub major_brand: FourCC,
     pub minor_brand: u32,
-    pub compatible_brands: Vec<FourCC>,
-    pub payload_size: u64
+    pub compatible_brands: Vec<FourCC>
 }
 
 impl FtypBox {
-    pub fn read(rdr: &mut impl Read, len: u64) -> io::Result<Self> {
+    pub fn read(rdr: &mut impl Read, header: BoxHeader, len: u64) -> Result<Self> {
+        let num_compatible_brands = len.checked_sub(8).ok_or_else(|| Error::spec_violation(format!("ftyp payload of {} bytes", len)))? / 4;
         let major_brand = FourCC::read(rdr)?;
         let minor_brand = rdr.read_u32::<BigEndian>()?;
-        let num_compatible_brands = (len - 8) / 4;
         let mut compatible_brands = Vec::new();
         for _i in 0..num_compatible_brands {
             let compatible_brand = FourCC::read(rdr)?;
@@ -22,28 +24,31 @@ impl FtypBox {
         }
 
         Ok(FtypBox {
+            header,
             major_brand,
             minor_brand,
-            compatible_brands,
-            payload_size: len
+            compatible_brands
         })
     }
 }
 
 impl Atom for FtypBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + 4 * self.compatible_brands.len() as u64)
     }
 
-    fn write(&self, wtr: &mut std::fs::File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"ftyp").unwrap();
-        self.major_brand.write(wtr);
-        wtr.write_u32::<BigEndian>(self.minor_brand).unwrap();
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        self.major_brand.write(wtr)?;
+        wtr.write_u32::<BigEndian>(self.minor_brand)?;
         for compatible_brand in &self.compatible_brands {
-            compatible_brand.write(wtr);
+            compatible_brand.write(wtr)?;
         }
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
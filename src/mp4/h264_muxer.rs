@@ -0,0 +1,433 @@
+use std::{io::Read, rc::Rc};
+
+use crate::{error::{Error, Result}, h264::{access_unit::AccessUnit, dec_ref_pic_marking::DecRefPicMarking, parameter_sets::ParameterSets, poc_decoder::PocDecoder, pps_nalu::PpsNalu, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider}};
+
+use super::{atom::Atom, avc1_box::Avc1Box, avc_decoder_configuration_record::AvcDecoderConfigurationRecord, avcc_box::AvccBox, box_header::BoxHeader, box_list::BoxList, ctts_box::CttsBox, four_cc::FourCC, ftyp_box::FtypBox, h264_nalu_list::H264NaluList, hdlr_box::HdlrBox, mdat_box::MdatBox, mdat_chunk::MdatChunk, mdat_segment::MdatSegment, mdhd_box::MdhdBox, mdia_box::MdiaBox, minf_box::MinfBox, moov_box::MoovBox, mp4_file::Mp4File, mvhd_box::MvhdBox, sample_data::SampleData, stbl_box::StblBox, stco_box::StcoBox, stsc_box::StscBox, stsd_box::StsdBox, stss_box::StssBox, stsz_box::StszBox, stts_box::SttsBox, tkhd_box::TkhdBox, trak_box::TrakBox, unknown_box::UnknownBox};
+
+const MOVIE_TIMESCALE: u32 = 1000;
+const UNITY_MATRIX: [i32; 9] = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000];
+
+// builds a single-track mp4 from an H.264 stream at a constant frame rate
+pub struct H264Muxer {
+    pub timescale: u32,     // ticks per second of the track
+    pub sample_delta: u32   // duration of each frame in ticks
+}
+
+impl H264Muxer {
+    // e.g. new(30000, 1001) for 29.97 frames per second
+    pub fn new(frame_rate_numerator: u32, frame_rate_denominator: u32) -> Self {
+        H264Muxer {
+            timescale: frame_rate_numerator,
+            sample_delta: frame_rate_denominator
+        }
+    }
+
+    pub fn mux_annex_b(&self, rdr: &mut impl Read) -> Result<Mp4File> {
+        let nalu_list = H264NaluList::read_annex_b(rdr, Rc::default())?;
+        self.mux(nalu_list.split_access_units())
+    }
+
+    // each list holds the units of one access unit, in decode order
+    pub fn mux(&self, mut access_units: Vec<H264NaluList>) -> Result<Mp4File> {
+        if self.timescale == 0 || self.sample_delta == 0 {
+            return Err(Error::unsupported(format!("frame rate {}/{}", self.timescale, self.sample_delta)));
+        }
+        if access_units.is_empty() {
+            return Err(Error::spec_violation("no access units to mux"));
+        }
+        let avc_configuration = H264Muxer::get_avc_configuration(&access_units)?;
+        let presentation_order = H264Muxer::get_presentation_order(&access_units)?;
+        for access_unit in &mut access_units {
+            access_unit.length_size = avc_configuration.length_size_minus_one + 1;
+        }
+
+        let sample_count = u32::try_from(access_units.len())?;
+        let duration = u64::from(sample_count) * u64::from(self.sample_delta);
+        let movie_duration = duration * u64::from(MOVIE_TIMESCALE) / u64::from(self.timescale);
+        let sps = &avc_configuration.sequence_parameter_set_nal_units[0];
+        let width = u16::try_from(sps.get_frame_width()?)?;
+        let height = u16::try_from(sps.get_frame_height()?)?;
+
+        let mut stbl_boxes: Vec<Box<dyn Atom>> = vec![
+            Box::new(StsdBox {
+                header: BoxHeader::new(b"stsd"),
+                version: 0,
+                flags: [0; 3],
+                box_list: BoxList {
+                    boxes: vec![Box::new(Avc1Box {
+                        header: BoxHeader::new(b"avc1"),
+                        data_reference_index: 1,
+                        visual_sample_entry_reserved: 0,
+                        width,
+                        height,
+                        horizresolution: 0x00480000,    // 72 dpi
+                        vertresolution: 0x00480000,
+                        frame_count: 1,
+                        compressorname: [0; 32],
+                        depth: 0x0018,
+                        box_list: BoxList {
+                            boxes: vec![Box::new(AvccBox {
+                                header: BoxHeader::new(b"avcC"),
+                                avc_decoder_configuration_record: avc_configuration,
+                                remaining: vec![]
+                            })]
+                        }
+                    })]
+                }
+            }),
+            Box::new(SttsBox {
+                header: BoxHeader::new(b"stts"),
+                version: 0,
+                flags: [0; 3],
+                sample_counts: vec![sample_count],
+                sample_deltas: vec![self.sample_delta]
+            })
+        ];
+
+        // composition offsets from the presentation order, shifted so that none is negative
+        let decode_delay = presentation_order.iter().enumerate()
+            .map(|(decode_index, &presentation_index)| decode_index.saturating_sub(presentation_index))
+            .max()
+            .unwrap_or(0);
+        if presentation_order.iter().enumerate().any(|(decode_index, &presentation_index)| decode_index != presentation_index) {
+            let mut ctts = CttsBox {
+                header: BoxHeader::new(b"ctts"),
+                version: 0,
+                flags: [0; 3],
+                sample_counts: vec![],
+                sample_offsets: vec![]
+            };
+            for (decode_index, &presentation_index) in presentation_order.iter().enumerate() {
+                let sample_offset = (presentation_index + decode_delay - decode_index) as i64 * i64::from(self.sample_delta);
+                if ctts.sample_offsets.last() == Some(&sample_offset) {
+                    *ctts.sample_counts.last_mut().unwrap() += 1;
+                } else {
+                    ctts.sample_counts.push(1);
+                    ctts.sample_offsets.push(sample_offset);
+                }
+            }
+            stbl_boxes.push(Box::new(ctts));
+        }
+
+        let sample_numbers: Vec<u32> = (1..=sample_count)
+            .zip(&access_units)
+            .filter(|(_, access_unit)| access_unit.units.iter().any(|unit| unit.get_nal_unit_type() == 5))
+            .map(|(sample_number, _)| sample_number)
+            .collect();
+        if sample_numbers.len() < access_units.len() {
+            stbl_boxes.push(Box::new(StssBox {
+                header: BoxHeader::new(b"stss"),
+                version: 0,
+                flags: [0; 3],
+                sample_numbers
+            }));
+        }
+
+        // all samples go into a single chunk, sizes and offset are filled in by layout
+        stbl_boxes.push(Box::new(StscBox {
+            header: BoxHeader::new(b"stsc"),
+            version: 0,
+            flags: [0; 3],
+            first_chunks: vec![1],
+            samples_per_chunks: vec![sample_count],
+            sample_description_indices: vec![1]
+        }));
+        stbl_boxes.push(Box::new(StszBox {
+            header: BoxHeader::new(b"stsz"),
+            version: 0,
+            flags: [0; 3],
+            sample_size: 0,
+            sample_count,
+            entry_sizes: vec![0; access_units.len()]
+        }));
+        stbl_boxes.push(Box::new(StcoBox {
+            header: BoxHeader::new(b"stco"),
+            version: 0,
+            flags: [0; 3],
+            chunk_offsets: vec![0]
+        }));
+
+        let minf = MinfBox {
+            header: BoxHeader::new(b"minf"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(UnknownBox {
+                        header: BoxHeader::new(b"vmhd"),
+                        remaining: vec![0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]     // flags 1, graphicsmode and opcolor 0
+                    }),
+                    Box::new(UnknownBox {
+                        header: BoxHeader::new(b"dinf"),
+                        remaining: vec![
+                            0, 0, 0, 28, b'd', b'r', b'e', b'f', 0, 0, 0, 0, 0, 0, 0, 1,
+                            0, 0, 0, 12, b'u', b'r', b'l', b' ', 0, 0, 0, 1     // media data in the same file
+                        ]
+                    }),
+                    Box::new(StblBox {
+                        header: BoxHeader::new(b"stbl"),
+                        box_list: BoxList {
+                            boxes: stbl_boxes
+                        }
+                    })
+                ]
+            }
+        };
+        let mdia = MdiaBox {
+            header: BoxHeader::new(b"mdia"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(MdhdBox {
+                        header: BoxHeader::new(b"mdhd"),
+                        version: 0,
+                        flags: [0; 3],
+                        creation_time: 0,
+                        modification_time: 0,
+                        timescale: self.timescale,
+                        duration,
+                        language: 0x55c4    // und
+                    }),
+                    Box::new(HdlrBox {
+                        header: BoxHeader::new(b"hdlr"),
+                        version: 0,
+                        flags: [0; 3],
+                        handler_type: FourCC::new(*b"vide"),
+                        name: b"VideoHandler\0".to_vec()
+                    }),
+                    Box::new(minf)
+                ]
+            }
+        };
+        let mut trak_boxes: Vec<Box<dyn Atom>> = vec![
+            Box::new(TkhdBox {
+                header: BoxHeader::new(b"tkhd"),
+                version: 0,
+                flags: [0, 0, 3],   // enabled and in movie
+                creation_time: 0,
+                modification_time: 0,
+                track_id: 1,
+                duration: movie_duration,
+                layer: 0,
+                alternate_group: 0,
+                volume: 0,
+                matrix: UNITY_MATRIX,
+                width: u32::from(width) << 16,
+                height: u32::from(height) << 16
+            })
+        ];
+        // an edit list skips the composition offset added to every sample, so that presentation starts at 0
+        if decode_delay > 0 {
+            let media_time = decode_delay as u64 * u64::from(self.sample_delta);
+            trak_boxes.push(Box::new(UnknownBox {
+                header: BoxHeader::new(b"edts"),
+                remaining: H264Muxer::get_elst_bytes(movie_duration, media_time)?
+            }));
+        }
+        trak_boxes.push(Box::new(mdia));
+        let trak = TrakBox {
+            header: BoxHeader::new(b"trak"),
+            box_list: BoxList {
+                boxes: trak_boxes
+            }
+        };
+
+        let moov = MoovBox {
+            header: BoxHeader::new(b"moov"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(MvhdBox {
+                        header: BoxHeader::new(b"mvhd"),
+                        version: 0,
+                        flags: [0; 3],
+                        creation_time: 0,
+                        modification_time: 0,
+                        timescale: MOVIE_TIMESCALE,
+                        duration: movie_duration,
+                        rate: 0x00010000,
+                        volume: 0x0100,
+                        matrix: UNITY_MATRIX,
+                        next_track_id: 2
+                    }),
+                    Box::new(trak)
+                ]
+            }
+        };
+
+        let mdat = MdatBox {
+            header: BoxHeader::new(b"mdat"),
+            segments: vec![MdatSegment::Chunk(MdatChunk {
+                track_index: 0,
+                chunk_index: 0,
+                first_sample_index: 0,
+                samples: access_units.into_iter().map(SampleData::H264).collect()
+            })],
+            payload_offset: 0,
+            payload_size: 0
+        };
+        let ftyp = FtypBox {
+            header: BoxHeader::new(b"ftyp"),
+            major_brand: FourCC::new(*b"isom"),
+            minor_brand: 0x200,
+            compatible_brands: vec![FourCC::new(*b"isom"), FourCC::new(*b"iso2"), FourCC::new(*b"avc1"), FourCC::new(*b"mp41")]
+        };
+
+        let mut mp4_file = Mp4File {
+            box_list: BoxList {
+                boxes: vec![Box::new(ftyp), Box::new(moov), Box::new(mdat)]
+            }
+        };
+        mp4_file.layout()?;
+        Ok(mp4_file)
+    }
+
+    // elst box with one edit of the whole track, starting at media_time in the track timescale
+    fn get_elst_bytes(segment_duration: u64, media_time: u64) -> Result<Vec<u8>> {
+        let mut elst = vec![];
+        match (u32::try_from(segment_duration), i32::try_from(media_time)) {
+            (Ok(segment_duration), Ok(media_time)) => {
+                elst.extend_from_slice(&28u32.to_be_bytes());
+                elst.extend_from_slice(b"elst");
+                elst.extend_from_slice(&[0, 0, 0, 0]);                       // version 0 and flags
+                elst.extend_from_slice(&1u32.to_be_bytes());                 // entry_count
+                elst.extend_from_slice(&segment_duration.to_be_bytes());
+                elst.extend_from_slice(&media_time.to_be_bytes());
+            },
+            _ => {
+                elst.extend_from_slice(&36u32.to_be_bytes());
+                elst.extend_from_slice(b"elst");
+                elst.extend_from_slice(&[1, 0, 0, 0]);                       // version 1 and flags
+                elst.extend_from_slice(&1u32.to_be_bytes());                 // entry_count
+                elst.extend_from_slice(&segment_duration.to_be_bytes());
+                elst.extend_from_slice(&i64::try_from(media_time)?.to_be_bytes());
+            }
+        }
+        elst.extend_from_slice(&0x00010000u32.to_be_bytes());              // media_rate 1.0
+        Ok(elst)
+    }
+
+    // every distinct SPS and PPS of the stream, first definition of each id wins
+    fn get_avc_configuration(access_units: &[H264NaluList]) -> Result<AvcDecoderConfigurationRecord> {
+        let mut parameter_sets = ParameterSets::new();
+        for access_unit in access_units {
+            let known_sps = access_unit.parameter_sets.sps.values();
+            let in_band_sps = access_unit.units.iter().filter_map(|unit| unit.as_any().downcast_ref::<SpsNalu>());
+            for sps in known_sps.chain(in_band_sps) {
+                parameter_sets.sps.entry(sps.seq_parameter_set_id).or_insert_with(|| sps.clone());
+            }
+            let known_pps = access_unit.parameter_sets.pps.values();
+            let in_band_pps = access_unit.units.iter().filter_map(|unit| unit.as_any().downcast_ref::<PpsNalu>());
+            for pps in known_pps.chain(in_band_pps) {
+                parameter_sets.pps.entry(pps.pic_parameter_set_id).or_insert_with(|| pps.clone());
+            }
+        }
+
+        let mut sequence_parameter_set_nal_units: Vec<SpsNalu> = parameter_sets.sps.into_values().collect();
+        sequence_parameter_set_nal_units.sort_by_key(|sps| sps.seq_parameter_set_id);
+        let mut picture_parameter_set_nal_units: Vec<PpsNalu> = parameter_sets.pps.into_values().collect();
+        picture_parameter_set_nal_units.sort_by_key(|pps| pps.pic_parameter_set_id);
+        let sps = sequence_parameter_set_nal_units.first()
+            .ok_or_else(|| Error::spec_violation("no SPS in the stream"))?;
+
+        let constraint_flags = [sps.constraint_set0_flag, sps.constraint_set1_flag, sps.constraint_set2_flag, sps.constraint_set3_flag, sps.constraint_set4_flag, sps.constraint_set5_flag];
+        let profile_compatibility = constraint_flags.iter()
+            .enumerate()
+            .fold(0, |compatibility, (i, &flag)| compatibility | (u8::from(flag) << (7 - i)));
+        Ok(AvcDecoderConfigurationRecord {
+            configuration_version: 1,
+            avc_profile_indication: sps.profile_idc,
+            profile_compatibility,
+            avc_level_indication: sps.level_idc,
+            length_size_minus_one: 3,
+            extension_present: matches!(sps.profile_idc, 100 | 110 | 122 | 144),
+            chroma_format: u8::try_from(sps.chroma_format_idc)?,
+            bit_depth_luma_minus8: u8::try_from(sps.bit_depth_luma_minus8)?,
+            bit_depth_chroma_minus8: u8::try_from(sps.bit_depth_chroma_minus8)?,
+            sequence_parameter_set_ext_nal_units: vec![],
+            sequence_parameter_set_nal_units,
+            picture_parameter_set_nal_units
+        })
+    }
+
+    // presentation position of each access unit from its PicOrderCnt (8.2.1); pictures are reordered
+    // within each period starting at an IDR picture or a memory_management_control_operation 5 only
+    fn get_presentation_order(access_units: &[H264NaluList]) -> Result<Vec<usize>> {
+        let mut order_keys = vec![];
+        let mut output_period = 0;
+        let mut poc_decoder = PocDecoder::new();
+        for (decode_index, access_unit) in access_units.iter().enumerate() {
+            let (nal_ref_idc, slice_header) = access_unit.units.iter()
+                .find_map(|unit| AccessUnit::get_slice(unit.as_ref()))
+                .ok_or_else(|| Error::spec_violation(format!("access unit #{} has no slice", decode_index)))?;
+            let pps = access_unit.get_pps(slice_header.pic_parameter_set_id)
+                .ok_or_else(|| Error::spec_violation(format!("slice refers to missing PPS {}", slice_header.pic_parameter_set_id)))?;
+            let sps = access_unit.get_sps(pps.seq_parameter_set_id)
+                .ok_or_else(|| Error::spec_violation(format!("PPS refers to missing SPS {}", pps.seq_parameter_set_id)))?;
+            if slice_header.idr_pic_flag || slice_header.dec_ref_pic_marking.as_ref().is_some_and(DecRefPicMarking::has_memory_management_control_operation_5) {
+                output_period += 1;
+            }
+            let pic_order_cnt = poc_decoder.decode(nal_ref_idc, slice_header, sps)?;
+            order_keys.push((output_period, pic_order_cnt.pic_order_cnt));
+        }
+
+        let mut decode_indices: Vec<usize> = (0..access_units.len()).collect();
+        decode_indices.sort_by_key(|&decode_index| order_keys[decode_index]);
+        let mut presentation_order = vec![0; access_units.len()];
+        for (presentation_index, decode_index) in decode_indices.into_iter().enumerate() {
+            presentation_order[decode_index] = presentation_index;
+        }
+        Ok(presentation_order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{mp4::{ctts_box::CttsBox, four_cc::FourCC, mp4_file::Mp4File, parameter_set_placement::ParameterSetPlacement, stco_box::StcoBox, stsc_box::StscBox, stss_box::StssBox, stsz_box::StszBox, stts_box::SttsBox, tkhd_box::TkhdBox, unknown_box::UnknownBox}, test_util::to_annex_b_unit};
+
+    use super::H264Muxer;
+
+    #[test]
+    fn mux_reordered_pictures() {
+        // Main 320x256 cropped to 320x240, pic_order_cnt_type 0 with 6-bit lsb
+        let sps = to_annex_b_unit(0x67, "01001101 00000000 00011110 1 1 1 011 011 0 000010100 000010000 1 1 1 1 1 1 0001001 0");
+        let pps = to_annex_b_unit(0x68, "1 1 0 0 1 1 1 0 00 1 1 1 1 0 0");
+        // decode order I0 P6 B2 B4 P8 by pic_order_cnt_lsb; the B slices are not references
+        let access_units = [
+            [sps, pps, to_annex_b_unit(0x65, "1 0001000 1 0000 1 000000 0 0 1 1 1 1 10101100")].concat(),
+            to_annex_b_unit(0x41, "1 00110 1 0001 000110 0 0 0 1 1 1 1 10101100"),
+            to_annex_b_unit(0x01, "1 00111 1 0010 000010 1 0 0 0 1 1 1 1 10101100"),
+            to_annex_b_unit(0x01, "1 00111 1 0010 000100 1 0 0 0 1 1 1 1 10101100"),
+            to_annex_b_unit(0x41, "1 00110 1 0010 001000 0 0 0 1 1 1 1 10101100")
+        ];
+        let mp4_file = H264Muxer::new(30000, 1001).mux_annex_b(&mut Cursor::new(access_units.concat())).unwrap();
+        let mut bytes = vec![];
+        mp4_file.box_list.write(&mut bytes).unwrap();
+
+        let mp4_file = Mp4File::from_reader(&mut Cursor::new(&bytes)).unwrap();
+        let trak = mp4_file.tracks().next().unwrap();
+        let tkhd = trak.box_list.find::<TkhdBox>().unwrap();
+        assert_eq!((tkhd.width, tkhd.height), (320 << 16, 240 << 16));
+        let stbl = trak.stbl().unwrap();
+        let stts = stbl.box_list.find::<SttsBox>().unwrap();
+        assert_eq!((&stts.sample_counts[..], &stts.sample_deltas[..]), (&[5][..], &[1001][..]));
+        // presentation order I0 B2 B4 P6 P8, delayed by one frame
+        let ctts = stbl.box_list.find::<CttsBox>().unwrap();
+        assert_eq!(ctts.sample_counts, [1, 1, 2, 1]);
+        assert_eq!(ctts.sample_offsets, [1001, 3003, 0, 1001]);
+        assert_eq!(stbl.box_list.find::<StssBox>().unwrap().sample_numbers, [1]);
+        let stsc = stbl.box_list.find::<StscBox>().unwrap();
+        assert_eq!((&stsc.first_chunks[..], &stsc.samples_per_chunks[..]), (&[1][..], &[5][..]));
+        // a 4-byte length replaces each 4-byte start code
+        let sample_sizes: Vec<u32> = access_units.iter().map(|access_unit| access_unit.len() as u32).collect();
+        assert_eq!(stbl.box_list.find::<StszBox>().unwrap().get_sample_sizes(), sample_sizes);
+        let mdat = mp4_file.mdats().next().unwrap();
+        assert_eq!(stbl.box_list.find::<StcoBox>().unwrap().chunk_offsets, [mdat.payload_offset as u32]);
+        let mut annex_b = vec![];
+        mp4_file.write_annex_b(0, &mut annex_b, ParameterSetPlacement::StartOnly).unwrap();
+        assert_eq!(annex_b, access_units.concat());
+
+        // the edit starts at the decode delay, one frame
+        let edts = trak.box_list.find_all::<UnknownBox>().find(|unknown| unknown.header.boxtype == FourCC::new(*b"edts")).unwrap();
+        assert_eq!(edts.remaining[8..12], [0, 0, 0, 0]);
+        assert_eq!(edts.remaining[20..24], 1001i32.to_be_bytes());
+    }
+}
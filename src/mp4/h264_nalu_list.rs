@@ -1,133 +1,314 @@
-use std::{fmt, fs::File, io::Write};
+use std::{fmt, io::{Cursor, Read, Seek, Write}, rc::Rc};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::h264::{delim_nalu::DelimNalu, idr_nalu::IdrNalu, nalu::Nalu, non_idr_nalu::NonIdrNalu, pps_nalu::PpsNalu, sei_nalu::SeiNalu, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider, unknown_nalu::UnknownNalu};
+use crate::{error::{Error, Result}, h264::{access_unit::AccessUnit, delim_nalu::DelimNalu, idr_nalu::IdrNalu, nalu::Nalu, non_idr_nalu::NonIdrNalu, parameter_sets::ParameterSets, pps_nalu::PpsNalu, sei_message::SeiMessage, sei_nalu::SeiNalu, sei_payload::SeiPayload, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider, unknown_nalu::UnknownNalu, user_data_unregistered::UserDataUnregistered}};
 
+// units are read and written against the parameter sets known before the list, updated by the SPS
+// and PPS units before them, so a parameter set sent again with new content applies from there on
 pub struct H264NaluList {
-    pub units: Vec<Box<dyn Nalu>>
+    pub units: Vec<Box<dyn Nalu>>,
+    pub length_size: u8,                    // size of the NALU length prefix, 1, 2 or 4 bytes
+    pub parameter_sets: Rc<ParameterSets>   // known before this list
 }
 
 impl H264NaluList {
-    pub fn read(rdr: &mut File, len: u64) -> Self {
-        let mut list = H264NaluList {
-            units: vec![]
-        };
+    pub fn new(length_size: u8, parameter_sets: Rc<ParameterSets>) -> Self {
+        H264NaluList {
+            units: vec![],
+            length_size,
+            parameter_sets
+        }
+    }
+
+    // parameter_sets is updated with the SPS and PPS units found in the list, for the lists that follow
+    pub fn read(rdr: &mut (impl Read + Seek), len: u64, length_size: u8, parameter_sets: &mut Rc<ParameterSets>) -> Result<Self> {
+        H264NaluList::check_length_size(length_size)?;
+        let mut list = H264NaluList::new(length_size, Rc::clone(parameter_sets));
         let mut read_len: u64 = 0;
-        loop {
-            let unit_payload_size = list.read_nalu(rdr);
-            read_len += 5 + u64::from(unit_payload_size);
-            if len != 0 && read_len >= len {
-                break;
+        while read_len < len {
+            let index = list.units.len();
+            let offset = rdr.stream_position()?;
+            let unit_payload_size = list.read_nalu(rdr, parameter_sets)
+                .map_err(|e| e.at_nalu(index).at_offset(offset))?;
+            read_len += u64::from(length_size) + 1 + u64::from(unit_payload_size);
+        }
+        if read_len > len {
+            return Err(Error::spec_violation("NALU extends beyond the end of the sample"));
+        }
+        Ok(list)
+    }
+
+    // regroups the units into one list per access unit, each with the parameter sets known before it
+    pub fn split_access_units(self) -> Vec<H264NaluList> {
+        let H264NaluList { units, length_size, mut parameter_sets } = self;
+        let mut lists = vec![];
+        for access_unit in AccessUnit::group(units) {
+            let list = H264NaluList {
+                units: access_unit.units,
+                length_size,
+                parameter_sets: Rc::clone(&parameter_sets)
+            };
+            list.update_parameter_sets(&mut parameter_sets);
+            lists.push(list);
+        }
+        lists
+    }
+
+    // reads an Annex B byte stream, with NALUs delimited by 3- or 4-byte start codes
+    pub fn read_annex_b(rdr: &mut impl Read, mut parameter_sets: Rc<ParameterSets>) -> Result<Self> {
+        let mut bytes = vec![];
+        rdr.read_to_end(&mut bytes)?;
+        let mut list = H264NaluList::new(4, Rc::clone(&parameter_sets));
+        let start_codes = H264NaluList::find_start_codes(&bytes);
+        // only leading_zero_8bits may precede the first start code
+        let first_start = start_codes.first().map_or(bytes.len(), |&(start, _)| start);
+        if bytes[..first_start].iter().any(|&b| b != 0) {
+            return Err(Error::spec_violation("byte stream does not start with a start code"));
+        }
+        for (index, &(_, nalu_start)) in start_codes.iter().enumerate() {
+            let nalu_end = match start_codes.get(index + 1) {
+                Some(&(next_start, _)) => next_start,
+                None => bytes.len()
+            };
+            // drop trailing_zero_8bits
+            let nalu_bytes = &bytes[nalu_start..nalu_end];
+            let nalu_len = nalu_bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+            if nalu_len == 0 {
+                return Err(Error::spec_violation("NALU of size 0").at_nalu(index).at_offset(nalu_start as u64));
             }
+            list.read_unit(&mut Cursor::new(&nalu_bytes[..nalu_len]), u32::try_from(nalu_len)?, &mut parameter_sets)
+                .map_err(|e| e.at_nalu(index).at_offset(nalu_start as u64))?;
         }
-        list
+        Ok(list)
+    }
+
+    // writes the units as an Annex B byte stream, always with 4-byte start codes
+    pub fn write_annex_b(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.for_each_unit_bytes(|_, bytes| {
+            wtr.write_all(&[0, 0, 0, 1])?;
+            wtr.write_all(&bytes)?;
+            Ok(())
+        })
     }
 
-    pub fn write(&self, wtr: &mut dyn Write) -> Vec<u32>{
-        let mut sample_offsets: Vec<u32> = vec![];
-        let mut offset: u32 = 0;
-        let mut size: u32 = 0;
+    pub fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.for_each_unit_bytes(|_, bytes| {
+            let unit_size = H264NaluList::check_unit_size(bytes.len(), self.length_size)?;
+            wtr.write_uint::<BigEndian>(unit_size.into(), self.length_size.into())?;
+            wtr.write_all(&bytes)?;
+            Ok(())
+        })
+    }
+
+    // calls f with each unit and its bytes, written against the parameter sets in effect for it
+    pub fn for_each_unit_bytes(&self, mut f: impl FnMut(&dyn Nalu, Vec<u8>) -> Result<()>) -> Result<()> {
+        let mut parameter_sets = Rc::clone(&self.parameter_sets);
         for unit in &self.units {
-            // remove SEI, SPS, PPS units
-            // if let Some(_) = unit.as_any().downcast_ref::<SeiNalu>() {
-            //     continue;
-            // } else if let Some(_) = unit.as_any().downcast_ref::<SpsNalu>() {
-            //     continue;
-            // } else if let Some(_) = unit.as_any().downcast_ref::<PpsNalu>() {
-            //     continue;
-            // }
-
-            let bytes = unit.to_bytes(self);
-            wtr.write_u32::<BigEndian>(u32::try_from(bytes.len()).unwrap()).unwrap();   
-            wtr.write_all(&bytes).unwrap();
-
-            if let Some(_) = unit.as_any().downcast_ref::<DelimNalu>() {
-                size += 4 + u32::try_from(bytes.len()).unwrap();    // not added to offset until next sample
-            } else if let Some(_) = unit.as_any().downcast_ref::<IdrNalu>() {
-                sample_offsets.push(offset);
-                offset = size + 4 + u32::try_from(bytes.len()).unwrap();  // offset for next sample, count earlier delim nalu
-                size = 0;
-            } else if let Some(_) = unit.as_any().downcast_ref::<NonIdrNalu>() {
-                sample_offsets.push(offset);
-                offset = size + 4 + u32::try_from(bytes.len()).unwrap();  // offset for next sample, count earlier delim nalu
-                size = 0;
-            } else {
-                offset += 4 + u32::try_from(bytes.len()).unwrap();
-            }
+            f(unit.as_ref(), unit.to_bytes(parameter_sets.as_ref())?)?;
+            H264NaluList::update_parameter_set(&mut parameter_sets, unit.as_ref());
         }
-        sample_offsets
+        Ok(())
     }
 
-    fn read_nalu(&mut self, rdr: &mut File) -> u32 {
-        let size = rdr.read_u32::<BigEndian>().unwrap();
-        let header = rdr.read_u8().unwrap();
+    // SEI units precede the first VCL unit of their access unit (7.4.1.2.3), and are coded against
+    // the SPS of its slice
+    pub fn insert_sei(&mut self, mut sei: SeiNalu) -> Result<()> {
+        let position = self.units.iter()
+            .position(|unit| matches!(unit.get_nal_unit_type(), 1..=5))
+            .ok_or_else(|| Error::spec_violation("no VCL NALU to insert SEI before"))?;
+        if let Some(seq_parameter_set_id) = H264NaluList::get_slice_sps_id(self.units[position].as_ref(), self) {
+            sei.seq_parameter_set_id = seq_parameter_set_id;
+        }
+        self.units.insert(position, Box::new(sei));
+        Ok(())
+    }
+
+    // inserts a SEI unit with one user_data_unregistered message
+    pub fn insert_user_data_unregistered(&mut self, uuid_iso_iec_11578: [u8; 16], user_data_payload_byte: Vec<u8>) -> Result<()> {
+        let sei_message = SeiMessage {
+            payload_type: 5,
+            payload: SeiPayload::UserDataUnregistered(UserDataUnregistered {
+                uuid_iso_iec_11578,
+                user_data_payload_byte
+            })
+        };
+        self.insert_sei(SeiNalu::new(vec![sei_message]))
+    }
+
+    pub fn get_size(&self) -> Result<u64> {
+        let mut size = 0;
+        self.for_each_unit_bytes(|_, bytes| {
+            size += u64::from(self.length_size) + bytes.len() as u64;
+            Ok(())
+        })?;
+        Ok(size)
+    }
+
+    pub fn check_length_size(length_size: u8) -> Result<()> {
+        match length_size {
+            1 | 2 | 4 => Ok(()),
+            _ => Err(Error::spec_violation(format!("NALU length size {}", length_size)))
+        }
+    }
+
+    // checks every unit can be written with the given length prefix size
+    pub fn check_units_fit(&self, length_size: u8) -> Result<()> {
+        H264NaluList::check_length_size(length_size)?;
+        self.for_each_unit_bytes(|_, bytes| H264NaluList::check_unit_size(bytes.len(), length_size).map(|_| ()))
+    }
+
+    fn check_unit_size(unit_size: usize, length_size: u8) -> Result<u32> {
+        let unit_size = u32::try_from(unit_size)?;
+        if length_size < 4 && unit_size >> (8 * length_size) != 0 {
+            return Err(Error::unsupported(format!("NALU of {} bytes with {}-byte length prefix", unit_size, length_size)));
+        }
+        Ok(unit_size)
+    }
+
+    fn read_nalu(&mut self, rdr: &mut (impl Read + Seek), parameter_sets: &mut Rc<ParameterSets>) -> Result<u32> {
+        let size = rdr.read_uint::<BigEndian>(self.length_size.into())? as u32;
+        if size == 0 {
+            return Err(Error::spec_violation("NALU of size 0"));
+        }
+        self.read_unit(rdr, size, parameter_sets)?;
+        Ok(size - 1)
+    }
+
+    // parameter_sets are those in effect for the unit, and take in the unit if it is an SPS or PPS
+    fn read_unit(&mut self, rdr: &mut (impl Read + Seek), size: u32, parameter_sets: &mut Rc<ParameterSets>) -> Result<()> {
+        let header = rdr.read_u8()?;
         let _nal_ref_idc = (header & 0b01100000) >> 5;
         let nal_unit_type = header & 0b00011111;
         let payload_size = size - 1;
         match nal_unit_type {
             1 => {
-                let unit = NonIdrNalu::read(rdr, payload_size, header, self).unwrap();
+                let unit = NonIdrNalu::read(rdr, payload_size, header, parameter_sets.as_ref())?;
                 self.units.push(Box::new(unit));
+                self.resolve_sei_sps(parameter_sets.as_ref())?;
             },
             5 => {
-                let unit = IdrNalu::read(rdr, payload_size, self).unwrap();
+                let unit = IdrNalu::read(rdr, payload_size, header, parameter_sets.as_ref())?;
                 self.units.push(Box::new(unit));
+                self.resolve_sei_sps(parameter_sets.as_ref())?;
             },
             6 => {
-                let unit = SeiNalu::read(rdr, payload_size).unwrap();
+                // the SPS of the previous slice until the slice of this access unit is read
+                let seq_parameter_set_id = self.units.iter().rev()
+                    .find_map(|unit| H264NaluList::get_slice_sps_id(unit.as_ref(), parameter_sets.as_ref()))
+                    .unwrap_or(0);
+                let unit = SeiNalu::read(rdr, payload_size, parameter_sets.as_ref(), seq_parameter_set_id)?;
                 self.units.push(Box::new(unit));
             },
             7 => {
-                let unit = SpsNalu::read(rdr, payload_size).unwrap();
+                let unit = SpsNalu::read(rdr, payload_size, header)?;
+                Rc::make_mut(parameter_sets).insert_sps(unit.clone());
                 self.units.push(Box::new(unit));
             },
             8 => {
-                let unit = PpsNalu::read(rdr, payload_size).unwrap();
+                let unit = PpsNalu::read(rdr, payload_size, header, parameter_sets.as_ref())?;
+                Rc::make_mut(parameter_sets).insert_pps(unit.clone());
                 self.units.push(Box::new(unit));
             },
             9 => {
-                let unit = DelimNalu::read(rdr, payload_size).unwrap();
+                let unit = DelimNalu::read(rdr, payload_size)?;
                 self.units.push(Box::new(unit));
             },
             _ => {
-                let unit = UnknownNalu::read(rdr, payload_size, nal_unit_type).unwrap();
+                let unit = UnknownNalu::read(rdr, payload_size, nal_unit_type)?;
                 self.units.push(Box::new(unit));
             }
         }
-        payload_size
+        Ok(())
+    }
+
+    // pic_timing depends on the SPS active for the access unit, which is only known from its first
+    // slice; the SEI units between the previous slice and the last one read are read again against it
+    fn resolve_sei_sps(&mut self, sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
+        let (last, previous_units) = match self.units.split_last() {
+            Some(split) => split,
+            None => return Ok(())
+        };
+        let seq_parameter_set_id = match H264NaluList::get_slice_sps_id(last.as_ref(), sps_pps_provider) {
+            Some(seq_parameter_set_id) => seq_parameter_set_id,
+            None => return Ok(())
+        };
+        let first = previous_units.iter()
+            .rposition(|unit| AccessUnit::get_slice(unit.as_ref()).is_some())
+            .map_or(0, |index| index + 1);
+        for index in first..previous_units.len() {
+            let sei_messages = match self.units[index].as_any().downcast_ref::<SeiNalu>() {
+                Some(sei) if sei.seq_parameter_set_id != seq_parameter_set_id => sei.get_sei_messages_for_sps(seq_parameter_set_id, sps_pps_provider)?,
+                _ => continue
+            };
+            if let Some(sei) = self.units[index].as_any_mut().downcast_mut::<SeiNalu>() {
+                sei.sei_messages = sei_messages;
+                sei.seq_parameter_set_id = seq_parameter_set_id;
+            }
+        }
+        Ok(())
+    }
+
+    // SPS of a VCL unit, through the PPS of its slice header
+    fn get_slice_sps_id(unit: &dyn Nalu, sps_pps_provider: &dyn SpsPpsProvider) -> Option<u64> {
+        let (_, slice_header) = AccessUnit::get_slice(unit)?;
+        sps_pps_provider.get_pps(slice_header.pic_parameter_set_id).map(|pps| pps.seq_parameter_set_id)
+    }
+
+    // adds the SPS and PPS units of the list
+    fn update_parameter_sets(&self, parameter_sets: &mut Rc<ParameterSets>) {
+        for unit in &self.units {
+            H264NaluList::update_parameter_set(parameter_sets, unit.as_ref());
+        }
+    }
+
+    fn update_parameter_set(parameter_sets: &mut Rc<ParameterSets>, unit: &dyn Nalu) {
+        if let Some(sps) = unit.as_any().downcast_ref::<SpsNalu>() {
+            Rc::make_mut(parameter_sets).insert_sps(sps.clone());
+        } else if let Some(pps) = unit.as_any().downcast_ref::<PpsNalu>() {
+            Rc::make_mut(parameter_sets).insert_pps(pps.clone());
+        }
+    }
+
+    // (start code position, NALU position) of every 0x000001 start code, including the zero_byte of 4-byte ones
+    fn find_start_codes(bytes: &[u8]) -> Vec<(usize, usize)> {
+        let mut start_codes = vec![];
+        let mut i = 0;
+        while i + 3 <= bytes.len() {
+            if bytes[i] == 0 && bytes[i + 1] == 0 && bytes[i + 2] == 1 {
+                let start = if i > 0 && bytes[i - 1] == 0 { i - 1 } else { i };
+                start_codes.push((start, i + 3));
+                i += 3;
+            } else {
+                i += 1;
+            }
+        }
+        start_codes
     }
 }
 
+// the parameter sets in effect after the list, the last unit with an id taking precedence
 impl SpsPpsProvider for H264NaluList {
     fn get_sps(&self, id: u64) -> Option<&SpsNalu> {
-        for unit in &self.units {
-            let any_unit = unit.as_any();
-            match any_unit.downcast_ref::<SpsNalu>() {
-                Some(sps_unit) => {
-                    if sps_unit.seq_parameter_set_id == id {
-                        return Some(sps_unit)
-                    }
-                },
-                _ => {}
+        for unit in self.units.iter().rev() {
+            if let Some(sps_unit) = unit.as_any().downcast_ref::<SpsNalu>() {
+                if sps_unit.seq_parameter_set_id == id {
+                    return Some(sps_unit)
+                }
             }
         }
-        None
+        self.parameter_sets.get_sps(id)
     }
 
     fn get_pps(&self, id: u64) -> Option<&PpsNalu> {
-        for unit in &self.units {
-            let any_unit = unit.as_any();
-            match any_unit.downcast_ref::<PpsNalu>() {
-                Some(pps_unit) => {
-                    if pps_unit.pic_parameter_set_id == id {
-                        return Some(pps_unit)
-                    }
-                },
-                _ => {}
+        for unit in self.units.iter().rev() {
+            if let Some(pps_unit) = unit.as_any().downcast_ref::<PpsNalu>() {
+                if pps_unit.pic_parameter_set_id == id {
+                    return Some(pps_unit)
+                }
             }
         }
-        None
+        self.parameter_sets.get_pps(id)
     }
 }
 
@@ -138,3 +319,45 @@ impl fmt::Debug for H264NaluList {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, rc::Rc};
+
+    use crate::{h264::{idr_nalu::IdrNalu, parameter_sets::ParameterSets, sps_nalu::SpsNalu}, test_util::to_annex_b_unit};
+
+    use super::H264NaluList;
+
+    // Baseline 320x240 SPS 0 with frame_num of log2_max_frame_num_minus4 + 4 bits
+    fn get_sps(log2_max_frame_num_minus4: &str) -> Vec<u8> {
+        to_annex_b_unit(0x67, &format!("01000010 00000000 00011110 1 {} 011 010 0 000010100 0001111 1 1 0 0", log2_max_frame_num_minus4))
+    }
+
+    #[test]
+    fn round_trip_annex_b_with_resent_sps() {
+        let pps = to_annex_b_unit(0x68, "1 1 0 0 1 1 1 0 00 1 1 1 1 0 0");
+        let mut stream = vec![];
+        stream.extend(get_sps("1"));
+        stream.extend(&pps);
+        // I slice with frame_num 0 in 4 bits, then slice data
+        stream.extend(to_annex_b_unit(0x65, "1 0001000 1 0000 1 0 0 1 010 10101100"));
+        stream.extend(get_sps("00101"));
+        stream.extend(&pps);
+        // frame_num 0 now takes 8 bits
+        stream.extend(to_annex_b_unit(0x65, "1 0001000 1 00000000 010 0 0 1 010 10101100"));
+
+        let nalu_list = H264NaluList::read_annex_b(&mut Cursor::new(&stream), Rc::new(ParameterSets::new())).unwrap();
+        assert_eq!(nalu_list.units.len(), 6);
+        let sps = nalu_list.units[3].as_any().downcast_ref::<SpsNalu>().unwrap();
+        assert_eq!(sps.log2_max_frame_num_minus4, 4);
+        let idr_pic_ids: Vec<u64> = nalu_list.units.iter()
+            .filter_map(|unit| unit.as_any().downcast_ref::<IdrNalu>())
+            .map(|idr| idr.slice_header.idr_pic_id)
+            .collect();
+        assert_eq!(idr_pic_ids, [0, 1]);
+
+        let mut bytes = vec![];
+        nalu_list.write_annex_b(&mut bytes).unwrap();
+        assert_eq!(bytes, stream);
+    }
+}
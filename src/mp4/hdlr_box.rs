@@ -0,0 +1,77 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Error, Result};
+
+use super::{atom::Atom, box_header::BoxHeader, four_cc::FourCC};
+
+pub struct HdlrBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub handler_type: FourCC,
+    pub name: Vec<u8>           // as stored, normally null-terminated UTF-8
+}
+
+impl HdlrBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+        let _pre_defined = rdr.read_u32::<BigEndian>()?;
+        let handler_type = FourCC::read(rdr)?;
+        let mut _reserved: [u8; 12] = [0; 12];
+        rdr.read_exact(&mut _reserved)?;
+
+        let name_len = len.checked_sub(24)
+            .ok_or_else(|| Error::spec_violation("hdlr box too small"))?;
+        let mut name = vec![0u8; name_len.try_into()?];
+        rdr.read_exact(&mut name)?;
+
+        Ok(HdlrBox {
+            header,
+            version,
+            flags,
+            handler_type,
+            name
+        })
+    }
+}
+
+impl Atom for HdlrBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(24 + self.name.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+        wtr.write_u32::<BigEndian>(0)?;             // pre_defined
+        self.handler_type.write(wtr)?;
+        wtr.write_all(&[0; 12])?;                   // reserved
+        wtr.write_all(&self.name)?;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for HdlrBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HdlrBox")
+            .field("handler_type", &self.handler_type)
+            .field("name", &String::from_utf8_lossy(&self.name).trim_end_matches('\0'))
+            .finish()
+    }
+}
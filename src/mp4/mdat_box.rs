@@ -1,38 +1,63 @@
-use std::{any::Any, fmt, fs::File, io::{self, Cursor, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, SeekFrom, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::{Error, Result};
 
-use super::{atom::Atom, h264_nalu_list::H264NaluList};
+use super::{atom::Atom, box_header::BoxHeader, mdat_chunk::MdatChunk, mdat_segment::MdatSegment};
 
 pub struct MdatBox {
-    pub nalu_list: H264NaluList,
-    pub payload_size: u64
+    pub header: BoxHeader,
+    pub segments: Vec<MdatSegment>,
+    pub payload_offset: u64,    // absolute offset of the payload in the file, as last read or laid out
+    pub payload_size: u64       // size of the payload in the file, as last read or laid out
 }
 
 impl MdatBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
-        let nalu_list = H264NaluList::read(rdr, len);
+    // the payload is skipped here and read chunk by chunk once the sample tables in moov are known, see Mp4File
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let payload_offset = rdr.stream_position()?;
+        let end = payload_offset.checked_add(len).ok_or_else(Error::truncated)?;
+        if end > rdr.seek(SeekFrom::End(0))? {
+            return Err(Error::truncated());
+        }
+        rdr.seek(SeekFrom::Start(end))?;
         Ok(MdatBox {
-            nalu_list,
+            header,
+            segments: vec![],
+            payload_offset,
             payload_size: len
         })
     }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &MdatChunk> {
+        self.segments.iter().filter_map(|segment| match segment {
+            MdatSegment::Chunk(chunk) => Some(chunk),
+            MdatSegment::Opaque(_) => None
+        })
+    }
+
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = &mut MdatChunk> {
+        self.segments.iter_mut().filter_map(|segment| match segment {
+            MdatSegment::Chunk(chunk) => Some(chunk),
+            MdatSegment::Opaque(_) => None
+        })
+    }
 }
 
 impl Atom for MdatBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
-    
-    fn write(&self, wtr: &mut File) {
-        let mut cursor = Cursor::new(Vec::new());
-        self.nalu_list.write(&mut cursor);
-        let buffer = cursor.into_inner();
-        let total_size = 8 + buffer.len();  // total size includes the size field itself
 
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();        
-        wtr.write_all(b"mdat").unwrap();
-        wtr.write_all(&buffer).unwrap();
+    fn get_payload_size(&self) -> Result<u64> {
+        self.segments.iter().map(|segment| segment.get_size()).sum()
+    }
+    
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        for segment in &self.segments {
+            segment.write(wtr)?;
+        }
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -47,7 +72,7 @@ impl Atom for MdatBox {
 impl fmt::Debug for MdatBox {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MdatBox")
-        .field("nalu_list", &self.nalu_list)
+        .field("segments", &self.segments)
         .finish()
     }
 }
@@ -0,0 +1,41 @@
+use std::{fmt, io::Write};
+
+use crate::error::Result;
+
+use super::sample_data::SampleData;
+
+// samples of one chunk of a track, stored contiguously in an mdat
+pub struct MdatChunk {
+    pub track_index: usize,         // position of the trak in moov
+    pub chunk_index: usize,         // 0-based index into the track's chunk offsets
+    pub first_sample_index: usize,  // 0-based index of the first sample in the track's sample table
+    pub samples: Vec<SampleData>
+}
+
+impl MdatChunk {
+    pub fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        for sample in &self.samples {
+            sample.write(wtr)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_size(&self) -> Result<u64> {
+        let mut size = 0;
+        for sample in &self.samples {
+            size += sample.get_size()?;
+        }
+        Ok(size)
+    }
+}
+
+impl fmt::Debug for MdatChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MdatChunk")
+            .field("track_index", &self.track_index)
+            .field("chunk_index", &self.chunk_index)
+            .field("first_sample_index", &self.first_sample_index)
+            .field("samples", &self.samples)
+            .finish()
+    }
+}
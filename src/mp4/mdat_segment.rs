@@ -0,0 +1,35 @@
+use std::{fmt, io::Write};
+
+use crate::error::Result;
+
+use super::mdat_chunk::MdatChunk;
+
+pub enum MdatSegment {
+    Chunk(MdatChunk),
+    Opaque(Vec<u8>)     // bytes not claimed by any track, kept as they are
+}
+
+impl MdatSegment {
+    pub fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        match self {
+            MdatSegment::Chunk(chunk) => chunk.write(wtr),
+            MdatSegment::Opaque(bytes) => Ok(wtr.write_all(bytes)?)
+        }
+    }
+
+    pub fn get_size(&self) -> Result<u64> {
+        match self {
+            MdatSegment::Chunk(chunk) => chunk.get_size(),
+            MdatSegment::Opaque(bytes) => Ok(bytes.len() as u64)
+        }
+    }
+}
+
+impl fmt::Debug for MdatSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MdatSegment::Chunk(chunk) => chunk.fmt(f),
+            MdatSegment::Opaque(bytes) => write!(f, "Opaque({} bytes)", bytes.len())
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct MdhdBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub creation_time: u64,
+    pub modification_time: u64,
+    pub timescale: u32,
+    pub duration: u64,
+    pub language: u16           // ISO-639-2/T code packed as three 5-bit letters
+}
+
+impl MdhdBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let creation_time: u64;
+        let modification_time: u64;
+        let timescale: u32;
+        let duration: u64;
+        if version == 1 {
+            creation_time = rdr.read_u64::<BigEndian>()?;
+            modification_time = rdr.read_u64::<BigEndian>()?;
+            timescale = rdr.read_u32::<BigEndian>()?;
+            duration = rdr.read_u64::<BigEndian>()?;
+        } else {
+            creation_time = rdr.read_u32::<BigEndian>()?.into();
+            modification_time = rdr.read_u32::<BigEndian>()?.into();
+            timescale = rdr.read_u32::<BigEndian>()?;
+            duration = rdr.read_u32::<BigEndian>()?.into();
+        }
+        let language = rdr.read_u16::<BigEndian>()? & 0x7fff;
+        let _pre_defined = rdr.read_u16::<BigEndian>()?;
+
+        Ok(MdhdBox {
+            header,
+            version,
+            flags,
+            creation_time,
+            modification_time,
+            timescale,
+            duration,
+            language
+        })
+    }
+
+    pub fn get_language(&self) -> String {
+        [10, 5, 0].iter()
+            .map(|shift| char::from(((self.language >> shift) & 0x1f) as u8 + 0x60))
+            .collect()
+    }
+}
+
+impl Atom for MdhdBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(4 + if self.version == 1 { 28 } else { 16 } + 4)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+        if self.version == 1 {
+            wtr.write_u64::<BigEndian>(self.creation_time)?;
+            wtr.write_u64::<BigEndian>(self.modification_time)?;
+            wtr.write_u32::<BigEndian>(self.timescale)?;
+            wtr.write_u64::<BigEndian>(self.duration)?;
+        } else {
+            wtr.write_u32::<BigEndian>(self.creation_time.try_into()?)?;
+            wtr.write_u32::<BigEndian>(self.modification_time.try_into()?)?;
+            wtr.write_u32::<BigEndian>(self.timescale)?;
+            wtr.write_u32::<BigEndian>(self.duration.try_into()?)?;
+        }
+        wtr.write_u16::<BigEndian>(self.language)?;
+        wtr.write_u16::<BigEndian>(0)?;             // pre_defined
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for MdhdBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MdhdBox")
+            .field("timescale", &self.timescale)
+            .field("duration", &self.duration)
+            .field("language", &self.get_language())
+            .finish()
+    }
+}
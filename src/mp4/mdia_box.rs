@@ -1,34 +1,36 @@
-use std::{any::Any, fmt, fs::File, io::{self, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::Result;
 
-use super::{atom::Atom, box_list::BoxList};
+use super::{atom::Atom, box_header::BoxHeader, box_list::BoxList};
 
 pub struct MdiaBox {
-    pub box_list: BoxList,
-    pub payload_size: u64
+    pub header: BoxHeader,
+    pub box_list: BoxList
 }
 
 impl MdiaBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
-        let box_list = BoxList::read(rdr, len);
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let box_list = BoxList::read(rdr, len)?;
         Ok(MdiaBox {
-            box_list,
-            payload_size: len
+            header,
+            box_list
         })
     }
 }
 
 impl Atom for MdiaBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"mdia").unwrap();
-        self.box_list.write(wtr);
+    fn get_payload_size(&self) -> Result<u64> {
+        self.box_list.get_size()
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        self.box_list.write(wtr)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -1,13 +1,20 @@
 pub mod four_cc;
 pub mod atom;
+pub mod box_header;
 pub mod ftyp_box;
 pub mod unknown_box;
 pub mod mdat_box;
+pub mod mdat_segment;
+pub mod mdat_chunk;
+pub mod sample_data;
 pub mod h264_nalu_list;
 pub mod moov_box;
 pub mod mvhd_box;
 pub mod trak_box;
+pub mod tkhd_box;
 pub mod mdia_box;
+pub mod mdhd_box;
+pub mod hdlr_box;
 pub mod minf_box;
 pub mod stbl_box;
 pub mod stsd_box;
@@ -15,4 +22,16 @@ pub mod avc1_box;
 pub mod avcc_box;
 pub mod avc_decoder_configuration_record;
 pub mod stsz_box;
+pub mod stco_box;
+pub mod co64_box;
+pub mod stts_box;
+pub mod ctts_box;
+pub mod sample_time;
+pub mod stss_box;
+pub mod stsc_box;
+pub mod sample;
+pub mod sample_table;
+pub mod parameter_set_placement;
 pub mod box_list;
+pub mod mp4_file;
+pub mod h264_muxer;
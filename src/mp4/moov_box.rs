@@ -1,35 +1,37 @@
-use std::{any::Any, fmt, fs::File, io::{self, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::Result;
 
-use super::{atom::Atom, box_list::{self, BoxList}};
+use super::{atom::Atom, box_header::BoxHeader, box_list::{self, BoxList}};
 
 pub struct MoovBox {
-    pub box_list: BoxList,
-    pub payload_size: u64
+    pub header: BoxHeader,
+    pub box_list: BoxList
 }
 
 impl MoovBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
         // rdr.seek(io::SeekFrom::Current(i64::try_from(len).unwrap()));
-        let box_list = box_list::BoxList::read(rdr, len);
+        let box_list = box_list::BoxList::read(rdr, len)?;
         Ok(MoovBox {
-            box_list,
-            payload_size: len
+            header,
+            box_list
         })
     }
 }
 
 impl Atom for MoovBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"moov").unwrap();
-        self.box_list.write(wtr);
+    fn get_payload_size(&self) -> Result<u64> {
+        self.box_list.get_size()
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        self.box_list.write(wtr)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -0,0 +1,802 @@
+use std::{collections::HashMap, fmt, fs::File, io::{Cursor, Read, Seek, SeekFrom, Write}, path::Path, rc::Rc};
+
+use crate::{caption::{caption_cue::CaptionCue, caption_source::CaptionSource, cc_data::CcData, cea608_decoder::Cea608Decoder, cea708_decoder::Cea708Decoder, subtitle_format::SubtitleFormat}, error::{Error, Result}, h264::{access_unit::AccessUnit, delim_nalu::DelimNalu, hrd_access_unit::HrdAccessUnit, hrd_report::HrdReport, hrd_type::HrdType, hrd_verifier::HrdVerifier, idr_nalu::IdrNalu, nalu::Nalu, parameter_sets::ParameterSets, pps_nalu::PpsNalu, sei_nalu::SeiNalu, sei_payload::SeiPayload, sps_nalu::SpsNalu, sps_pps_provider::SpsPpsProvider, timecode::Timecode, timecode_decoder::TimecodeDecoder}};
+
+use super::{atom::Atom, avc_decoder_configuration_record::AvcDecoderConfigurationRecord, box_header::BoxHeader, box_list::BoxList, four_cc::FourCC, ftyp_box::FtypBox, h264_nalu_list::H264NaluList, hdlr_box::HdlrBox, mdat_box::MdatBox, mdat_chunk::MdatChunk, mdat_segment::MdatSegment, mdhd_box::MdhdBox, mdia_box::MdiaBox, minf_box::MinfBox, moov_box::MoovBox, mvhd_box::MvhdBox, parameter_set_placement::ParameterSetPlacement, sample::Sample, sample_data::SampleData, stbl_box::StblBox, stco_box::StcoBox, stsc_box::StscBox, stsd_box::StsdBox, stsz_box::StszBox, stts_box::SttsBox, tkhd_box::TkhdBox, trak_box::TrakBox, unknown_box::UnknownBox};
+
+pub struct Mp4File {
+    pub box_list: BoxList
+}
+
+impl Mp4File {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = File::open(path)?;
+        Mp4File::from_reader(&mut file)
+    }
+
+    pub fn from_reader(rdr: &mut (impl Read + Seek)) -> Result<Self> {
+        let box_list = BoxList::read_to_end(rdr)?;
+        let mut mp4_file = Mp4File {
+            box_list
+        };
+        mp4_file.read_samples(rdr)?;
+        Ok(mp4_file)
+    }
+
+    pub fn save(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = File::create(path)?;
+        self.write(&mut file)
+    }
+
+    pub fn write(&mut self, wtr: &mut dyn Write) -> Result<()> {
+        self.layout()?;
+        self.box_list.write(wtr)
+    }
+
+    // recomputes sample sizes and chunk offsets of every track for the current contents of the mdat boxes
+    pub fn layout(&mut self) -> Result<()> {
+        let mut chunk_sample_sizes: HashMap<usize, Vec<(usize, Vec<u32>)>> = HashMap::new();
+        for mdat in self.mdats() {
+            for chunk in mdat.chunks() {
+                let mut sample_sizes = vec![];
+                for sample in &chunk.samples {
+                    sample_sizes.push(u32::try_from(sample.get_size()?)?);
+                }
+                chunk_sample_sizes.entry(chunk.track_index).or_default().push((chunk.first_sample_index, sample_sizes));
+            }
+        }
+        for (track_index, trak) in self.tracks_mut().enumerate() {
+            let stsz = match (chunk_sample_sizes.get(&track_index), trak.stbl_mut().and_then(|stbl| stbl.box_list.find_mut::<StszBox>())) {
+                (Some(_), Some(stsz)) => stsz,
+                _ => continue
+            };
+            let mut track_sample_sizes = stsz.get_sample_sizes();
+            for (first_sample_index, sample_sizes) in &chunk_sample_sizes[&track_index] {
+                track_sample_sizes[*first_sample_index..*first_sample_index + sample_sizes.len()].copy_from_slice(sample_sizes);
+            }
+            stsz.set_sample_sizes(track_sample_sizes)?;
+        }
+
+        // switching a track from stco to co64 grows moov, which moves any mdat after it, so repeat until stable
+        let mut chunk_positions = self.get_chunk_positions()?;
+        loop {
+            for (track_index, trak) in self.tracks_mut().enumerate() {
+                if let Some(stbl) = trak.stbl_mut() {
+                    if let Some(chunk_offsets) = stbl.get_chunk_offsets() {
+                        // chunks outside of any mdat keep their offset
+                        let chunk_offsets = chunk_offsets.into_iter().enumerate()
+                            .map(|(chunk_index, offset)| *chunk_positions.get(&(track_index, chunk_index)).unwrap_or(&offset))
+                            .collect();
+                        stbl.set_chunk_offsets(chunk_offsets)?;
+                    }
+                }
+            }
+            let new_chunk_positions = self.get_chunk_positions()?;
+            if new_chunk_positions == chunk_positions {
+                break;
+            }
+            chunk_positions = new_chunk_positions;
+        }
+
+        let mdat_payload_offsets = self.get_mdat_payload_offsets()?;
+        for (mdat, payload_offset) in self.mdats_mut().zip(mdat_payload_offsets) {
+            mdat.payload_offset = payload_offset;
+            mdat.payload_size = mdat.get_payload_size()?;
+        }
+        Ok(())
+    }
+
+    // changes the NALU length prefix of an H.264 track, in avcC and in every sample
+    pub fn set_nalu_length_size(&mut self, track_index: usize, length_size: u8) -> Result<()> {
+        H264NaluList::check_length_size(length_size)?;
+        if self.tracks().nth(track_index).is_none() {
+            return Err(Error::spec_violation(format!("no track #{}", track_index)));
+        }
+        // check every sample first so that a failed conversion leaves the file untouched
+        for mdat in self.mdats() {
+            for chunk in mdat.chunks().filter(|chunk| chunk.track_index == track_index) {
+                for sample in &chunk.samples {
+                    if let SampleData::H264(nalu_list) = sample {
+                        nalu_list.check_units_fit(length_size)?;
+                    }
+                }
+            }
+        }
+        for mdat in self.mdats_mut() {
+            for chunk in mdat.chunks_mut().filter(|chunk| chunk.track_index == track_index) {
+                for sample in &mut chunk.samples {
+                    if let SampleData::H264(nalu_list) = sample {
+                        nalu_list.length_size = length_size;
+                    }
+                }
+            }
+        }
+        if let Some(trak) = self.tracks_mut().nth(track_index) {
+            for avc_configuration in trak.get_avc_configurations_mut().into_iter().flatten() {
+                avc_configuration.length_size_minus_one = length_size - 1;
+            }
+        }
+        Ok(())
+    }
+
+    // inserts a user_data_unregistered SEI into each sample of an H.264 track for which
+    // user_data returns a payload; sample sizes and chunk offsets follow on the next write
+    pub fn insert_user_data_unregistered(&mut self, track_index: usize, uuid_iso_iec_11578: [u8; 16], mut user_data: impl FnMut(usize, &H264NaluList) -> Option<Vec<u8>>) -> Result<()> {
+        if self.tracks().nth(track_index).is_none() {
+            return Err(Error::spec_violation(format!("no track #{}", track_index)));
+        }
+        // collect and check every payload first so that a failed insertion leaves the file untouched
+        let mut user_data_payloads: HashMap<usize, Vec<u8>> = HashMap::new();
+        for mdat in self.mdats() {
+            for chunk in mdat.chunks().filter(|chunk| chunk.track_index == track_index) {
+                for (sample_index, sample) in (chunk.first_sample_index..).zip(&chunk.samples) {
+                    let nalu_list = match sample {
+                        SampleData::H264(nalu_list) => nalu_list,
+                        SampleData::Raw(_) => return Err(Error::unsupported(format!("sample #{} of track #{} is not H.264", sample_index, track_index)))
+                    };
+                    if let Some(user_data_payload_byte) = user_data(sample_index, nalu_list) {
+                        if !nalu_list.units.iter().any(|unit| matches!(unit.get_nal_unit_type(), 1..=5)) {
+                            return Err(Error::spec_violation(format!("sample #{} of track #{} has no VCL NALU", sample_index, track_index)));
+                        }
+                        user_data_payloads.insert(sample_index, user_data_payload_byte);
+                    }
+                }
+            }
+        }
+        for mdat in self.mdats_mut() {
+            for chunk in mdat.chunks_mut().filter(|chunk| chunk.track_index == track_index) {
+                for (sample_index, sample) in (chunk.first_sample_index..).zip(&mut chunk.samples) {
+                    if let (SampleData::H264(nalu_list), Some(user_data_payload_byte)) = (sample, user_data_payloads.remove(&sample_index)) {
+                        nalu_list.insert_user_data_unregistered(uuid_iso_iec_11578, user_data_payload_byte)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_annex_b(&self, track_index: usize, path: impl AsRef<Path>, parameter_set_placement: ParameterSetPlacement) -> Result<()> {
+        let mut file = File::create(path)?;
+        self.write_annex_b(track_index, &mut file, parameter_set_placement)
+    }
+
+    // writes the samples of an H.264 track in decode order as an Annex B byte stream
+    pub fn write_annex_b(&self, track_index: usize, wtr: &mut dyn Write, parameter_set_placement: ParameterSetPlacement) -> Result<()> {
+        let trak = self.tracks().nth(track_index)
+            .ok_or_else(|| Error::spec_violation(format!("no track #{}", track_index)))?;
+        let sample_table = trak.get_sample_table()?;
+        let avc_configurations = trak.get_avc_configurations();
+
+        let mut chunks: Vec<&MdatChunk> = self.mdats()
+            .flat_map(|mdat| mdat.chunks())
+            .filter(|chunk| chunk.track_index == track_index)
+            .collect();
+        chunks.sort_by_key(|chunk| chunk.first_sample_index);
+        let sample_count: usize = chunks.iter().map(|chunk| chunk.samples.len()).sum();
+        if sample_count != sample_table.len() {
+            return Err(Error::spec_violation(format!("{} of {} samples of track #{} are outside of the mdat boxes", sample_table.len() - sample_count, sample_table.len(), track_index)));
+        }
+
+        let mut previous_description_index = None;
+        for chunk in chunks {
+            for (sample_index, sample) in (chunk.first_sample_index..).zip(&chunk.samples) {
+                let nalu_list = match sample {
+                    SampleData::H264(nalu_list) => nalu_list,
+                    SampleData::Raw(_) => return Err(Error::unsupported(format!("sample #{} of track #{} is not H.264", sample_index, track_index)))
+                };
+                let description_index = sample_table.samples[sample_index].description_index;
+                let avc_configuration = (description_index as usize).checked_sub(1)
+                    .and_then(|index| avc_configurations.get(index).copied().flatten())
+                    .ok_or_else(|| Error::spec_violation(format!("sample #{} of track #{} has no avcC", sample_index, track_index)))?;
+
+                let has_parameter_sets = nalu_list.units.iter().any(|unit| unit.as_any().is::<SpsNalu>())
+                    && nalu_list.units.iter().any(|unit| unit.as_any().is::<PpsNalu>());
+                let insert_parameter_sets = !has_parameter_sets && match parameter_set_placement {
+                    ParameterSetPlacement::EveryIdr => nalu_list.units.iter().any(|unit| unit.as_any().is::<IdrNalu>()),
+                    ParameterSetPlacement::StartOnly => previous_description_index != Some(description_index)
+                };
+                previous_description_index = Some(description_index);
+
+                // parameter sets go right after the access unit delimiter, if any
+                let insert_position = nalu_list.units.iter().take_while(|unit| unit.as_any().is::<DelimNalu>()).count();
+                for (unit_index, unit) in nalu_list.units.iter().enumerate() {
+                    if insert_parameter_sets && unit_index == insert_position {
+                        Mp4File::write_annex_b_parameter_sets(wtr, avc_configuration)?;
+                    }
+                    Mp4File::write_annex_b_unit(wtr, unit.as_ref(), nalu_list)?;
+                }
+                if insert_parameter_sets && insert_position == nalu_list.units.len() {
+                    Mp4File::write_annex_b_parameter_sets(wtr, avc_configuration)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_captions(&self, track_index: usize, source: CaptionSource, path: impl AsRef<Path>, format: SubtitleFormat) -> Result<()> {
+        let mut file = File::create(path)?;
+        self.write_captions(track_index, source, &mut file, format)
+    }
+
+    pub fn write_captions(&self, track_index: usize, source: CaptionSource, wtr: &mut dyn Write, format: SubtitleFormat) -> Result<()> {
+        let cues = self.get_captions(track_index, source)?;
+        format.write(&cues, wtr)
+    }
+
+    // decodes the closed captions carried in the cc_data of the SEI of an H.264 track, timed by
+    // the presentation time of each sample
+    pub fn get_captions(&self, track_index: usize, source: CaptionSource) -> Result<Vec<CaptionCue>> {
+        let trak = self.tracks().nth(track_index)
+            .ok_or_else(|| Error::spec_violation(format!("no track #{}", track_index)))?;
+        let timescale = trak.mdhd().ok_or_else(|| Error::spec_violation("trak without mdhd"))?.timescale;
+        if timescale == 0 {
+            return Err(Error::spec_violation(format!("track #{} has timescale 0", track_index)));
+        }
+        let sample_times = trak.get_sample_times()?;
+
+        // samples are stored in decoding order, captions follow presentation order
+        let mut samples: Vec<(i64, Vec<CcData>)> = vec![];
+        for chunk in self.mdats().flat_map(|mdat| mdat.chunks()).filter(|chunk| chunk.track_index == track_index) {
+            for (sample_index, sample) in (chunk.first_sample_index..).zip(&chunk.samples) {
+                let nalu_list = match sample {
+                    SampleData::H264(nalu_list) => nalu_list,
+                    SampleData::Raw(_) => return Err(Error::unsupported(format!("sample #{} of track #{} is not H.264", sample_index, track_index)))
+                };
+                let sample_time = sample_times.get(sample_index)
+                    .ok_or_else(|| Error::spec_violation(format!("sample #{} of track #{} has no timestamp", sample_index, track_index)))?;
+                let cc_data = nalu_list.units.iter()
+                    .filter_map(|unit| unit.as_any().downcast_ref::<SeiNalu>())
+                    .flat_map(CcData::read_sei)
+                    .filter(|cc_data| cc_data.cc_valid)
+                    .collect();
+                samples.push((sample_time.pts, cc_data));
+            }
+        }
+        samples.sort_by_key(|(pts, _)| *pts);
+
+        // milliseconds, with presentation times before zero shown at zero
+        let to_milliseconds = |pts: i64| u64::try_from(pts).unwrap_or(0) * 1000 / u64::from(timescale);
+        let end_time = match sample_times.as_slice() {
+            [.., previous, last] => to_milliseconds(samples.last().map_or(0, |(pts, _)| *pts) + i64::try_from(last.dts - previous.dts)?),
+            _ => to_milliseconds(samples.last().map_or(0, |(pts, _)| *pts))
+        };
+        match source {
+            CaptionSource::Cea608Field1 | CaptionSource::Cea608Field2 => {
+                let cc_type = if source == CaptionSource::Cea608Field1 { 0 } else { 1 };
+                let mut decoder = Cea608Decoder::new();
+                for (pts, cc_data) in &samples {
+                    for cc_data in cc_data.iter().filter(|cc_data| cc_data.cc_type == cc_type) {
+                        decoder.decode(cc_data.cc_data_1, cc_data.cc_data_2, to_milliseconds(*pts));
+                    }
+                }
+                Ok(decoder.finish(end_time))
+            },
+            CaptionSource::Cea708Service1 => {
+                let mut decoder = Cea708Decoder::new();
+                for (pts, cc_data) in &samples {
+                    for cc_data in cc_data.iter().filter(|cc_data| cc_data.cc_type >= 2) {
+                        decoder.decode(cc_data.cc_type, cc_data.cc_data_1, cc_data.cc_data_2, to_milliseconds(*pts));
+                    }
+                }
+                Ok(decoder.finish(end_time))
+            }
+        }
+    }
+
+    // SMPTE timecode of each sample of an H.264 track in decoding order, from the clock timestamps
+    // of its pic_timing SEI; None for samples without one
+    pub fn get_timecodes(&self, track_index: usize) -> Result<Vec<Option<Timecode>>> {
+        if self.tracks().nth(track_index).is_none() {
+            return Err(Error::spec_violation(format!("no track #{}", track_index)));
+        }
+        let mut chunks: Vec<&MdatChunk> = self.mdats()
+            .flat_map(|mdat| mdat.chunks())
+            .filter(|chunk| chunk.track_index == track_index)
+            .collect();
+        chunks.sort_by_key(|chunk| chunk.first_sample_index);
+
+        let mut timecodes = vec![];
+        let mut timecode_decoder = TimecodeDecoder::new();
+        for chunk in chunks {
+            for (sample_index, sample) in (chunk.first_sample_index..).zip(&chunk.samples) {
+                let nalu_list = match sample {
+                    SampleData::H264(nalu_list) => nalu_list,
+                    SampleData::Raw(_) => return Err(Error::unsupported(format!("sample #{} of track #{} is not H.264", sample_index, track_index)))
+                };
+                let pic_timing = nalu_list.units.iter()
+                    .filter_map(|unit| unit.as_any().downcast_ref::<SeiNalu>())
+                    .flat_map(|sei| &sei.sei_messages)
+                    .find_map(|sei_message| match &sei_message.payload {
+                        SeiPayload::PicTiming(pic_timing) => Some(pic_timing),
+                        _ => None
+                    });
+                timecodes.push(pic_timing.and_then(|pic_timing| timecode_decoder.decode(pic_timing)));
+            }
+        }
+        Ok(timecodes)
+    }
+
+    // runs the samples of an H.264 track through the HRD of the SPS of its first slice, with the
+    // sizes of the NAL units the HRD type counts and the buffering_period and pic_timing SEI
+    pub fn verify_hrd(&self, track_index: usize, hrd_type: HrdType, sched_sel_idx: usize) -> Result<HrdReport> {
+        if self.tracks().nth(track_index).is_none() {
+            return Err(Error::spec_violation(format!("no track #{}", track_index)));
+        }
+        let mut chunks: Vec<&MdatChunk> = self.mdats()
+            .flat_map(|mdat| mdat.chunks())
+            .filter(|chunk| chunk.track_index == track_index)
+            .collect();
+        chunks.sort_by_key(|chunk| chunk.first_sample_index);
+
+        let mut sps = None;
+        let mut access_units = vec![];
+        for chunk in chunks {
+            for (sample_index, sample) in (chunk.first_sample_index..).zip(&chunk.samples) {
+                let nalu_list = match sample {
+                    SampleData::H264(nalu_list) => nalu_list,
+                    SampleData::Raw(_) => return Err(Error::unsupported(format!("sample #{} of track #{} is not H.264", sample_index, track_index)))
+                };
+                if sps.is_none() {
+                    sps = nalu_list.units.iter()
+                        .find_map(|unit| AccessUnit::get_slice(unit.as_ref()))
+                        .and_then(|(_, slice_header)| nalu_list.get_pps(slice_header.pic_parameter_set_id))
+                        .and_then(|pps| nalu_list.get_sps(pps.seq_parameter_set_id));
+                }
+                // the VCL HRD sees VCL and filler data NAL units only
+                let mut size = 0;
+                nalu_list.for_each_unit_bytes(|unit, bytes| {
+                    if hrd_type == HrdType::Nal || matches!(unit.get_nal_unit_type(), 1..=5 | 12) {
+                        size += 8 * bytes.len() as u64;
+                    }
+                    Ok(())
+                })?;
+                let sei_payloads = nalu_list.units.iter()
+                    .filter_map(|unit| unit.as_any().downcast_ref::<SeiNalu>())
+                    .flat_map(|sei| &sei.sei_messages)
+                    .map(|sei_message| &sei_message.payload);
+                let mut access_unit = HrdAccessUnit {
+                    size,
+                    buffering_period: None,
+                    pic_timing: None,
+                    arrival_time: None
+                };
+                for payload in sei_payloads {
+                    match payload {
+                        SeiPayload::BufferingPeriod(buffering_period) => access_unit.buffering_period = Some(buffering_period.clone()),
+                        SeiPayload::PicTiming(pic_timing) => access_unit.pic_timing = Some(pic_timing.clone()),
+                        _ => ()
+                    }
+                }
+                access_units.push(access_unit);
+            }
+        }
+        let sps = sps.ok_or_else(|| Error::spec_violation(format!("track #{} has no slice with parameter sets", track_index)))?;
+        HrdVerifier::new(hrd_type, sched_sel_idx).verify(sps, &access_units)
+    }
+
+    pub fn get_timecode_track_index(&self) -> Option<usize> {
+        self.tracks().position(|trak| trak.hdlr().is_some_and(|hdlr| hdlr.handler_type == FourCC::new(*b"tmcd")))
+    }
+
+    // adds a tmcd track for an H.264 track from the timecodes of its pic_timing SEI, as one sample
+    // with the frame number of the first frame in presentation order; returns the new track index
+    pub fn add_timecode_track(&mut self, track_index: usize) -> Result<usize> {
+        if self.get_timecode_track_index().is_some() {
+            return Err(Error::unsupported("file already has a tmcd track"));
+        }
+        let timecodes = self.get_timecodes(track_index)?;
+        let trak = self.tracks().nth(track_index)
+            .ok_or_else(|| Error::spec_violation(format!("no track #{}", track_index)))?;
+        let mdhd = trak.mdhd().ok_or_else(|| Error::spec_violation("trak without mdhd"))?;
+        let tkhd = trak.box_list.find::<TkhdBox>().ok_or_else(|| Error::spec_violation("trak without tkhd"))?;
+        let sample_times = trak.get_sample_times()?;
+
+        let frame_duration = match sample_times.as_slice() {
+            [first, second, ..] => u32::try_from(second.dts - first.dts)?,
+            _ => u32::try_from(mdhd.duration)?
+        };
+        if frame_duration == 0 {
+            return Err(Error::unsupported(format!("track #{} has samples of duration 0", track_index)));
+        }
+        let frames_per_second = (mdhd.timescale + frame_duration / 2) / frame_duration;
+        let number_of_frames = u8::try_from(frames_per_second)
+            .map_err(|_| Error::unsupported(format!("timecode at {} frames per second", frames_per_second)))?;
+
+        // count back from the first presented frame with a timecode to the first presented frame
+        let mut presentation_order: Vec<usize> = (0..timecodes.len()).collect();
+        presentation_order.sort_by_key(|&sample_index| sample_times.get(sample_index).map(|sample_time| sample_time.pts));
+        let (presentation_index, timecode) = presentation_order.iter()
+            .enumerate()
+            .find_map(|(presentation_index, &sample_index)| timecodes[sample_index].map(|timecode| (presentation_index, timecode)))
+            .ok_or_else(|| Error::spec_violation(format!("track #{} has no clock timestamps", track_index)))?;
+        let frame_number = timecode.get_frame_number(frames_per_second).saturating_sub(presentation_index as u64);
+
+        // next_track_ID of all ones, or one already taken, means an unused ID has to be searched for
+        let track_ids: Vec<u32> = self.tracks()
+            .filter_map(|trak| trak.box_list.find::<TkhdBox>())
+            .map(|tkhd| tkhd.track_id)
+            .collect();
+        let next_track_id = self.mvhd().ok_or_else(|| Error::spec_violation("no mvhd"))?.next_track_id;
+        let track_id = if next_track_id == 0 || next_track_id == u32::MAX || track_ids.contains(&next_track_id) {
+            match track_ids.iter().max() {
+                Some(max_track_id) => max_track_id.checked_add(1).ok_or_else(|| Error::unsupported("no unused track ID"))?,
+                None => 1
+            }
+        } else {
+            next_track_id
+        };
+        let timecode_trak = Mp4File::get_timecode_trak(track_id, tkhd, mdhd, frame_duration, number_of_frames, timecode.drop_frame);
+
+        let moov = self.moov_mut().ok_or_else(|| Error::spec_violation("no moov"))?;
+        if let Some(mvhd) = moov.box_list.find_mut::<MvhdBox>() {
+            mvhd.next_track_id = track_id.saturating_add(1);
+        }
+        // the media track refers to the timecode track through a tref box
+        let trak = moov.box_list.find_all_mut::<TrakBox>().nth(track_index)
+            .ok_or_else(|| Error::spec_violation(format!("no track #{}", track_index)))?;
+        let mut track_reference = vec![0, 0, 0, 12, b't', b'm', b'c', b'd'];
+        track_reference.extend_from_slice(&track_id.to_be_bytes());
+        let is_tref = |atom: &UnknownBox| atom.header.boxtype == FourCC::new(*b"tref");
+        if trak.box_list.find_all::<UnknownBox>().any(is_tref) {
+            trak.box_list.find_all_mut::<UnknownBox>()
+                .filter(|atom| is_tref(atom))
+                .for_each(|tref| tref.remaining.extend(&track_reference));
+        } else {
+            trak.box_list.boxes.push(Box::new(UnknownBox {
+                header: BoxHeader::new(b"tref"),
+                remaining: track_reference
+            }));
+        }
+        let timecode_track_index = moov.box_list.find_all::<TrakBox>().count();
+        moov.box_list.boxes.push(Box::new(timecode_trak));
+
+        let mdat = self.mdats_mut().last().ok_or_else(|| Error::spec_violation("no mdat"))?;
+        mdat.segments.push(MdatSegment::Chunk(MdatChunk {
+            track_index: timecode_track_index,
+            chunk_index: 0,
+            first_sample_index: 0,
+            samples: vec![SampleData::Raw(u32::try_from(frame_number)?.to_be_bytes().to_vec())]
+        }));
+        self.layout()?;
+        Ok(timecode_track_index)
+    }
+
+    pub fn ftyp(&self) -> Option<&FtypBox> {
+        self.box_list.find::<FtypBox>()
+    }
+
+    pub fn moov(&self) -> Option<&MoovBox> {
+        self.box_list.find::<MoovBox>()
+    }
+
+    pub fn moov_mut(&mut self) -> Option<&mut MoovBox> {
+        self.box_list.find_mut::<MoovBox>()
+    }
+
+    pub fn mvhd(&self) -> Option<&MvhdBox> {
+        self.moov()?.box_list.find::<MvhdBox>()
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = &TrakBox> {
+        self.moov().into_iter().flat_map(|moov| moov.box_list.find_all::<TrakBox>())
+    }
+
+    pub fn tracks_mut(&mut self) -> impl Iterator<Item = &mut TrakBox> {
+        self.moov_mut().into_iter().flat_map(|moov| moov.box_list.find_all_mut::<TrakBox>())
+    }
+
+    pub fn mdats(&self) -> impl Iterator<Item = &MdatBox> {
+        self.box_list.find_all::<MdatBox>()
+    }
+
+    pub fn mdats_mut(&mut self) -> impl Iterator<Item = &mut MdatBox> {
+        self.box_list.find_all_mut::<MdatBox>()
+    }
+
+    // splits the mdat payloads into the chunks described by the sample tables, parsing samples of known codecs
+    // reads the bytes of each chunk from the sample tables into the mdat containing it, and the
+    // bytes between chunks as opaque segments, without holding a whole mdat payload in memory
+    fn read_samples(&mut self, rdr: &mut (impl Read + Seek)) -> Result<()> {
+        let payload_ranges: Vec<(u64, u64)> = self.mdats()
+            .map(|mdat| (mdat.payload_offset, mdat.payload_offset + mdat.payload_size))
+            .collect();
+
+        // chunks claimed in each mdat, by absolute offset
+        let mut mdat_chunks: Vec<Vec<(u64, u64, MdatChunk)>> = payload_ranges.iter().map(|_| vec![]).collect();
+        for (track_index, trak) in self.tracks().enumerate() {
+            let sample_table = match trak.get_sample_table() {
+                Ok(sample_table) => sample_table,
+                Err(_) => continue      // a track without a usable sample table claims nothing
+            };
+            let avc_configurations = trak.get_avc_configurations();
+            let mut parameter_sets: HashMap<u32, Rc<ParameterSets>> = HashMap::new();
+
+            let mut sample_index = 0;
+            while sample_index < sample_table.len() {
+                let first_sample = &sample_table.samples[sample_index];
+                let chunk_samples: Vec<&Sample> = sample_table.iter()
+                    .skip(sample_index)
+                    .take_while(|sample| sample.chunk_index == first_sample.chunk_index)
+                    .collect();
+                let chunk_start = first_sample.offset;
+                let chunk_end = chunk_start + chunk_samples.iter().map(|sample| u64::from(sample.size)).sum::<u64>();
+
+                let containing_mdat = payload_ranges.iter()
+                    .position(|(payload_start, payload_end)| *payload_start <= chunk_start && chunk_end <= *payload_end);
+                if let Some(mdat_index) = containing_mdat {
+                    let chunk_bytes = Mp4File::read_bytes(rdr, chunk_start, chunk_end - chunk_start)
+                        .map_err(|e| e.at_offset(chunk_start).in_box("mdat"))?;
+                    let mut samples = vec![];
+                    for sample in &chunk_samples {
+                        let start = usize::try_from(sample.offset - chunk_start)?;
+                        let bytes = &chunk_bytes[start..start + sample.size as usize];
+                        let avc_configuration = (sample.description_index as usize).checked_sub(1).and_then(|index| avc_configurations.get(index));
+                        let sample_data = match avc_configuration {
+                            Some(Some(avc_configuration)) => {
+                                let parameter_sets = parameter_sets.entry(sample.description_index)
+                                    .or_insert_with(|| Rc::new(avc_configuration.get_parameter_sets()));
+                                let length_size = avc_configuration.length_size_minus_one + 1;
+                                let nalu_list = H264NaluList::read(&mut Cursor::new(bytes), bytes.len() as u64, length_size, parameter_sets)
+                                    .map_err(|e| e.at_offset(sample.offset).in_box("mdat"))?;
+                                SampleData::H264(nalu_list)
+                            },
+                            _ => SampleData::Raw(bytes.to_vec())
+                        };
+                        samples.push(sample_data);
+                    }
+                    mdat_chunks[mdat_index].push((chunk_start, chunk_end, MdatChunk {
+                        track_index,
+                        chunk_index: first_sample.chunk_index,
+                        first_sample_index: first_sample.index,
+                        samples
+                    }));
+                }
+                sample_index += chunk_samples.len();
+            }
+        }
+
+        for ((mdat, (payload_start, payload_end)), mut chunks) in self.mdats_mut().zip(payload_ranges).zip(mdat_chunks) {
+            chunks.sort_by_key(|(start, _, _)| *start);
+            let mut segments = vec![];
+            let mut position = payload_start;
+            for (start, end, chunk) in chunks {
+                if start < position {
+                    return Err(Error::spec_violation(format!("chunk {} of track #{} overlaps another chunk", chunk.chunk_index, chunk.track_index)).in_box("mdat"));
+                }
+                if start > position {
+                    segments.push(MdatSegment::Opaque(Mp4File::read_bytes(rdr, position, start - position)?));
+                }
+                segments.push(MdatSegment::Chunk(chunk));
+                position = end;
+            }
+            if position < payload_end || segments.is_empty() {
+                segments.push(MdatSegment::Opaque(Mp4File::read_bytes(rdr, position, payload_end - position)?));
+            }
+            mdat.segments = segments;
+        }
+        Ok(())
+    }
+
+    fn read_bytes(rdr: &mut (impl Read + Seek), offset: u64, size: u64) -> Result<Vec<u8>> {
+        rdr.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0u8; size.try_into()?];
+        rdr.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // a tmcd track with a single sample spanning the media track
+    fn get_timecode_trak(track_id: u32, tkhd: &TkhdBox, mdhd: &MdhdBox, frame_duration: u32, number_of_frames: u8, drop_frame: bool) -> TrakBox {
+        // 24-hour maximum, plus drop frame
+        let tmcd_flags = 0x2 | u32::from(drop_frame);
+        let mut tmcd_remaining = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0];    // reserved, data_reference_index 1, reserved
+        tmcd_remaining.extend_from_slice(&tmcd_flags.to_be_bytes());
+        tmcd_remaining.extend_from_slice(&mdhd.timescale.to_be_bytes());
+        tmcd_remaining.extend_from_slice(&frame_duration.to_be_bytes());
+        tmcd_remaining.extend_from_slice(&[number_of_frames, 0]);
+
+        let stbl = StblBox {
+            header: BoxHeader::new(b"stbl"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(StsdBox {
+                        header: BoxHeader::new(b"stsd"),
+                        version: 0,
+                        flags: [0; 3],
+                        box_list: BoxList {
+                            boxes: vec![Box::new(UnknownBox {
+                                header: BoxHeader::new(b"tmcd"),
+                                remaining: tmcd_remaining
+                            })]
+                        }
+                    }),
+                    Box::new(SttsBox {
+                        header: BoxHeader::new(b"stts"),
+                        version: 0,
+                        flags: [0; 3],
+                        sample_counts: vec![1],
+                        sample_deltas: vec![u32::try_from(mdhd.duration).unwrap_or(u32::MAX)]
+                    }),
+                    Box::new(StscBox {
+                        header: BoxHeader::new(b"stsc"),
+                        version: 0,
+                        flags: [0; 3],
+                        first_chunks: vec![1],
+                        samples_per_chunks: vec![1],
+                        sample_description_indices: vec![1]
+                    }),
+                    Box::new(StszBox {
+                        header: BoxHeader::new(b"stsz"),
+                        version: 0,
+                        flags: [0; 3],
+                        sample_size: 0,
+                        sample_count: 1,
+                        entry_sizes: vec![4]
+                    }),
+                    Box::new(StcoBox {
+                        header: BoxHeader::new(b"stco"),
+                        version: 0,
+                        flags: [0; 3],
+                        chunk_offsets: vec![0]
+                    })
+                ]
+            }
+        };
+        let minf = MinfBox {
+            header: BoxHeader::new(b"minf"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(UnknownBox {
+                        header: BoxHeader::new(b"nmhd"),
+                        remaining: vec![0, 0, 0, 0]
+                    }),
+                    Box::new(UnknownBox {
+                        header: BoxHeader::new(b"dinf"),
+                        remaining: vec![
+                            0, 0, 0, 28, b'd', b'r', b'e', b'f', 0, 0, 0, 0, 0, 0, 0, 1,
+                            0, 0, 0, 12, b'u', b'r', b'l', b' ', 0, 0, 0, 1     // media data in the same file
+                        ]
+                    }),
+                    Box::new(stbl)
+                ]
+            }
+        };
+        let mdia = MdiaBox {
+            header: BoxHeader::new(b"mdia"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(MdhdBox {
+                        header: BoxHeader::new(b"mdhd"),
+                        version: mdhd.version,
+                        flags: [0; 3],
+                        creation_time: mdhd.creation_time,
+                        modification_time: mdhd.modification_time,
+                        timescale: mdhd.timescale,
+                        duration: mdhd.duration,
+                        language: mdhd.language
+                    }),
+                    Box::new(HdlrBox {
+                        header: BoxHeader::new(b"hdlr"),
+                        version: 0,
+                        flags: [0; 3],
+                        handler_type: FourCC::new(*b"tmcd"),
+                        name: b"TimeCodeHandler\0".to_vec()
+                    }),
+                    Box::new(minf)
+                ]
+            }
+        };
+        TrakBox {
+            header: BoxHeader::new(b"trak"),
+            box_list: BoxList {
+                boxes: vec![
+                    Box::new(TkhdBox {
+                        header: BoxHeader::new(b"tkhd"),
+                        version: tkhd.version,
+                        flags: [0, 0, 3],   // enabled and in movie
+                        creation_time: tkhd.creation_time,
+                        modification_time: tkhd.modification_time,
+                        track_id,
+                        duration: tkhd.duration,
+                        layer: 0,
+                        alternate_group: 0,
+                        volume: 0,
+                        matrix: tkhd.matrix,
+                        width: 0,
+                        height: 0
+                    }),
+                    Box::new(mdia)
+                ]
+            }
+        }
+    }
+
+    fn write_annex_b_parameter_sets(wtr: &mut dyn Write, avc_configuration: &AvcDecoderConfigurationRecord) -> Result<()> {
+        for sps in &avc_configuration.sequence_parameter_set_nal_units {
+            Mp4File::write_annex_b_unit(wtr, sps, avc_configuration)?;
+        }
+        for pps in &avc_configuration.picture_parameter_set_nal_units {
+            Mp4File::write_annex_b_unit(wtr, pps, avc_configuration)?;
+        }
+        Ok(())
+    }
+
+    fn write_annex_b_unit(wtr: &mut dyn Write, unit: &dyn Nalu, sps_pps_provider: &dyn SpsPpsProvider) -> Result<()> {
+        wtr.write_all(&[0, 0, 0, 1])?;
+        wtr.write_all(&unit.to_bytes(sps_pps_provider)?)?;
+        Ok(())
+    }
+
+    // absolute offset of every chunk in the mdat boxes, by track index and chunk index
+    fn get_chunk_positions(&self) -> Result<HashMap<(usize, usize), u64>> {
+        let mut chunk_positions = HashMap::new();
+        for (mdat, payload_offset) in self.mdats().zip(self.get_mdat_payload_offsets()?) {
+            let mut position = payload_offset;
+            for segment in &mdat.segments {
+                if let MdatSegment::Chunk(chunk) = segment {
+                    chunk_positions.insert((chunk.track_index, chunk.chunk_index), position);
+                }
+                position += segment.get_size()?;
+            }
+        }
+        Ok(chunk_positions)
+    }
+
+    fn get_mdat_payload_offsets(&self) -> Result<Vec<u64>> {
+        let mut payload_offsets = vec![];
+        let mut position = 0;
+        for atom in &self.box_list.boxes {
+            if let Some(mdat) = atom.as_any().downcast_ref::<MdatBox>() {
+                payload_offsets.push(position + mdat.header.get_size(mdat.get_payload_size()?));
+            }
+            position += atom.get_size()?;
+        }
+        Ok(payload_offsets)
+    }
+}
+
+impl fmt::Debug for Mp4File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mp4File")
+            .field("box_list", &self.box_list)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{mp4::{h264_muxer::H264Muxer, mvhd_box::MvhdBox, tkhd_box::TkhdBox}, test_util::{BASELINE_SPS, to_annex_b_unit}};
+
+    use super::Mp4File;
+
+    // one IDR picture with a pic_timing SEI of timecode 01:56:34;12
+    fn get_mp4_file() -> Mp4File {
+        let mut stream = vec![0, 0, 0, 1, 0x67];
+        stream.extend(BASELINE_SPS);
+        stream.extend(to_annex_b_unit(0x68, "1 1 0 0 1 1 1 0 00 1 1 1 1 0 0"));
+        stream.extend(to_annex_b_unit(0x06, "00000001 00001001 0000 1 00 0 00100 1 0 1 00001100 100010 111000 00001 111111111111111111111110 1000000"));
+        stream.extend(to_annex_b_unit(0x65, "1 0001000 1 0000 1 0 0 1 010 10101100"));
+        H264Muxer::new(30000, 1001).mux_annex_b(&mut Cursor::new(stream)).unwrap()
+    }
+
+    fn get_track_ids(mp4_file: &Mp4File) -> (Vec<u32>, u32) {
+        let track_ids = mp4_file.tracks().map(|trak| trak.box_list.find::<TkhdBox>().unwrap().track_id).collect();
+        (track_ids, mp4_file.mvhd().unwrap().next_track_id)
+    }
+
+    #[test]
+    fn add_timecode_track_with_next_track_id() {
+        let mut mp4_file = get_mp4_file();
+        mp4_file.moov_mut().unwrap().box_list.find_mut::<MvhdBox>().unwrap().next_track_id = 5;
+        assert_eq!(mp4_file.add_timecode_track(0).unwrap(), 1);
+        assert_eq!(get_track_ids(&mp4_file), (vec![1, 5], 6));
+        assert_eq!(mp4_file.get_timecode_track_index(), Some(1));
+
+        // a next_track_ID already taken is not used
+        let mut mp4_file = get_mp4_file();
+        mp4_file.moov_mut().unwrap().box_list.find_mut::<MvhdBox>().unwrap().next_track_id = 1;
+        mp4_file.add_timecode_track(0).unwrap();
+        assert_eq!(get_track_ids(&mp4_file), (vec![1, 2], 3));
+    }
+}
@@ -1,26 +1,30 @@
-use std::{any::Any, fmt, fs::File, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use super::atom::Atom;
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
 
 pub struct MvhdBox {
+    pub header: BoxHeader,
     pub version: u8,
     pub flags: [u8; 3],
     pub creation_time: u64,
     pub modification_time: u64,
     pub timescale: u32,
     pub duration: u64,
-    pub remaining: Vec<u8>,
-    pub payload_size: u64
+    pub rate: i32,              // 16.16 fixed point
+    pub volume: i16,            // 8.8 fixed point
+    pub matrix: [i32; 9],
+    pub next_track_id: u32
 }
 
 impl MvhdBox {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u64) -> io::Result<Self> {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
         let version = rdr.read_u8()?;
         let mut flags: [u8; 3] = [0; 3];
-        rdr.read(&mut flags).unwrap();
+        rdr.read_exact(&mut flags)?;
 
-        let mut remaining_len = len - 4;
         let creation_time: u64;
         let modification_time: u64;
         let timescale: u32;
@@ -30,53 +34,73 @@ impl MvhdBox {
             modification_time = rdr.read_u64::<BigEndian>()?;
             timescale = rdr.read_u32::<BigEndian>()?;
             duration = rdr.read_u64::<BigEndian>()?;
-            remaining_len -= 28;
         } else {
             creation_time = rdr.read_u32::<BigEndian>()?.into();
             modification_time = rdr.read_u32::<BigEndian>()?.into();
             timescale = rdr.read_u32::<BigEndian>()?;
             duration = rdr.read_u32::<BigEndian>()?.into();
-            remaining_len -= 16
         }
-
-        let mut remaining = vec![0u8; remaining_len.try_into().unwrap()];
-        rdr.read_exact(&mut remaining).unwrap();
+        let rate = rdr.read_i32::<BigEndian>()?;
+        let volume = rdr.read_i16::<BigEndian>()?;
+        let _reserved = rdr.read_u16::<BigEndian>()?;
+        let _reserved = rdr.read_u64::<BigEndian>()?;
+        let mut matrix: [i32; 9] = [0; 9];
+        for value in &mut matrix {
+            *value = rdr.read_i32::<BigEndian>()?;
+        }
+        let mut _pre_defined: [u8; 24] = [0; 24];
+        rdr.read_exact(&mut _pre_defined)?;
+        let next_track_id = rdr.read_u32::<BigEndian>()?;
         Ok(MvhdBox {
+            header,
             version,
             flags,
             creation_time,
             modification_time,
             timescale,
             duration,
-            remaining,
-            payload_size: len
+            rate,
+            volume,
+            matrix,
+            next_track_id
         })
     }
 }
 
 impl Atom for MvhdBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"mvhd").unwrap();
-        wtr.write_u8(self.version).unwrap();
-        wtr.write_all(&self.flags).unwrap();
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(4 + if self.version == 1 { 28 } else { 16 } + 80)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
         if self.version == 1 {
-            wtr.write_u64::<BigEndian>(self.creation_time).unwrap();
-            wtr.write_u64::<BigEndian>(self.modification_time).unwrap();
-            wtr.write_u32::<BigEndian>(self.timescale).unwrap();
-            wtr.write_u64::<BigEndian>(self.duration).unwrap();
+            wtr.write_u64::<BigEndian>(self.creation_time)?;
+            wtr.write_u64::<BigEndian>(self.modification_time)?;
+            wtr.write_u32::<BigEndian>(self.timescale)?;
+            wtr.write_u64::<BigEndian>(self.duration)?;
         } else {
-            wtr.write_u32::<BigEndian>(self.creation_time.try_into().unwrap()).unwrap();
-            wtr.write_u32::<BigEndian>(self.modification_time.try_into().unwrap()).unwrap();
-            wtr.write_u32::<BigEndian>(self.timescale).unwrap();
-            wtr.write_u32::<BigEndian>(self.duration.try_into().unwrap()).unwrap();
+            wtr.write_u32::<BigEndian>(self.creation_time.try_into()?)?;
+            wtr.write_u32::<BigEndian>(self.modification_time.try_into()?)?;
+            wtr.write_u32::<BigEndian>(self.timescale)?;
+            wtr.write_u32::<BigEndian>(self.duration.try_into()?)?;
+        }
+        wtr.write_i32::<BigEndian>(self.rate)?;
+        wtr.write_i16::<BigEndian>(self.volume)?;
+        wtr.write_u16::<BigEndian>(0)?;             // reserved
+        wtr.write_u64::<BigEndian>(0)?;             // reserved
+        for value in &self.matrix {
+            wtr.write_i32::<BigEndian>(*value)?;
         }
-        wtr.write_all(&self.remaining).unwrap();
+        wtr.write_all(&[0; 24])?;                   // pre_defined
+        wtr.write_u32::<BigEndian>(self.next_track_id)?;
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -95,6 +119,7 @@ impl fmt::Debug for MvhdBox {
         .field("modification_time", &self.modification_time)
         .field("timescale", &self.timescale)
         .field("duration", &self.duration)
+        .field("next_track_id", &self.next_track_id)
         .finish()
     }
 }
@@ -0,0 +1,6 @@
+// where the SPS and PPS from avcC are inserted when extracting an Annex B stream from a track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterSetPlacement {
+    EveryIdr,   // before every IDR picture that does not carry its own SPS and PPS
+    StartOnly   // at the start of the stream, and again whenever the sample entry changes
+}
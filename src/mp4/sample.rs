@@ -0,0 +1,11 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub index: usize,               // 0-based, sample numbers in the boxes are 1-based
+    pub offset: u64,                // absolute offset in the file
+    pub size: u32,
+    pub dts: u64,
+    pub pts: i64,
+    pub is_sync: bool,
+    pub chunk_index: usize,         // 0-based index into the chunk offsets
+    pub description_index: u32      // 1-based index into stsd
+}
@@ -0,0 +1,35 @@
+use std::{fmt, io::Write};
+
+use crate::error::Result;
+
+use super::h264_nalu_list::H264NaluList;
+
+pub enum SampleData {
+    H264(H264NaluList),
+    Raw(Vec<u8>)        // sample of a codec we do not parse
+}
+
+impl SampleData {
+    pub fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        match self {
+            SampleData::H264(nalu_list) => nalu_list.write(wtr),
+            SampleData::Raw(bytes) => Ok(wtr.write_all(bytes)?)
+        }
+    }
+
+    pub fn get_size(&self) -> Result<u64> {
+        match self {
+            SampleData::H264(nalu_list) => nalu_list.get_size(),
+            SampleData::Raw(bytes) => Ok(bytes.len() as u64)
+        }
+    }
+}
+
+impl fmt::Debug for SampleData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleData::H264(nalu_list) => f.debug_tuple("H264").field(nalu_list).finish(),
+            SampleData::Raw(bytes) => write!(f, "Raw({} bytes)", bytes.len())
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::slice;
+
+use crate::error::{Error, Result};
+
+use super::{sample::Sample, stbl_box::StblBox, stsc_box::StscBox, stss_box::StssBox, stsz_box::StszBox, trak_box::TrakBox};
+
+// samples of a track as described by its stsz, stsc, stco/co64, stts, ctts and stss boxes
+pub struct SampleTable {
+    pub samples: Vec<Sample>
+}
+
+impl SampleTable {
+    pub fn new(trak: &TrakBox) -> Result<Self> {
+        let stbl = trak.stbl().ok_or_else(|| Error::spec_violation("trak without stbl"))?;
+        let sample_sizes = stbl.box_list.find::<StszBox>()
+            .ok_or_else(|| Error::spec_violation("stbl without stsz"))?
+            .get_sample_sizes();
+        let sample_times = trak.get_sample_times()?;
+        if sample_times.len() != sample_sizes.len() {
+            return Err(Error::spec_violation(format!("stts covers {} samples but stsz has {}", sample_times.len(), sample_sizes.len())));
+        }
+        let sync_sample_numbers = stbl.box_list.find::<StssBox>().map(|stss| &stss.sample_numbers);
+
+        let mut samples = Vec::with_capacity(sample_sizes.len());
+        for (chunk_index, (chunk_offset, samples_per_chunk, description_index)) in SampleTable::get_chunks(stbl)?.into_iter().enumerate() {
+            let mut offset = chunk_offset;
+            for _i in 0..samples_per_chunk {
+                let index = samples.len();
+                let size = *sample_sizes.get(index)
+                    .ok_or_else(|| Error::spec_violation("stsc describes more samples than stsz"))?;
+                let is_sync = match sync_sample_numbers {
+                    Some(sample_numbers) => sample_numbers.binary_search(&u32::try_from(index + 1)?).is_ok(),
+                    None => true    // no stss means every sample is a sync sample
+                };
+                samples.push(Sample {
+                    index,
+                    offset,
+                    size,
+                    dts: sample_times[index].dts,
+                    pts: sample_times[index].pts,
+                    is_sync,
+                    chunk_index,
+                    description_index
+                });
+                offset += u64::from(size);
+            }
+        }
+        if samples.len() != sample_sizes.len() {
+            return Err(Error::spec_violation(format!("stsc describes {} samples but stsz has {}", samples.len(), sample_sizes.len())));
+        }
+
+        Ok(SampleTable {
+            samples
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Sample> {
+        self.samples.get(index)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Sample> {
+        self.samples.iter()
+    }
+
+    // offset, number of samples and sample description index of each chunk
+    fn get_chunks(stbl: &StblBox) -> Result<Vec<(u64, u32, u32)>> {
+        let chunk_offsets = stbl.get_chunk_offsets()
+            .ok_or_else(|| Error::spec_violation("stbl has neither stco nor co64"))?;
+        let stsc = stbl.box_list.find::<StscBox>()
+            .ok_or_else(|| Error::spec_violation("stbl without stsc"))?;
+        let chunk_layout = stsc.get_chunk_layout(chunk_offsets.len())?;
+        Ok(chunk_offsets.into_iter()
+            .zip(chunk_layout)
+            .map(|(chunk_offset, (samples_per_chunk, description_index))| (chunk_offset, samples_per_chunk, description_index))
+            .collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a SampleTable {
+    type Item = &'a Sample;
+    type IntoIter = slice::Iter<'a, Sample>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.samples.iter()
+    }
+}
@@ -0,0 +1,6 @@
+// timestamps of a sample in media timescale units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleTime {
+    pub dts: u64,
+    pub pts: i64
+}
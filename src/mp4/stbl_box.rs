@@ -1,34 +1,66 @@
-use std::{any::Any, fmt, fs::File, io::{self, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::{Error, Result};
 
-use super::{atom::Atom, box_list::BoxList};
+use super::{atom::Atom, box_header::BoxHeader, box_list::BoxList, co64_box::Co64Box, stco_box::StcoBox};
 
 pub struct StblBox {
-    pub box_list: BoxList,
-    pub payload_size: u64
+    pub header: BoxHeader,
+    pub box_list: BoxList
 }
 
 impl StblBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
-        let box_list = BoxList::read(rdr, len);
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let box_list = BoxList::read(rdr, len)?;
         Ok(StblBox {
-            box_list,
-            payload_size: len
+            header,
+            box_list
         })
     }
+
+    pub fn get_chunk_offsets(&self) -> Option<Vec<u64>> {
+        if let Some(stco) = self.box_list.find::<StcoBox>() {
+            Some(stco.chunk_offsets.iter().map(|&offset| offset.into()).collect())
+        } else {
+            self.box_list.find::<Co64Box>().map(|co64| co64.chunk_offsets.clone())
+        }
+    }
+
+    // stco is replaced by co64 in place when an offset no longer fits in 32 bits
+    pub fn set_chunk_offsets(&mut self, chunk_offsets: Vec<u64>) -> Result<()> {
+        if let Some(co64) = self.box_list.find_mut::<Co64Box>() {
+            co64.chunk_offsets = chunk_offsets;
+            return Ok(());
+        }
+        let index = self.box_list.boxes.iter().position(|atom| atom.as_any().is::<StcoBox>())
+            .ok_or_else(|| Error::spec_violation("stbl has neither stco nor co64"))?;
+        if chunk_offsets.iter().all(|&offset| offset <= u32::MAX.into()) {
+            let stco = self.box_list.boxes[index].as_any_mut().downcast_mut::<StcoBox>().unwrap();
+            stco.chunk_offsets = chunk_offsets.into_iter().map(|offset| offset as u32).collect();
+        } else {
+            self.box_list.boxes[index] = Box::new(Co64Box {
+                header: BoxHeader::new(b"co64"),
+                version: 0,
+                flags: [0; 3],
+                chunk_offsets
+            });
+        }
+        Ok(())
+    }
 }
 
 impl Atom for StblBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        self.box_list.get_size()
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"stbl").unwrap();
-        self.box_list.write(wtr);
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        self.box_list.write(wtr)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -0,0 +1,73 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct StcoBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub chunk_offsets: Vec<u32>
+}
+
+impl StcoBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let entry_count = rdr.read_u32::<BigEndian>()?;
+        let mut chunk_offsets = vec![];
+        for _i in 0..entry_count {
+            chunk_offsets.push(rdr.read_u32::<BigEndian>()?);
+        }
+
+        Ok(StcoBox {
+            header,
+            version,
+            flags,
+            chunk_offsets
+        })
+    }
+}
+
+impl Atom for StcoBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + 4 * self.chunk_offsets.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+
+        wtr.write_u32::<BigEndian>(self.chunk_offsets.len().try_into()?)?;
+        for chunk_offset in &self.chunk_offsets {
+            wtr.write_u32::<BigEndian>(*chunk_offset)?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for StcoBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StcoBox")
+            .field("chunk_offsets", &self.chunk_offsets)
+            .finish()
+    }
+}
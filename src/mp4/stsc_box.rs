@@ -0,0 +1,107 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::{Error, Result};
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct StscBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub first_chunks: Vec<u32>,     // 1-based, each run lasts until the next first_chunk
+    pub samples_per_chunks: Vec<u32>,
+    pub sample_description_indices: Vec<u32>
+}
+
+impl StscBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let entry_count = rdr.read_u32::<BigEndian>()?;
+        let mut first_chunks = vec![];
+        let mut samples_per_chunks = vec![];
+        let mut sample_description_indices = vec![];
+        for _i in 0..entry_count {
+            first_chunks.push(rdr.read_u32::<BigEndian>()?);
+            samples_per_chunks.push(rdr.read_u32::<BigEndian>()?);
+            sample_description_indices.push(rdr.read_u32::<BigEndian>()?);
+        }
+
+        Ok(StscBox {
+            header,
+            version,
+            flags,
+            first_chunks,
+            samples_per_chunks,
+            sample_description_indices
+        })
+    }
+
+    // number of samples and sample description index of each of chunk_count chunks
+    pub fn get_chunk_layout(&self, chunk_count: usize) -> Result<Vec<(u32, u32)>> {
+        let mut chunks = Vec::with_capacity(chunk_count);
+        for entry_index in 0..self.first_chunks.len() {
+            let first_chunk = self.first_chunks[entry_index];
+            if first_chunk == 0 || first_chunk as usize - 1 != chunks.len() {
+                return Err(Error::spec_violation(format!("stsc first_chunk {} out of order", first_chunk)));
+            }
+            let next_first_chunk = match self.first_chunks.get(entry_index + 1) {
+                Some(&next_first_chunk) => next_first_chunk as usize,
+                None => chunk_count + 1
+            };
+            while chunks.len() + 1 < next_first_chunk && chunks.len() < chunk_count {
+                chunks.push((self.samples_per_chunks[entry_index], self.sample_description_indices[entry_index]));
+            }
+        }
+        if chunks.len() != chunk_count {
+            return Err(Error::spec_violation(format!("stsc describes {} chunks but there are {}", chunks.len(), chunk_count)));
+        }
+        Ok(chunks)
+    }
+}
+
+impl Atom for StscBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + 12 * self.first_chunks.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+
+        wtr.write_u32::<BigEndian>(self.first_chunks.len().try_into()?)?;
+        for entry_index in 0..self.first_chunks.len() {
+            wtr.write_u32::<BigEndian>(self.first_chunks[entry_index])?;
+            wtr.write_u32::<BigEndian>(self.samples_per_chunks[entry_index])?;
+            wtr.write_u32::<BigEndian>(self.sample_description_indices[entry_index])?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for StscBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StscBox")
+            .field("first_chunks", &self.first_chunks)
+            .field("samples_per_chunks", &self.samples_per_chunks)
+            .field("sample_description_indices", &self.sample_description_indices)
+            .finish()
+    }
+}
@@ -1,46 +1,51 @@
-use std::{any::Any, fmt, fs::File, io::{self, Read, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use super::{atom::Atom, box_list::BoxList};
+use crate::error::{Error, Result};
+
+use super::{atom::Atom, box_header::BoxHeader, box_list::BoxList};
 
 pub struct StsdBox {
+    pub header: BoxHeader,
     pub version: u8,
     pub flags: [u8; 3],
-    pub box_list: BoxList,
-    pub payload_size: u64
+    pub box_list: BoxList
 }
 
 impl StsdBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let box_list_len = len.checked_sub(8).ok_or_else(|| Error::spec_violation(format!("stsd payload of {} bytes", len)))?;
         let version = rdr.read_u8()?;
         let mut flags: [u8; 3] = [0; 3];
-        rdr.read(&mut flags).unwrap();
+        rdr.read_exact(&mut flags)?;
 
         let _entry_count = rdr.read_u32::<BigEndian>()?;
-        let box_list = BoxList::read(rdr, len - 8);
+        let box_list = BoxList::read(rdr, box_list_len)?;
         Ok(StsdBox {
+            header,
             version,
             flags,
-            box_list,
-            payload_size: len
+            box_list
         })
     }
 }
 
 impl Atom for StsdBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + self.box_list.get_size()?)
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"stsd").unwrap();
-        wtr.write_u8(self.version).unwrap();
-        wtr.write_all(&self.flags).unwrap();
-        wtr.write_u32::<BigEndian>(self.box_list.boxes.len().try_into().unwrap()).unwrap();
-        self.box_list.write(wtr);
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+        wtr.write_u32::<BigEndian>(self.box_list.boxes.len().try_into()?)?;
+        self.box_list.write(wtr)
     }
 
     fn as_any(&self) -> &dyn Any {
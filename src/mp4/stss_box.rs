@@ -0,0 +1,73 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct StssBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub sample_numbers: Vec<u32>    // 1-based, in increasing order
+}
+
+impl StssBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let entry_count = rdr.read_u32::<BigEndian>()?;
+        let mut sample_numbers = vec![];
+        for _i in 0..entry_count {
+            sample_numbers.push(rdr.read_u32::<BigEndian>()?);
+        }
+
+        Ok(StssBox {
+            header,
+            version,
+            flags,
+            sample_numbers
+        })
+    }
+}
+
+impl Atom for StssBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + 4 * self.sample_numbers.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+
+        wtr.write_u32::<BigEndian>(self.sample_numbers.len().try_into()?)?;
+        for sample_number in &self.sample_numbers {
+            wtr.write_u32::<BigEndian>(*sample_number)?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for StssBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StssBox")
+            .field("sample_numbers", &self.sample_numbers)
+            .finish()
+    }
+}
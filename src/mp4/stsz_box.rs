@@ -1,62 +1,89 @@
-use std::{any::Any, fmt, fs::File, io::{self, Read, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use super::atom::Atom;
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
 
 pub struct StszBox {
+    pub header: BoxHeader,
     pub version: u8,
     pub flags: [u8; 3],
     pub sample_size: u32,
     pub sample_count: u32,
-    pub entry_sizes: Vec<u32>,
-    pub payload_size: u64
+    pub entry_sizes: Vec<u32>
 }
 
 impl StszBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
         let version = rdr.read_u8()?;
         let mut flags: [u8; 3] = [0; 3];
-        rdr.read(&mut flags).unwrap();
+        rdr.read_exact(&mut flags)?;
 
-        let sample_size = rdr.read_u32::<BigEndian>().unwrap();
-        let sample_count = rdr.read_u32::<BigEndian>().unwrap();
+        let sample_size = rdr.read_u32::<BigEndian>()?;
+        let sample_count = rdr.read_u32::<BigEndian>()?;
         let mut entry_sizes = vec![];
-        for _i in 0..sample_count {
-            let entry_size = rdr.read_u32::<BigEndian>().unwrap();
+        // entry sizes are only present when samples have different sizes
+        for _i in 0..if sample_size == 0 { sample_count } else { 0 } {
+            let entry_size = rdr.read_u32::<BigEndian>()?;
             entry_sizes.push(entry_size);
         }
-        // recompute size from mdat to sample unit
-        // entry_sizes[0] = 429;
-        
         Ok(StszBox {
+            header,
             version,
             flags,
             sample_size,
             sample_count,
-            entry_sizes,
-            payload_size: len
+            entry_sizes
         })
     }
+
+    pub fn get_sample_sizes(&self) -> Vec<u32> {
+        if self.sample_size == 0 {
+            self.entry_sizes.clone()
+        } else {
+            vec![self.sample_size; self.sample_count as usize]
+        }
+    }
+
+    // keeps a constant sample_size if every sample still has that size
+    pub fn set_sample_sizes(&mut self, sample_sizes: Vec<u32>) -> Result<()> {
+        self.sample_count = sample_sizes.len().try_into()?;
+        if self.sample_size != 0 && sample_sizes.iter().all(|&size| size == self.sample_size) {
+            self.entry_sizes = vec![];
+        } else {
+            self.sample_size = 0;
+            self.entry_sizes = sample_sizes;
+        }
+        Ok(())
+    }
 }
 
 impl Atom for StszBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"stsz").unwrap();
-        wtr.write_u8(self.version).unwrap();
-        wtr.write_all(&self.flags).unwrap();
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(12 + 4 * self.entry_sizes.len() as u64)
+    }
 
-        wtr.write_u32::<BigEndian>(self.sample_size.try_into().unwrap()).unwrap();
-        wtr.write_u32::<BigEndian>(self.sample_count.try_into().unwrap()).unwrap();
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+
+        wtr.write_u32::<BigEndian>(self.sample_size)?;
+        if self.sample_size == 0 {
+            wtr.write_u32::<BigEndian>(self.entry_sizes.len().try_into()?)?;
+        } else {
+            wtr.write_u32::<BigEndian>(self.sample_count)?;
+        }
         for sample_size in &self.entry_sizes {
-            wtr.write_u32::<BigEndian>(*sample_size).unwrap();
+            wtr.write_u32::<BigEndian>(*sample_size)?;
         }
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
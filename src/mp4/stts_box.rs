@@ -0,0 +1,92 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct SttsBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub sample_counts: Vec<u32>,
+    pub sample_deltas: Vec<u32>
+}
+
+impl SttsBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let entry_count = rdr.read_u32::<BigEndian>()?;
+        let mut sample_counts = vec![];
+        let mut sample_deltas = vec![];
+        for _i in 0..entry_count {
+            sample_counts.push(rdr.read_u32::<BigEndian>()?);
+            sample_deltas.push(rdr.read_u32::<BigEndian>()?);
+        }
+
+        Ok(SttsBox {
+            header,
+            version,
+            flags,
+            sample_counts,
+            sample_deltas
+        })
+    }
+
+    // decoding time of each sample, the first sample decodes at 0
+    pub fn get_decoding_times(&self) -> Vec<u64> {
+        let mut decoding_times = vec![];
+        let mut decoding_time = 0;
+        for (&sample_count, &sample_delta) in self.sample_counts.iter().zip(&self.sample_deltas) {
+            for _i in 0..sample_count {
+                decoding_times.push(decoding_time);
+                decoding_time += u64::from(sample_delta);
+            }
+        }
+        decoding_times
+    }
+}
+
+impl Atom for SttsBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(8 + 8 * self.sample_counts.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+
+        wtr.write_u32::<BigEndian>(self.sample_counts.len().try_into()?)?;
+        for (&sample_count, &sample_delta) in self.sample_counts.iter().zip(&self.sample_deltas) {
+            wtr.write_u32::<BigEndian>(sample_count)?;
+            wtr.write_u32::<BigEndian>(sample_delta)?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for SttsBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SttsBox")
+            .field("sample_counts", &self.sample_counts)
+            .field("sample_deltas", &self.sample_deltas)
+            .finish()
+    }
+}
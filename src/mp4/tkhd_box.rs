@@ -0,0 +1,135 @@
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::Result;
+
+use super::{atom::Atom, box_header::BoxHeader};
+
+pub struct TkhdBox {
+    pub header: BoxHeader,
+    pub version: u8,
+    pub flags: [u8; 3],
+    pub creation_time: u64,
+    pub modification_time: u64,
+    pub track_id: u32,
+    pub duration: u64,          // in the timescale of mvhd
+    pub layer: i16,
+    pub alternate_group: i16,
+    pub volume: i16,            // 8.8 fixed point
+    pub matrix: [i32; 9],
+    pub width: u32,             // 16.16 fixed point
+    pub height: u32             // 16.16 fixed point
+}
+
+impl TkhdBox {
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, _len: u64) -> Result<Self> {
+        let version = rdr.read_u8()?;
+        let mut flags: [u8; 3] = [0; 3];
+        rdr.read_exact(&mut flags)?;
+
+        let creation_time: u64;
+        let modification_time: u64;
+        let track_id: u32;
+        let duration: u64;
+        if version == 1 {
+            creation_time = rdr.read_u64::<BigEndian>()?;
+            modification_time = rdr.read_u64::<BigEndian>()?;
+            track_id = rdr.read_u32::<BigEndian>()?;
+            let _reserved = rdr.read_u32::<BigEndian>()?;
+            duration = rdr.read_u64::<BigEndian>()?;
+        } else {
+            creation_time = rdr.read_u32::<BigEndian>()?.into();
+            modification_time = rdr.read_u32::<BigEndian>()?.into();
+            track_id = rdr.read_u32::<BigEndian>()?;
+            let _reserved = rdr.read_u32::<BigEndian>()?;
+            duration = rdr.read_u32::<BigEndian>()?.into();
+        }
+        let _reserved = rdr.read_u64::<BigEndian>()?;
+        let layer = rdr.read_i16::<BigEndian>()?;
+        let alternate_group = rdr.read_i16::<BigEndian>()?;
+        let volume = rdr.read_i16::<BigEndian>()?;
+        let _reserved = rdr.read_u16::<BigEndian>()?;
+        let mut matrix: [i32; 9] = [0; 9];
+        for value in &mut matrix {
+            *value = rdr.read_i32::<BigEndian>()?;
+        }
+        let width = rdr.read_u32::<BigEndian>()?;
+        let height = rdr.read_u32::<BigEndian>()?;
+
+        Ok(TkhdBox {
+            header,
+            version,
+            flags,
+            creation_time,
+            modification_time,
+            track_id,
+            duration,
+            layer,
+            alternate_group,
+            volume,
+            matrix,
+            width,
+            height
+        })
+    }
+}
+
+impl Atom for TkhdBox {
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(4 + if self.version == 1 { 32 } else { 20 } + 60)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_u8(self.version)?;
+        wtr.write_all(&self.flags)?;
+        if self.version == 1 {
+            wtr.write_u64::<BigEndian>(self.creation_time)?;
+            wtr.write_u64::<BigEndian>(self.modification_time)?;
+            wtr.write_u32::<BigEndian>(self.track_id)?;
+            wtr.write_u32::<BigEndian>(0)?;         // reserved
+            wtr.write_u64::<BigEndian>(self.duration)?;
+        } else {
+            wtr.write_u32::<BigEndian>(self.creation_time.try_into()?)?;
+            wtr.write_u32::<BigEndian>(self.modification_time.try_into()?)?;
+            wtr.write_u32::<BigEndian>(self.track_id)?;
+            wtr.write_u32::<BigEndian>(0)?;         // reserved
+            wtr.write_u32::<BigEndian>(self.duration.try_into()?)?;
+        }
+        wtr.write_u64::<BigEndian>(0)?;             // reserved
+        wtr.write_i16::<BigEndian>(self.layer)?;
+        wtr.write_i16::<BigEndian>(self.alternate_group)?;
+        wtr.write_i16::<BigEndian>(self.volume)?;
+        wtr.write_u16::<BigEndian>(0)?;             // reserved
+        for value in &self.matrix {
+            wtr.write_i32::<BigEndian>(*value)?;
+        }
+        wtr.write_u32::<BigEndian>(self.width)?;
+        wtr.write_u32::<BigEndian>(self.height)?;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Debug for TkhdBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TkhdBox")
+            .field("flags", &self.flags)
+            .field("track_id", &self.track_id)
+            .field("duration", &self.duration)
+            .field("width", &(self.width >> 16))
+            .field("height", &(self.height >> 16))
+            .finish()
+    }
+}
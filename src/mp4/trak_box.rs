@@ -1,34 +1,112 @@
-use std::{any::Any, fmt, fs::File, io::{self, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::{Error, Result};
 
-use super::{atom::Atom, box_list::BoxList};
+use super::{atom::Atom, avc1_box::Avc1Box, avc_decoder_configuration_record::AvcDecoderConfigurationRecord, avcc_box::AvccBox, box_header::BoxHeader, box_list::BoxList, ctts_box::CttsBox, hdlr_box::HdlrBox, mdhd_box::MdhdBox, mdia_box::MdiaBox, minf_box::MinfBox, sample_table::SampleTable, sample_time::SampleTime, stbl_box::StblBox, stsd_box::StsdBox, stts_box::SttsBox};
 
 pub struct TrakBox {
-    pub box_list: BoxList,
-    pub payload_size: u64
+    pub header: BoxHeader,
+    pub box_list: BoxList
 }
 
 impl TrakBox {
-    pub fn read(rdr: &mut File, len: u64) -> io::Result<Self> {
-        let box_list = BoxList::read(rdr, len);
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let box_list = BoxList::read(rdr, len)?;
         Ok(TrakBox {
-            box_list,
-            payload_size: len
+            header,
+            box_list
         })
     }
+
+    pub fn mdhd(&self) -> Option<&MdhdBox> {
+        self.box_list.find::<MdiaBox>()?
+            .box_list.find::<MdhdBox>()
+    }
+
+    pub fn hdlr(&self) -> Option<&HdlrBox> {
+        self.box_list.find::<MdiaBox>()?
+            .box_list.find::<HdlrBox>()
+    }
+
+    pub fn stbl(&self) -> Option<&StblBox> {
+        self.box_list.find::<MdiaBox>()?
+            .box_list.find::<MinfBox>()?
+            .box_list.find::<StblBox>()
+    }
+
+    pub fn stbl_mut(&mut self) -> Option<&mut StblBox> {
+        self.box_list.find_mut::<MdiaBox>()?
+            .box_list.find_mut::<MinfBox>()?
+            .box_list.find_mut::<StblBox>()
+    }
+
+    // avcC configuration of each sample entry in stsd, None for sample entries other than avc1
+    pub fn get_avc_configurations(&self) -> Vec<Option<&AvcDecoderConfigurationRecord>> {
+        let stsd = match self.stbl().and_then(|stbl| stbl.box_list.find::<StsdBox>()) {
+            Some(stsd) => stsd,
+            None => return vec![]
+        };
+        stsd.box_list.boxes.iter()
+            .map(|sample_entry| {
+                let avcc = sample_entry.as_any().downcast_ref::<Avc1Box>()?.box_list.find::<AvccBox>()?;
+                Some(&avcc.avc_decoder_configuration_record)
+            })
+            .collect()
+    }
+
+    pub fn get_avc_configurations_mut(&mut self) -> Vec<Option<&mut AvcDecoderConfigurationRecord>> {
+        let stsd = match self.stbl_mut().and_then(|stbl| stbl.box_list.find_mut::<StsdBox>()) {
+            Some(stsd) => stsd,
+            None => return vec![]
+        };
+        stsd.box_list.boxes.iter_mut()
+            .map(|sample_entry| {
+                let avcc = sample_entry.as_any_mut().downcast_mut::<Avc1Box>()?.box_list.find_mut::<AvccBox>()?;
+                Some(&mut avcc.avc_decoder_configuration_record)
+            })
+            .collect()
+    }
+
+    pub fn get_sample_table(&self) -> Result<SampleTable> {
+        SampleTable::new(self)
+    }
+
+    // decoding and presentation time of each sample, in media timescale units
+    pub fn get_sample_times(&self) -> Result<Vec<SampleTime>> {
+        let stbl = self.stbl().ok_or_else(|| Error::spec_violation("trak without stbl"))?;
+        let stts = stbl.box_list.find::<SttsBox>().ok_or_else(|| Error::spec_violation("stbl without stts"))?;
+        let decoding_times = stts.get_decoding_times();
+        let composition_offsets = match stbl.box_list.find::<CttsBox>() {
+            Some(ctts) => ctts.get_composition_offsets(),
+            None => vec![0; decoding_times.len()]
+        };
+        if composition_offsets.len() != decoding_times.len() {
+            return Err(Error::spec_violation(format!("ctts covers {} samples but stts covers {}", composition_offsets.len(), decoding_times.len())));
+        }
+
+        let mut sample_times = vec![];
+        for (dts, composition_offset) in decoding_times.into_iter().zip(composition_offsets) {
+            sample_times.push(SampleTime {
+                dts,
+                pts: i64::try_from(dts)? + composition_offset
+            });
+        }
+        Ok(sample_times)
+    }
 }
 
 impl Atom for TrakBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
+    }
+
+    fn get_payload_size(&self) -> Result<u64> {
+        self.box_list.get_size()
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.payload_size;
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        wtr.write_all(b"trak").unwrap();
-        self.box_list.write(wtr);
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        self.box_list.write(wtr)
     }
 
     fn as_any(&self) -> &dyn Any {
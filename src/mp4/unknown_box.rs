@@ -1,38 +1,39 @@
-use std::{any::Any, fmt, fs::File, io::{self, Read, Seek, Write}};
+use std::{any::Any, fmt, io::{Read, Seek, Write}};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use crate::error::Result;
 
-use super::{atom::Atom, four_cc::FourCC};
+use super::{atom::Atom, box_header::BoxHeader};
 
 pub struct UnknownBox {
-    pub boxtype: FourCC,
-    pub remaining: Vec<u8>,
-    pub payload_size: u64
+    pub header: BoxHeader,
+    pub remaining: Vec<u8>
 }
 
 impl UnknownBox {
-    pub fn read(rdr: &mut (impl Read + Seek), len: u64, boxtype: FourCC) -> io::Result<Self> {
-        let mut remaining = vec![0u8; len.try_into().unwrap()];
-        rdr.read_exact(&mut remaining).unwrap();
+    pub fn read(rdr: &mut (impl Read + Seek), header: BoxHeader, len: u64) -> Result<Self> {
+        let mut remaining = vec![0u8; len.try_into()?];
+        rdr.read_exact(&mut remaining)?;
 
         Ok(UnknownBox {
-            boxtype,
-            remaining,
-            payload_size: len
+            header,
+            remaining
         })
     }
 }
 
 impl Atom for UnknownBox {
-    fn get_payload_size(&self) -> u64 {
-        self.payload_size
+    fn get_header(&self) -> &BoxHeader {
+        &self.header
     }
 
-    fn write(&self, wtr: &mut File) {
-        let total_size = 8 + self.remaining.len();
-        wtr.write_u32::<BigEndian>(total_size.try_into().unwrap()).unwrap();
-        self.boxtype.write(wtr);
-        wtr.write_all(&self.remaining).unwrap();
+    fn get_payload_size(&self) -> Result<u64> {
+        Ok(self.remaining.len() as u64)
+    }
+
+    fn write(&self, wtr: &mut dyn Write) -> Result<()> {
+        self.header.write(wtr, self.get_payload_size()?)?;
+        wtr.write_all(&self.remaining)?;
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -47,7 +48,7 @@ impl Atom for UnknownBox {
 impl fmt::Debug for UnknownBox {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("UnknownBox")
-            .field("boxtype", &self.boxtype)
+            .field("boxtype", &self.header.boxtype)
             .finish()
     }
 }
@@ -0,0 +1,33 @@
+// fixtures shared by the unit tests
+
+// Baseline 320x240 at 29.97 fps: SPS 0 with 4-bit frame_num, pic_order_cnt_type 2, pic_struct_present_flag
+// and no HRD parameters
+pub const BASELINE_SPS: [u8; 16] = [0x42, 0x00, 0x1e, 0xda, 0x05, 0x07, 0xe8, 0x40, 0x00, 0x00, 0xfa, 0x40, 0x00, 0x3a, 0x98, 0x25];
+
+// bytes of a bit string, padded with the rbsp trailing bits
+pub fn to_bytes(bits: &str) -> Vec<u8> {
+    let mut bits: String = bits.chars().filter(|c| !c.is_whitespace()).collect();
+    bits.push('1');
+    while !bits.len().is_multiple_of(8) {
+        bits.push('0');
+    }
+    (0..bits.len()).step_by(8).map(|i| u8::from_str_radix(&bits[i..i + 8], 2).unwrap()).collect()
+}
+
+// NAL unit with a 4-byte start code, from a header and a bit string
+pub fn to_annex_b_unit(header: u8, bits: &str) -> Vec<u8> {
+    let mut bytes = vec![0, 0, 0, 1, header];
+    bytes.extend(to_bytes(bits));
+    bytes
+}
+
+// bit string of an ue(v) value
+pub fn ue(value: u64) -> String {
+    let bits = format!("{:b}", value + 1);
+    format!("{}{}", "0".repeat(bits.len() - 1), bits)
+}
+
+// bit string of an se(v) value
+pub fn se(value: i64) -> String {
+    ue(if value > 0 { 2 * value.unsigned_abs() - 1 } else { 2 * value.unsigned_abs() })
+}